@@ -37,10 +37,6 @@ impl System for PlayerController {
                 .expect("Failed to get DeltaTime resource")
                 .get_delta_time() as f32;
 
-            let physics_info = entities_and_components
-                .get_resource::<RapierPhysicsInfo>()
-                .expect("Failed to get PhysicsInfo resource");
-
             let input = entities_and_components.get_resource::<Input>().unwrap();
 
             if input.get_key_state(KeyCode::A) == KeyState::Held {
@@ -51,30 +47,16 @@ impl System for PlayerController {
                 normalized_dir[0] += 1.0;
             }
 
-            let is_ground_check = |collider_handle: ColliderHandle, _: &Collider| {
-                let entity = physics_info
-                    .get_associated_entity_with_collider_handle(collider_handle.into())
-                    .expect("Failed to get associated entity with collider handle");
-
-                entities_and_components
-                    .try_get_components::<(Ground,)>(entity)
-                    .0
-                    .is_some()
-            };
+            let is_ground_filter = EntityFilter::new().with_component::<Ground>();
 
-            let is_ground_filter = QueryFilter {
-                predicate: Some(&is_ground_check),
-                ..Default::default()
-            };
-
-            let intersection = physics_info.cast_ray(
+            let intersection = EntityQuery::new(entities_and_components).cast_ray(
                 &Ray::new(
                     vector![player_x as f32, player_y as f32 - 5.01].into(),
                     vector![0.0, -1.0],
                 ),
                 Real::MAX,
                 true,
-                is_ground_filter,
+                &is_ground_filter,
             );
 
             if input.get_key_state(KeyCode::Space) == KeyState::Pressed && intersection.is_some() {
@@ -86,17 +68,17 @@ impl System for PlayerController {
             }
         }
 
-        if let (Some(_), Some(_), Some(rigid_body)) = entities_and_components
-            .try_get_components_mut::<(Player, Transform, RigidBody)>(player_entity)
-        {
-            rigid_body.apply_impulse(
-                vector![
-                    normalized_dir[0] * self.speed * delta_time,
-                    normalized_dir[1] * self.jump_force,
-                ],
-                true,
-            );
-        }
+        let physics_info = entities_and_components
+            .get_resource_mut::<RapierPhysicsInfo>()
+            .expect("Failed to get PhysicsInfo resource");
+
+        physics_info.apply_impulse(
+            player_entity,
+            vector![
+                normalized_dir[0] * self.speed * delta_time,
+                normalized_dir[1] * self.jump_force,
+            ],
+        );
     }
 }
 
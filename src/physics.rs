@@ -1,6 +1,18 @@
 use crate::Scene;
 
+pub mod area_effectors;
+pub mod collider_generation;
+pub mod explosions;
+mod line_of_sight;
+mod physics_commands;
+pub mod physics_debugging;
+pub mod physics_hooks;
+pub mod physics_queries;
+pub mod physics_settings;
+pub mod physics_snapshot;
 pub mod physics_system;
+pub mod physics_worlds;
+mod shape_scaling;
 pub use rapier2d;
 use ABC_ECS::World;
 
@@ -12,3 +24,14 @@ pub fn add_default_physics_systems(world: &mut World) {
         physics_system::RapierPhysicsSystem::new(&mut world.entities_and_components);
     world.add_system(physics_system);
 }
+
+/// a world with only the physics system in it, for the tests of the physics modules
+#[cfg(test)]
+pub(crate) fn physics_test_world() -> World {
+    let mut world = World::new();
+    world
+        .entities_and_components
+        .add_resource(crate::DeltaTime::new());
+    add_default_physics_systems(&mut world);
+    world
+}
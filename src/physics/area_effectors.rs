@@ -0,0 +1,621 @@
+use fxhash::FxHashSet;
+use rapier2d::prelude::*;
+use ABC_ECS::EntitiesAndComponents;
+use ABC_ECS::Entity;
+
+use crate::physics::physics_commands::SavedUserForce;
+use crate::physics::physics_system::{ColliderHandle, RapierPhysicsInfo, SimulationState};
+
+/// What an AreaEffector does to the dynamic bodies inside of it.
+/// like gravity and the RigidBody component, these are in meters and not in pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AreaEffect {
+    /// pushes everything inside in one direction with the given force
+    Wind { force: Vector<Real> },
+    /// pulls everything inside towards the center of the effector, a negative strength pushes things away
+    /// the force is strength / distance^falloff, so a falloff of 0 is the same everywhere and 2 is like real gravity
+    Point { strength: Real, falloff: Real },
+    /// replaces the gravity of the physics world for everything inside, gravity scales still apply
+    /// a strength below 1 blends between the world's gravity and this one
+    Gravity { gravity: Vector<Real> },
+    /// makes things float, the surface is the top of the effector's collider
+    /// bodies less dense than the fluid float, the drag slows down anything that is in the fluid
+    Buoyancy {
+        fluid_density: Real,
+        linear_drag: Real,
+        angular_drag: Real,
+    },
+}
+
+/// A component that applies an AreaEffect to every dynamic body touching the collider on the same entity.
+/// the collider should be a sensor, otherwise things will bounce off of it instead of going inside
+pub struct AreaEffector {
+    effect: AreaEffect,
+    strength: Real,
+    strength_over_time: Option<Box<dyn Fn(f64) -> Real>>,
+}
+
+impl AreaEffector {
+    pub fn new(effect: AreaEffect) -> Self {
+        Self {
+            effect,
+            strength: 1.0,
+            strength_over_time: None,
+        }
+    }
+
+    pub fn wind(force: Vector<Real>) -> Self {
+        Self::new(AreaEffect::Wind { force })
+    }
+
+    pub fn point(strength: Real, falloff: Real) -> Self {
+        Self::new(AreaEffect::Point { strength, falloff })
+    }
+
+    pub fn gravity(gravity: Vector<Real>) -> Self {
+        Self::new(AreaEffect::Gravity { gravity })
+    }
+
+    pub fn buoyancy(fluid_density: Real, linear_drag: Real, angular_drag: Real) -> Self {
+        Self::new(AreaEffect::Buoyancy {
+            fluid_density,
+            linear_drag,
+            angular_drag,
+        })
+    }
+
+    pub fn get_effect(&self) -> AreaEffect {
+        self.effect
+    }
+
+    pub fn set_effect(&mut self, effect: AreaEffect) {
+        self.effect = effect;
+    }
+
+    /// multiplies the effect, 1.0 by default
+    pub fn get_strength(&self) -> Real {
+        self.strength
+    }
+
+    /// multiplies the effect, 0.0 turns it off
+    pub fn set_strength(&mut self, strength: Real) {
+        self.strength = strength;
+    }
+
+    pub fn with_strength(mut self, strength: Real) -> Self {
+        self.strength = strength;
+        self
+    }
+
+    /// the strength is multiplied by the result of the function, which is given the time in seconds
+    /// that the physics world has simulated, this is useful for gusts of wind or waves
+    pub fn set_strength_over_time(&mut self, strength_over_time: impl Fn(f64) -> Real + 'static) {
+        self.strength_over_time = Some(Box::new(strength_over_time));
+    }
+
+    pub fn with_strength_over_time(
+        mut self,
+        strength_over_time: impl Fn(f64) -> Real + 'static,
+    ) -> Self {
+        self.set_strength_over_time(strength_over_time);
+        self
+    }
+
+    /// the strength at the given simulated time
+    pub fn get_strength_at(&self, time: f64) -> Real {
+        match &self.strength_over_time {
+            Some(strength_over_time) => self.strength * strength_over_time(time),
+            None => self.strength,
+        }
+    }
+}
+
+/// a force added to a body for one step
+struct EffectorForce {
+    rigid_body: RigidBodyHandle,
+    force: Vector<Real>,
+    torque: Real,
+    // where the force pushes, the center of mass if None
+    point: Option<Point<Real>>,
+}
+
+/// how much of the mass of the body comes from the collider, the shares of all of its colliders add up to 1
+fn mass_share(collider_set: &ColliderSet, rigid_body: &RigidBody, collider: &Collider) -> Real {
+    let colliders = rigid_body.colliders();
+    let total_mass: Real = colliders
+        .iter()
+        .filter_map(|handle| collider_set.get(*handle))
+        .map(|collider| collider.mass())
+        .sum();
+
+    if total_mass > 0.0 {
+        collider.mass() / total_mass
+    } else {
+        1.0 / colliders.len().max(1) as Real
+    }
+}
+
+/// the area of the part of the shape below the surface and where its center is, None if nothing is below it
+fn submerged_part(
+    shape: &dyn Shape,
+    position: &Isometry<Real>,
+    surface: Real,
+) -> Option<(Real, Point<Real>)> {
+    if let Some(ball) = shape.as_ball() {
+        return submerged_part_of_ball(ball.radius, position.translation.vector.into(), surface);
+    }
+
+    if let Some(compound) = shape.as_compound() {
+        let mut total_area = 0.0;
+        let mut weighted_center = Vector::zeros();
+        for (part_position, part) in compound.shapes() {
+            let part_position = position * part_position;
+            if let Some((area, center)) = submerged_part(&**part, &part_position, surface) {
+                total_area += area;
+                weighted_center += center.coords * area;
+            }
+        }
+
+        return (total_area > 0.0).then(|| (total_area, (weighted_center / total_area).into()));
+    }
+
+    let local_points = if let Some(cuboid) = shape.as_cuboid() {
+        cuboid.to_polyline()
+    } else if let Some(round_cuboid) = shape.as_round_cuboid() {
+        round_cuboid.inner_shape.to_polyline()
+    } else if let Some(polygon) = shape.as_convex_polygon() {
+        polygon.points().to_vec()
+    } else if let Some(triangle) = shape.as_triangle() {
+        vec![triangle.a, triangle.b, triangle.c]
+    } else {
+        // anything else is treated like its bounding box
+        let aabb = shape.compute_aabb(position);
+        let height = (aabb.maxs.y - aabb.mins.y).max(Real::EPSILON);
+        let submerged = ((surface - aabb.mins.y) / height).clamp(0.0, 1.0);
+        let area = shape.mass_properties(1.0).mass() * submerged;
+        let center = point![
+            (aabb.mins.x + aabb.maxs.x) / 2.0,
+            (aabb.mins.y + aabb.maxs.y.min(surface)) / 2.0
+        ];
+        return (area > 0.0).then_some((area, center));
+    };
+
+    let points = local_points
+        .iter()
+        .map(|point| position * point)
+        .collect::<Vec<Point<Real>>>();
+    polygon_area_and_center(&clip_below(&points, surface))
+}
+
+/// the circular segment of the ball below the surface
+fn submerged_part_of_ball(
+    radius: Real,
+    center: Point<Real>,
+    surface: Real,
+) -> Option<(Real, Point<Real>)> {
+    let depth = (surface - (center.y - radius)).clamp(0.0, 2.0 * radius);
+    if depth <= 0.0 {
+        return None;
+    }
+    if depth >= 2.0 * radius {
+        return Some((std::f32::consts::PI as Real * radius * radius, center));
+    }
+
+    // how far the surface is above the center, negative if it is below it
+    let surface_height = depth - radius;
+    let half_chord_squared = radius * radius - surface_height * surface_height;
+    let area = radius * radius * (-surface_height / radius).acos()
+        + surface_height * half_chord_squared.sqrt();
+    let center_offset = 2.0 * half_chord_squared.powf(1.5) / (3.0 * area);
+
+    Some((area, point![center.x, center.y - center_offset]))
+}
+
+/// the part of the polygon below the surface
+fn clip_below(points: &[Point<Real>], surface: Real) -> Vec<Point<Real>> {
+    let mut clipped = vec![];
+
+    for (index, current) in points.iter().enumerate() {
+        let next = points[(index + 1) % points.len()];
+        let current_below = current.y <= surface;
+        let next_below = next.y <= surface;
+
+        if current_below {
+            clipped.push(*current);
+        }
+        if current_below != next_below {
+            let t = (surface - current.y) / (next.y - current.y);
+            clipped.push(current + (next - current) * t);
+        }
+    }
+
+    clipped
+}
+
+fn polygon_area_and_center(points: &[Point<Real>]) -> Option<(Real, Point<Real>)> {
+    let mut twice_area = 0.0;
+    let mut weighted_center = Vector::zeros();
+
+    for (index, current) in points.iter().enumerate() {
+        let next = points[(index + 1) % points.len()];
+        let cross = current.x * next.y - next.x * current.y;
+        twice_area += cross;
+        weighted_center += (current.coords + next.coords) * cross;
+    }
+
+    if twice_area.abs() <= Real::EPSILON {
+        return None;
+    }
+
+    let center = weighted_center / (3.0 * twice_area);
+    Some(((twice_area / 2.0).abs(), center.into()))
+}
+
+/// adds the forces of every area effector to the bodies inside of them
+/// the returned user forces must be put back with restore_user_forces after the step
+pub(crate) fn apply_area_effector_forces(
+    physics_info: &RapierPhysicsInfo,
+    state: &mut SimulationState,
+    simulated_time: f64,
+    entities_and_components: &EntitiesAndComponents,
+) -> Vec<SavedUserForce> {
+    let mut effector_forces = vec![];
+
+    let mut effector_entities = entities_and_components
+        .get_entities_with_component::<AreaEffector>()
+        .copied()
+        .collect::<Vec<Entity>>();
+    // forces are added up in this order, and float addition isn't associative
+    if physics_info.deterministic {
+        physics_info.sort_entities_deterministically(&mut effector_entities);
+    }
+
+    for effector_entity in effector_entities {
+        let (effector, effector_collider) = entities_and_components
+            .try_get_components::<(AreaEffector, ColliderHandle)>(effector_entity);
+        let (Some(effector), Some(effector_handle)) = (effector, effector_collider) else {
+            continue;
+        };
+        // the handle might belong to a collider in another physics world
+        if physics_info.collider_handle_map.get(effector_handle) != Some(&effector_entity) {
+            continue;
+        }
+        let effector_handle = effector_handle.0;
+        let Some(effector_collider) = state.collider_set.get(effector_handle) else {
+            continue;
+        };
+
+        let strength = effector.get_strength_at(simulated_time);
+        if strength == 0.0 {
+            continue;
+        }
+
+        let effector_aabb = effector_collider.compute_aabb();
+        let effector_center = effector_collider.position().translation.vector;
+
+        // a body with more than one collider inside is only pushed once, except by water which pushes each collider
+        let mut affected_bodies = FxHashSet::default();
+
+        for (collider1, collider2, intersecting) in
+            state.narrow_phase.intersection_pairs_with(effector_handle)
+        {
+            if !intersecting {
+                continue;
+            }
+
+            let other_handle = if collider1 == effector_handle {
+                collider2
+            } else {
+                collider1
+            };
+            let Some(other_collider) = state.collider_set.get(other_handle) else {
+                continue;
+            };
+            let Some(rigid_body_handle) = other_collider.parent() else {
+                continue;
+            };
+            let Some(rigid_body) = state.rigid_body_set.get(rigid_body_handle) else {
+                continue;
+            };
+            if !rigid_body.is_dynamic() {
+                continue;
+            }
+
+            let is_buoyancy = matches!(effector.effect, AreaEffect::Buoyancy { .. });
+            if !is_buoyancy && !affected_bodies.insert(rigid_body_handle) {
+                continue;
+            }
+
+            let mass = rigid_body.mass();
+            let center_of_mass = rigid_body.center_of_mass().coords;
+
+            let force = match effector.effect {
+                AreaEffect::Wind { force } => force * strength,
+                AreaEffect::Point {
+                    strength: point_strength,
+                    falloff,
+                } => {
+                    let offset = effector_center - center_of_mass;
+                    let distance = offset.norm().max(0.01);
+                    let magnitude = point_strength * strength / distance.powf(falloff);
+                    offset / distance * magnitude
+                }
+                AreaEffect::Gravity { gravity } => {
+                    // cancel out the world's gravity and replace it with the zone's,
+                    // the strength blends between the two
+                    let difference = (gravity - physics_info.gravity) * strength;
+                    difference * mass * rigid_body.gravity_scale()
+                }
+                AreaEffect::Buoyancy {
+                    fluid_density,
+                    linear_drag,
+                    angular_drag,
+                } => {
+                    let Some((submerged_area, submerged_center)) = submerged_part(
+                        other_collider.shape(),
+                        other_collider.position(),
+                        effector_aabb.maxs.y,
+                    ) else {
+                        continue;
+                    };
+
+                    // the mass of the shape at a density of 1 is its area
+                    let area = other_collider.shape().mass_properties(1.0).mass();
+                    let submerged = (submerged_area / area.max(Real::EPSILON)).min(1.0);
+                    let displaced_mass = fluid_density * strength * submerged_area;
+
+                    // the water pushes up where the submerged part is, so a tipped over body is turned back upright
+                    effector_forces.push(EffectorForce {
+                        rigid_body: rigid_body_handle,
+                        force: -physics_info.gravity * displaced_mass,
+                        torque: 0.0,
+                        point: Some(submerged_center),
+                    });
+
+                    // the drag is applied once per collider, so each collider only drags its share of the body
+                    let share = mass_share(&state.collider_set, rigid_body, other_collider);
+                    let angular_inertia = rigid_body.mass_properties().effective_angular_inertia();
+                    let drag = -rigid_body.linvel() * linear_drag * submerged * mass * share;
+                    let drag_torque =
+                        -rigid_body.angvel() * angular_drag * submerged * angular_inertia * share;
+
+                    effector_forces.push(EffectorForce {
+                        rigid_body: rigid_body_handle,
+                        force: drag,
+                        torque: drag_torque,
+                        point: None,
+                    });
+                    continue;
+                }
+            };
+
+            effector_forces.push(EffectorForce {
+                rigid_body: rigid_body_handle,
+                force,
+                torque: 0.0,
+                point: None,
+            });
+        }
+    }
+
+    let mut saved_forces = vec![];
+    let mut saved_rigid_bodies = FxHashSet::default();
+    for effector_force in &effector_forces {
+        let Some(rigid_body) = state.rigid_body_set.get_mut(effector_force.rigid_body) else {
+            continue;
+        };
+
+        if saved_rigid_bodies.insert(effector_force.rigid_body) {
+            saved_forces.push(SavedUserForce::save(effector_force.rigid_body, rigid_body));
+        }
+
+        match effector_force.point {
+            Some(point) => rigid_body.add_force_at_point(effector_force.force, point, true),
+            None => rigid_body.add_force(effector_force.force, true),
+        }
+        rigid_body.add_torque(effector_force.torque, true);
+    }
+
+    saved_forces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::physics_system::RigidBodyHandle;
+    use crate::physics::physics_test_world;
+    use crate::physics::physics_worlds::{get_physics_info, get_physics_info_mut, PhysicsWorldId};
+    use crate::Transform;
+    use ABC_ECS::World;
+
+    fn paused_world(gravity: Vector<Real>) -> World {
+        let mut world = physics_test_world();
+        let physics_info =
+            get_physics_info_mut(&mut world.entities_and_components, PhysicsWorldId::DEFAULT)
+                .unwrap();
+        physics_info.pause();
+        physics_info.set_gravity(gravity);
+        world
+    }
+
+    fn add_effector(world: &mut World, effector: AreaEffector, y: f64, half_height: Real) {
+        world.entities_and_components.add_entity_with((
+            Transform {
+                y,
+                ..Default::default()
+            },
+            ColliderBuilder::cuboid(10.0, half_height)
+                .sensor(true)
+                .build(),
+            effector,
+        ));
+    }
+
+    fn add_body(world: &mut World, x: f64, y: f64, rotation: f64, collider: Collider) -> Entity {
+        world.entities_and_components.add_entity_with((
+            Transform {
+                x,
+                y,
+                rotation,
+                ..Default::default()
+            },
+            RigidBodyBuilder::dynamic().build(),
+            collider,
+        ))
+    }
+
+    fn step(world: &mut World, steps: usize) {
+        get_physics_info_mut(&mut world.entities_and_components, PhysicsWorldId::DEFAULT)
+            .unwrap()
+            .step_n(steps);
+        world.run();
+    }
+
+    fn rapier_body(world: &World, entity: Entity) -> &RigidBody {
+        let (handle,) = world
+            .entities_and_components
+            .get_components::<(RigidBodyHandle,)>(entity);
+        &get_physics_info(&world.entities_and_components, PhysicsWorldId::DEFAULT)
+            .unwrap()
+            .rigid_body_set[handle.0]
+    }
+
+    #[test]
+    fn wind_only_pushes_bodies_inside_of_it() {
+        let mut world = paused_world(vector![0.0, 0.0]);
+        add_effector(&mut world, AreaEffector::wind(vector![10.0, 0.0]), 0.0, 5.0);
+        let inside = add_body(
+            &mut world,
+            0.0,
+            0.0,
+            0.0,
+            ColliderBuilder::ball(1.0).build(),
+        );
+        let outside = add_body(
+            &mut world,
+            0.0,
+            20.0,
+            0.0,
+            ColliderBuilder::ball(1.0).build(),
+        );
+        world.run();
+
+        step(&mut world, 10);
+
+        let inside = rapier_body(&world, inside);
+        assert!(inside.linvel().x > 0.0);
+        assert_eq!(inside.linvel().y, 0.0);
+        // the wind is only added for the step
+        assert_eq!(inside.user_force(), vector![0.0, 0.0]);
+        assert_eq!(*rapier_body(&world, outside).linvel(), vector![0.0, 0.0]);
+    }
+
+    #[test]
+    fn gravity_zones_replace_the_gravity_of_the_world() {
+        let mut world = paused_world(vector![0.0, -10.0]);
+        add_effector(
+            &mut world,
+            AreaEffector::gravity(vector![0.0, 10.0]),
+            0.0,
+            5.0,
+        );
+        let inside = add_body(
+            &mut world,
+            0.0,
+            0.0,
+            0.0,
+            ColliderBuilder::ball(1.0).build(),
+        );
+        let outside = add_body(
+            &mut world,
+            0.0,
+            20.0,
+            0.0,
+            ColliderBuilder::ball(1.0).build(),
+        );
+        world.run();
+
+        step(&mut world, 10);
+
+        // pushed up by the zone, while the body above it keeps falling
+        assert!(rapier_body(&world, inside).linvel().y > 0.0);
+        assert!(rapier_body(&world, outside).linvel().y < 0.0);
+    }
+
+    #[test]
+    fn bodies_lighter_than_the_fluid_float_at_the_surface() {
+        let mut world = paused_world(vector![0.0, -9.81]);
+        // the surface of the water is at 0
+        add_effector(&mut world, AreaEffector::buoyancy(1.0, 2.0, 2.0), -5.0, 5.0);
+        let plank = add_body(
+            &mut world,
+            0.0,
+            -2.0,
+            0.0,
+            ColliderBuilder::cuboid(1.0, 0.25).density(0.5).build(),
+        );
+        world.run();
+
+        step(&mut world, 1200);
+
+        // half as dense as the water, so it floats half under the surface
+        let plank = rapier_body(&world, plank);
+        assert!(
+            plank.translation().y.abs() < 0.05,
+            "at {}",
+            plank.translation().y
+        );
+        assert!(plank.linvel().norm() < 0.05);
+    }
+
+    #[test]
+    fn tipped_over_bodies_are_turned_upright_by_the_fluid() {
+        let mut world = paused_world(vector![0.0, -9.81]);
+        add_effector(&mut world, AreaEffector::buoyancy(1.0, 2.0, 2.0), -5.0, 5.0);
+        let plank = add_body(
+            &mut world,
+            0.0,
+            0.0,
+            0.5,
+            ColliderBuilder::cuboid(1.0, 0.25).density(0.5).build(),
+        );
+        world.run();
+
+        step(&mut world, 1200);
+
+        let angle = rapier_body(&world, plank).rotation().angle();
+        assert!(angle.abs() < 0.05, "the plank is at {angle}");
+    }
+
+    #[test]
+    fn the_submerged_part_of_a_ball() {
+        let (area, center) = submerged_part_of_ball(1.0, point![0.0, 0.0], 0.0).unwrap();
+        assert!((area - std::f32::consts::FRAC_PI_2).abs() < 1.0e-5);
+        let expected_center = -4.0 / (3.0 * std::f32::consts::PI);
+        assert!((center.y - expected_center).abs() < 1.0e-5);
+
+        assert!(submerged_part_of_ball(1.0, point![0.0, 2.0], 0.0).is_none());
+        let (area, center) = submerged_part_of_ball(1.0, point![0.0, -2.0], 0.0).unwrap();
+        assert!((area - std::f32::consts::PI).abs() < 1.0e-5);
+        assert_eq!(center, point![0.0, -2.0]);
+    }
+
+    #[test]
+    fn the_submerged_part_of_a_tipped_over_box_is_off_center() {
+        let cuboid = Cuboid::new(vector![1.0, 1.0]);
+        let position = Isometry::new(vector![0.0, 0.0], std::f32::consts::FRAC_PI_4);
+
+        // turned into a diamond, only the bottom corner is under the surface
+        let (area, center) = submerged_part(&cuboid, &position, -0.5).unwrap();
+        let corner_depth = std::f32::consts::SQRT_2 - 0.5;
+        assert!((area - corner_depth * corner_depth).abs() < 1.0e-4);
+        assert!(center.x.abs() < 1.0e-4);
+
+        // tipped over, the center of the submerged part isn't below the center of the box
+        let position = Isometry::new(vector![3.0, 0.0], 0.3);
+        let (area, center) = submerged_part(&cuboid, &position, 0.0).unwrap();
+        assert!((area - 2.0).abs() < 1.0e-4);
+        assert!(center.x > 3.0);
+        assert!(center.y < 0.0);
+    }
+}
@@ -0,0 +1,796 @@
+use fxhash::FxHashMap;
+use rapier2d::parry::transformation::vhacd::VHACDParameters;
+use rapier2d::parry::transformation::voxelization::FillMode;
+use rapier2d::prelude::*;
+use ABC_ECS::EntitiesAndComponents;
+use ABC_ECS::Entity;
+
+use crate::Transform;
+
+/// how the solid tiles of a TileGrid are turned into colliders
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileColliderShape {
+    /// solid tiles are merged into as few rectangles as possible, put together in one compound collider
+    Rectangles,
+    /// the outline of every group of solid tiles becomes a polyline, this avoids snagging on the seams between tiles
+    Polylines,
+}
+
+/// how the solid pixels of an AlphaMask are turned into colliders
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MaskColliderShape {
+    /// the outline of every group of solid pixels is split up into convex pieces, put together in one compound collider
+    /// holes in a group are left empty
+    ConvexDecomposition,
+    /// the outline of every group of solid pixels becomes a polyline
+    Polylines,
+}
+
+/// A grid of tiles that are either solid or empty.
+/// row 0 is the top row, and the top left corner of the grid is at the position of the entity it is added to
+#[derive(Clone, Debug)]
+pub struct TileGrid {
+    width: usize,
+    height: usize,
+    tiles: Vec<bool>,
+    tile_size: f32,
+}
+
+impl TileGrid {
+    /// creates a grid where every tile is empty
+    pub fn new(width: usize, height: usize, tile_size: f32) -> Self {
+        Self {
+            width,
+            height,
+            tiles: vec![false; width * height],
+            tile_size,
+        }
+    }
+
+    /// creates a grid from the tiles of a tilemap, stored row by row
+    pub fn from_tiles<T>(
+        width: usize,
+        tiles: &[T],
+        tile_size: f32,
+        is_solid: impl Fn(&T) -> bool,
+    ) -> Self {
+        assert!(
+            width > 0 && tiles.len() % width == 0,
+            "the number of tiles must be a multiple of the width"
+        );
+
+        Self {
+            width,
+            height: tiles.len() / width,
+            tiles: tiles.iter().map(is_solid).collect(),
+            tile_size,
+        }
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get_tile_size(&self) -> f32 {
+        self.tile_size
+    }
+
+    /// tiles outside of the grid are always empty
+    pub fn is_solid(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.tiles[y * self.width + x]
+    }
+
+    pub fn set_solid(&mut self, x: usize, y: usize, solid: bool) {
+        assert!(
+            x < self.width && y < self.height,
+            "tile ({}, {}) is outside of the grid",
+            x,
+            y
+        );
+        self.tiles[y * self.width + x] = solid;
+    }
+
+    fn as_solid_grid(&self) -> SolidGrid {
+        SolidGrid {
+            width: self.width,
+            height: self.height,
+            cells: &self.tiles,
+            cell_size: self.tile_size,
+        }
+    }
+
+    /// builds the collider shapes for the grid, relative to the top left corner of the grid
+    pub fn build_shapes(&self, shape: TileColliderShape) -> Vec<SharedShape> {
+        let grid = self.as_solid_grid();
+
+        match shape {
+            TileColliderShape::Rectangles => {
+                let rectangles = grid
+                    .merged_rectangles()
+                    .into_iter()
+                    .map(|rectangle| grid.rectangle_to_shape(rectangle))
+                    .collect::<Vec<_>>();
+
+                if rectangles.is_empty() {
+                    vec![]
+                } else {
+                    vec![SharedShape::compound(rectangles)]
+                }
+            }
+            TileColliderShape::Polylines => grid
+                .outlines(0.0)
+                .into_iter()
+                .map(|outline| grid.outline_to_polyline(&outline))
+                .collect(),
+        }
+    }
+}
+
+/// The solid pixels of an image, usually made from its alpha channel.
+/// row 0 is the top row, and the top left corner of the mask is at the position of the entity it is added to
+#[derive(Clone, Debug)]
+pub struct AlphaMask {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+    pixel_size: f32,
+    tolerance: f32,
+}
+
+impl AlphaMask {
+    /// creates a mask from one alpha value per pixel, pixels with an alpha above the threshold are solid
+    pub fn new(width: usize, height: usize, alpha: &[u8], threshold: u8) -> Self {
+        assert!(
+            alpha.len() == width * height,
+            "there must be one alpha value per pixel"
+        );
+
+        Self {
+            width,
+            height,
+            pixels: alpha.iter().map(|alpha| *alpha > threshold).collect(),
+            pixel_size: 1.0,
+            tolerance: 1.0,
+        }
+    }
+
+    /// creates a mask from rgba8 image data, pixels with an alpha above the threshold are solid
+    pub fn from_rgba(width: usize, height: usize, rgba: &[u8], threshold: u8) -> Self {
+        assert!(
+            rgba.len() == width * height * 4,
+            "there must be four bytes per pixel"
+        );
+
+        let alpha = rgba
+            .chunks_exact(4)
+            .map(|pixel| pixel[3])
+            .collect::<Vec<u8>>();
+        Self::new(width, height, &alpha, threshold)
+    }
+
+    pub fn get_width(&self) -> usize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> usize {
+        self.height
+    }
+
+    /// pixels outside of the mask are always empty
+    pub fn is_solid(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.pixels[y * self.width + x]
+    }
+
+    /// how big one pixel of the mask is in the world, 1.0 by default
+    pub fn get_pixel_size(&self) -> f32 {
+        self.pixel_size
+    }
+
+    pub fn set_pixel_size(&mut self, pixel_size: f32) {
+        self.pixel_size = pixel_size;
+    }
+
+    pub fn with_pixel_size(mut self, pixel_size: f32) -> Self {
+        self.pixel_size = pixel_size;
+        self
+    }
+
+    /// how far (in pixels of the mask) the outline is allowed to stray from the pixels to use fewer points, 1.0 by default
+    pub fn get_tolerance(&self) -> f32 {
+        self.tolerance
+    }
+
+    /// how far (in pixels of the mask) the outline is allowed to stray from the pixels to use fewer points
+    /// 0.0 follows every pixel exactly
+    pub fn set_tolerance(&mut self, tolerance: f32) {
+        self.tolerance = tolerance.max(0.0);
+    }
+
+    pub fn with_tolerance(mut self, tolerance: f32) -> Self {
+        self.set_tolerance(tolerance);
+        self
+    }
+
+    fn as_solid_grid(&self) -> SolidGrid {
+        SolidGrid {
+            width: self.width,
+            height: self.height,
+            cells: &self.pixels,
+            cell_size: self.pixel_size,
+        }
+    }
+
+    /// builds the collider shapes for the mask, relative to the top left corner of the mask
+    pub fn build_shapes(&self, shape: MaskColliderShape) -> Vec<SharedShape> {
+        let grid = self.as_solid_grid();
+
+        match shape {
+            MaskColliderShape::ConvexDecomposition => {
+                // without looking for cavities everything inside of the outer outline would be filled in
+                let parameters = VHACDParameters {
+                    fill_mode: FillMode::FloodFill {
+                        detect_cavities: true,
+                    },
+                    ..Default::default()
+                };
+
+                grid.outlines_with_holes(self.tolerance)
+                    .into_iter()
+                    .map(|(outer, holes)| {
+                        let mut vertices = vec![];
+                        let mut indices = vec![];
+                        for outline in std::iter::once(&outer).chain(&holes) {
+                            let start = vertices.len() as u32;
+                            let len = outline.len() as u32;
+                            vertices
+                                .extend(outline.iter().map(|corner| grid.corner_to_point(*corner)));
+                            indices.extend((0..len).map(|i| [start + i, start + (i + 1) % len]));
+                        }
+
+                        SharedShape::convex_decomposition_with_params(
+                            &vertices,
+                            &indices,
+                            &parameters,
+                        )
+                    })
+                    .collect()
+            }
+            MaskColliderShape::Polylines => grid
+                .outlines(self.tolerance)
+                .iter()
+                .map(|outline| grid.outline_to_polyline(outline))
+                .collect(),
+        }
+    }
+}
+
+/// Adds the colliders for a tile grid to the world, the returned entity has the given transform.
+/// colliders without a rigidbody never move, so no rigidbodies are made, give the returned entity
+/// a RigidBody to move the whole grid at once.
+pub fn add_tile_grid_colliders(
+    entities_and_components: &mut EntitiesAndComponents,
+    tile_grid: &TileGrid,
+    shape: TileColliderShape,
+    transform: Transform,
+) -> Entity {
+    add_shapes_as_entities(
+        entities_and_components,
+        tile_grid.build_shapes(shape),
+        transform,
+    )
+}
+
+/// Adds the colliders for an alpha mask to the world, the returned entity has the given transform.
+/// colliders without a rigidbody never move, so no rigidbodies are made, give the returned entity
+/// a RigidBody to make the whole mask move as one body.
+pub fn add_alpha_mask_colliders(
+    entities_and_components: &mut EntitiesAndComponents,
+    alpha_mask: &AlphaMask,
+    shape: MaskColliderShape,
+    transform: Transform,
+) -> Entity {
+    add_shapes_as_entities(
+        entities_and_components,
+        alpha_mask.build_shapes(shape),
+        transform,
+    )
+}
+
+/// a single shape goes on the root entity, more than one are each put on a child of it
+fn add_shapes_as_entities(
+    entities_and_components: &mut EntitiesAndComponents,
+    mut shapes: Vec<SharedShape>,
+    transform: Transform,
+) -> Entity {
+    if shapes.len() == 1 {
+        let collider = ColliderBuilder::new(shapes.remove(0)).build();
+        return entities_and_components.add_entity_with((transform, collider));
+    }
+
+    let root = entities_and_components.add_entity_with((transform,));
+    for shape in shapes {
+        let collider = ColliderBuilder::new(shape).build();
+        let child = entities_and_components.add_entity_with((Transform::default(), collider));
+        entities_and_components.set_parent(child, root);
+    }
+
+    root
+}
+
+/// a corner between cells, (0, 0) is the top left corner of the grid and y goes down
+type Corner = (i32, i32);
+
+/// a rectangle of cells, (x, y, width, height)
+type CellRectangle = (usize, usize, usize, usize);
+
+struct SolidGrid<'a> {
+    width: usize,
+    height: usize,
+    cells: &'a [bool],
+    cell_size: f32,
+}
+
+impl SolidGrid<'_> {
+    fn is_solid(&self, x: i32, y: i32) -> bool {
+        x >= 0
+            && y >= 0
+            && (x as usize) < self.width
+            && (y as usize) < self.height
+            && self.cells[y as usize * self.width + x as usize]
+    }
+
+    /// y is flipped because rows go down and the world goes up
+    fn corner_to_point(&self, corner: Corner) -> Point<Real> {
+        point![
+            corner.0 as Real * self.cell_size,
+            -corner.1 as Real * self.cell_size
+        ]
+    }
+
+    fn rectangle_to_shape(&self, rectangle: CellRectangle) -> (Isometry<Real>, SharedShape) {
+        let (x, y, width, height) = rectangle;
+        let half_width = width as Real * self.cell_size / 2.0;
+        let half_height = height as Real * self.cell_size / 2.0;
+
+        let top_left = self.corner_to_point((x as i32, y as i32));
+        let center = Isometry::translation(top_left.x + half_width, top_left.y - half_height);
+
+        (center, SharedShape::cuboid(half_width, half_height))
+    }
+
+    fn outline_to_polyline(&self, outline: &[Corner]) -> SharedShape {
+        let vertices = outline
+            .iter()
+            .map(|corner| self.corner_to_point(*corner))
+            .collect::<Vec<_>>();
+        let len = vertices.len() as u32;
+        let indices = (0..len).map(|i| [i, (i + 1) % len]).collect();
+
+        SharedShape::polyline(vertices, Some(indices))
+    }
+
+    /// greedily merges the solid cells into rectangles, first as wide as possible and then as tall as possible
+    fn merged_rectangles(&self) -> Vec<CellRectangle> {
+        let mut used = vec![false; self.width * self.height];
+        let mut rectangles = vec![];
+        let free = |used: &[bool], x: usize, y: usize| {
+            self.is_solid(x as i32, y as i32) && !used[y * self.width + x]
+        };
+
+        for y in 0..self.height {
+            let mut x = 0;
+            while x < self.width {
+                if !free(&used, x, y) {
+                    x += 1;
+                    continue;
+                }
+
+                let mut width = 1;
+                while x + width < self.width && free(&used, x + width, y) {
+                    width += 1;
+                }
+
+                let mut height = 1;
+                while y + height < self.height && (x..x + width).all(|i| free(&used, i, y + height))
+                {
+                    height += 1;
+                }
+
+                for used_y in y..y + height {
+                    for used_x in x..x + width {
+                        used[used_y * self.width + used_x] = true;
+                    }
+                }
+
+                rectangles.push((x, y, width, height));
+                x += width;
+            }
+        }
+
+        rectangles
+    }
+
+    /// finds the closed outlines around every group of solid cells, holes get their own outline
+    /// points that are less than tolerance cells away from the line between their neighbours are removed
+    fn outlines(&self, tolerance: f32) -> Vec<Vec<Corner>> {
+        self.traced_outlines()
+            .into_iter()
+            .map(|outline| simplify_outline(remove_collinear_corners(outline), tolerance))
+            .filter(|outline| outline.len() >= 3)
+            .collect()
+    }
+
+    /// the outlines of every group of solid cells, each with the outlines of the holes inside of it
+    fn outlines_with_holes(&self, tolerance: f32) -> Vec<(Vec<Corner>, Vec<Vec<Corner>>)> {
+        // outer outlines go clockwise around the solid cells and holes go the other way
+        let (outers, holes): (Vec<Vec<Corner>>, Vec<Vec<Corner>>) = self
+            .traced_outlines()
+            .into_iter()
+            .partition(|outline| twice_signed_area(outline) > 0);
+
+        let mut holes_in_outer = vec![vec![]; outers.len()];
+        for hole in holes {
+            // a hole belongs to the smallest outline around it, a hole in an island in a hole isn't in the big outline
+            let inside = point_inside_hole(&hole);
+            let outer = outers
+                .iter()
+                .enumerate()
+                .filter(|(_, outer)| contains_point(outer, inside))
+                .min_by_key(|(_, outer)| twice_signed_area(outer))
+                .map(|(index, _)| index);
+
+            if let Some(outer) = outer {
+                holes_in_outer[outer].push(hole);
+            }
+        }
+
+        let simplify = |outline| simplify_outline(remove_collinear_corners(outline), tolerance);
+        outers
+            .into_iter()
+            .zip(holes_in_outer)
+            .map(|(outer, holes)| {
+                let holes = holes
+                    .into_iter()
+                    .map(simplify)
+                    .filter(|hole| hole.len() >= 3)
+                    .collect();
+                (simplify(outer), holes)
+            })
+            .filter(|(outer, _)| outer.len() >= 3)
+            .collect()
+    }
+
+    /// follows the edges between solid and empty cells into closed outlines, with a corner at every cell
+    fn traced_outlines(&self) -> Vec<Vec<Corner>> {
+        // every side of a solid cell that touches an empty cell is an edge, they go clockwise around the solid cells
+        let mut edges: FxHashMap<Corner, Vec<Corner>> = FxHashMap::default();
+        let mut add_edge = |from: Corner, to: Corner| edges.entry(from).or_default().push(to);
+
+        for y in 0..self.height as i32 {
+            for x in 0..self.width as i32 {
+                if !self.is_solid(x, y) {
+                    continue;
+                }
+
+                if !self.is_solid(x, y - 1) {
+                    add_edge((x, y), (x + 1, y));
+                }
+                if !self.is_solid(x + 1, y) {
+                    add_edge((x + 1, y), (x + 1, y + 1));
+                }
+                if !self.is_solid(x, y + 1) {
+                    add_edge((x + 1, y + 1), (x, y + 1));
+                }
+                if !self.is_solid(x - 1, y) {
+                    add_edge((x, y + 1), (x, y));
+                }
+            }
+        }
+
+        // sorted so the outlines come out the same every time
+        let mut starts = edges.keys().copied().collect::<Vec<Corner>>();
+        starts.sort_unstable_by_key(|corner| (corner.1, corner.0));
+
+        let mut outlines = vec![];
+        for start in starts {
+            while let Some(first) = edges.get_mut(&start).and_then(|ends| ends.pop()) {
+                let first_direction = direction(start, first);
+                let mut outline = vec![start];
+                let (mut previous, mut current) = (start, first);
+
+                loop {
+                    let incoming = direction(previous, current);
+                    let ends = edges.entry(current).or_default();
+                    let Some(next_index) = sharpest_right_turn(current, incoming, ends) else {
+                        break;
+                    };
+
+                    // when the start is a pinch corner, the outline only closes if it would turn into the first edge
+                    if current == start
+                        && turn(incoming, first_direction)
+                            > turn(incoming, direction(current, ends[next_index]))
+                    {
+                        break;
+                    }
+
+                    let next = ends.swap_remove(next_index);
+                    outline.push(current);
+                    previous = current;
+                    current = next;
+                }
+
+                outlines.push(outline);
+            }
+        }
+
+        outlines
+    }
+}
+
+fn direction(from: Corner, to: Corner) -> (i32, i32) {
+    (to.0 - from.0, to.1 - from.1)
+}
+
+/// positive for a right turn, y goes down so this is the other way around from the usual cross product
+fn turn(incoming: (i32, i32), outgoing: (i32, i32)) -> i32 {
+    incoming.0 * outgoing.1 - incoming.1 * outgoing.0
+}
+
+/// At a pinch corner, where two solid cells only touch diagonally, there are two edges to go on with.
+/// turning right keeps going around the same cell, so cells that only touch at a corner get their own outlines
+fn sharpest_right_turn(corner: Corner, incoming: (i32, i32), ends: &[Corner]) -> Option<usize> {
+    (0..ends.len()).max_by_key(|index| turn(incoming, direction(corner, ends[*index])))
+}
+
+/// twice the area inside of the outline, positive if it goes clockwise around the solid cells
+fn twice_signed_area(outline: &[Corner]) -> i64 {
+    let len = outline.len();
+    (0..len)
+        .map(|i| {
+            let (current, next) = (outline[i], outline[(i + 1) % len]);
+            current.0 as i64 * next.1 as i64 - next.0 as i64 * current.1 as i64
+        })
+        .sum()
+}
+
+/// the center of the empty cell next to the first edge of a traced hole, it is never on a cell edge
+fn point_inside_hole(hole: &[Corner]) -> (f32, f32) {
+    let (from, to) = (hole[0], hole[1]);
+    let (dx, dy) = direction(from, to);
+    // the solid cells are on the right of the edge, so the empty cell is on the left
+    (
+        (from.0 + to.0) as f32 / 2.0 + dy as f32 / 2.0,
+        (from.1 + to.1) as f32 / 2.0 - dx as f32 / 2.0,
+    )
+}
+
+/// even-odd test, the point should not be on an edge of the outline
+fn contains_point(outline: &[Corner], point: (f32, f32)) -> bool {
+    let len = outline.len();
+    let mut inside = false;
+    for i in 0..len {
+        let (a, b) = (outline[i], outline[(i + 1) % len]);
+        let (ay, by) = (a.1 as f32, b.1 as f32);
+        if (ay > point.1) != (by > point.1) {
+            let x = a.0 as f32 + (point.1 - ay) / (by - ay) * (b.0 - a.0) as f32;
+            if point.0 < x {
+                inside = !inside;
+            }
+        }
+    }
+
+    inside
+}
+
+fn remove_collinear_corners(outline: Vec<Corner>) -> Vec<Corner> {
+    let len = outline.len();
+    (0..len)
+        .filter(|i| {
+            let previous = outline[(i + len - 1) % len];
+            let current = outline[*i];
+            let next = outline[(i + 1) % len];
+
+            let cross = (current.0 - previous.0) * (next.1 - current.1)
+                - (current.1 - previous.1) * (next.0 - current.0);
+            cross != 0
+        })
+        .map(|i| outline[i])
+        .collect()
+}
+
+/// Douglas-Peucker on a closed outline, split in two at the corner furthest from the first one
+fn simplify_outline(outline: Vec<Corner>, tolerance: f32) -> Vec<Corner> {
+    if tolerance <= 0.0 || outline.len() <= 3 {
+        return outline;
+    }
+
+    let start = outline[0];
+    let furthest = (1..outline.len())
+        .max_by_key(|i| {
+            let corner = outline[*i];
+            (corner.0 - start.0).pow(2) + (corner.1 - start.1).pow(2)
+        })
+        .unwrap_or(0);
+
+    let mut keep = vec![false; outline.len()];
+    keep[0] = true;
+    keep[furthest] = true;
+
+    let mut closed = outline.clone();
+    closed.push(start);
+    mark_kept_corners(&closed, 0, furthest, tolerance, &mut keep);
+    mark_kept_corners(&closed, furthest, outline.len(), tolerance, &mut keep);
+
+    outline
+        .into_iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(corner, _)| corner)
+        .collect()
+}
+
+fn mark_kept_corners(
+    corners: &[Corner],
+    first: usize,
+    last: usize,
+    tolerance: f32,
+    keep: &mut [bool],
+) {
+    if last <= first + 1 {
+        return;
+    }
+
+    let a = corners[first];
+    let b = corners[last];
+    let (dx, dy) = ((b.0 - a.0) as f32, (b.1 - a.1) as f32);
+    let length = (dx * dx + dy * dy).sqrt();
+
+    let distance_to_line = |corner: Corner| {
+        let (px, py) = ((corner.0 - a.0) as f32, (corner.1 - a.1) as f32);
+        if length == 0.0 {
+            (px * px + py * py).sqrt()
+        } else {
+            (px * dy - py * dx).abs() / length
+        }
+    };
+
+    let (furthest, distance) = (first + 1..last)
+        .map(|i| (i, distance_to_line(corners[i])))
+        .fold((first, 0.0), |best, current| {
+            if current.1 > best.1 {
+                current
+            } else {
+                best
+            }
+        });
+
+    if distance > tolerance {
+        keep[furthest] = true;
+        mark_kept_corners(corners, first, furthest, tolerance, keep);
+        mark_kept_corners(corners, furthest, last, tolerance, keep);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rapier2d::parry::query::PointQuery;
+
+    #[test]
+    fn merge_tiles_into_rectangles() {
+        // an L shape, which should be two rectangles
+        #[rustfmt::skip]
+        let tiles = [
+            1, 0, 0,
+            1, 0, 0,
+            1, 1, 1,
+        ];
+        let grid = TileGrid::from_tiles(3, &tiles, 16.0, |tile| *tile == 1);
+
+        assert_eq!(grid.as_solid_grid().merged_rectangles().len(), 2);
+    }
+
+    #[test]
+    fn outline_of_a_block_of_tiles() {
+        let tiles = [true; 6];
+        let grid = TileGrid::from_tiles(3, &tiles, 1.0, |tile| *tile);
+        let outlines = grid.as_solid_grid().outlines(0.0);
+
+        assert_eq!(outlines.len(), 1);
+        assert_eq!(outlines[0].len(), 4);
+    }
+
+    #[test]
+    fn hole_gets_its_own_outline() {
+        #[rustfmt::skip]
+        let tiles = [
+            true, true, true,
+            true, false, true,
+            true, true, true,
+        ];
+        let grid = TileGrid::from_tiles(3, &tiles, 1.0, |tile| *tile);
+
+        assert_eq!(grid.as_solid_grid().outlines(0.0).len(), 2);
+    }
+
+    #[test]
+    fn tiles_touching_at_a_corner_get_their_own_outlines() {
+        #[rustfmt::skip]
+        let tiles = [
+            true, false, true,
+            false, true, false,
+            true, false, true,
+        ];
+        let grid = TileGrid::from_tiles(3, &tiles, 1.0, |tile| *tile);
+        let outlines = grid.as_solid_grid().outlines(0.0);
+
+        assert_eq!(outlines.len(), 5);
+        for outline in outlines {
+            assert_eq!(outline.len(), 4);
+            assert_eq!(twice_signed_area(&outline), 2);
+        }
+    }
+
+    #[test]
+    fn a_hole_touching_the_outside_at_a_corner_is_part_of_the_outline() {
+        #[rustfmt::skip]
+        let tiles = [
+            false, true, true,
+            true, false, true,
+            true, true, true,
+        ];
+        let grid = TileGrid::from_tiles(3, &tiles, 1.0, |tile| *tile);
+        let outlines = grid.as_solid_grid().outlines(0.0);
+
+        assert_eq!(outlines.len(), 1);
+        assert_eq!(twice_signed_area(&outlines[0]), 14);
+    }
+
+    #[test]
+    fn holes_are_grouped_with_the_outline_around_them() {
+        // a ring with an island in its hole, and a block next to it
+        #[rustfmt::skip]
+        let tiles = [
+            1, 1, 1, 1, 1, 0, 1,
+            1, 0, 0, 0, 1, 0, 1,
+            1, 0, 1, 0, 1, 0, 0,
+            1, 0, 0, 0, 1, 0, 0,
+            1, 1, 1, 1, 1, 0, 0,
+        ];
+        let grid = TileGrid::from_tiles(7, &tiles, 1.0, |tile| *tile == 1);
+        let mut groups = grid.as_solid_grid().outlines_with_holes(0.0);
+        groups.sort_by_key(|(outer, _)| twice_signed_area(outer));
+
+        let areas = groups
+            .iter()
+            .map(|(outer, holes)| {
+                let hole_areas = holes.iter().map(|hole| twice_signed_area(hole) / 2);
+                (twice_signed_area(outer) / 2, hole_areas.collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(areas, vec![(1, vec![]), (2, vec![]), (25, vec![-9])]);
+    }
+
+    #[test]
+    fn the_convex_decomposition_leaves_holes_empty() {
+        let mut alpha = vec![255; 12 * 12];
+        for y in 4..8 {
+            for x in 4..8 {
+                alpha[y * 12 + x] = 0;
+            }
+        }
+        let mask = AlphaMask::new(12, 12, &alpha, 127).with_tolerance(0.0);
+        let shapes = mask.build_shapes(MaskColliderShape::ConvexDecomposition);
+
+        assert_eq!(shapes.len(), 1);
+        let contains =
+            |x: Real, y: Real| shapes[0].contains_point(&Isometry::identity(), &point![x, y]);
+        assert!(contains(1.0, -1.0));
+        assert!(contains(10.0, -6.0));
+        assert!(!contains(6.0, -6.0));
+    }
+}
@@ -0,0 +1,389 @@
+use rapier2d::parry::query::PointQuery;
+use rapier2d::prelude::*;
+use ABC_ECS::EntitiesAndComponents;
+use ABC_ECS::Entity;
+
+use crate::physics::physics_queries::{EntityFilter, EntityQuery};
+use crate::physics::physics_system::{ColliderHandle, RigidBodyHandle};
+use crate::physics::physics_worlds::{get_physics_info, get_physics_info_mut, PhysicsWorldId};
+
+/// A burst that finds everything within a radius of a point and pushes the dynamic bodies away from it, see explode.
+/// like the other queries, positions and distances are in pixels
+pub struct Explosion<'a> {
+    center: Vector<Real>,
+    radius: Real,
+    impulse: Real,
+    falloff: Real,
+    line_of_sight: Option<EntityFilter<'a>>,
+    world_id: PhysicsWorldId,
+}
+
+impl<'a> Explosion<'a> {
+    /// an explosion with no impulse, linear falloff and no line of sight checks, radius must be greater than 0
+    pub fn new(center: Vector<Real>, radius: Real) -> Self {
+        assert!(radius > 0.0, "explosion radius must be greater than 0");
+
+        Self {
+            center,
+            radius,
+            impulse: 0.0,
+            falloff: 1.0,
+            line_of_sight: None,
+            world_id: PhysicsWorldId::DEFAULT,
+        }
+    }
+
+    pub fn get_center(&self) -> Vector<Real> {
+        self.center
+    }
+
+    pub fn get_radius(&self) -> Real {
+        self.radius
+    }
+
+    /// the impulse given to a dynamic body right at the center, it is scaled down by the falloff further out
+    pub fn get_impulse(&self) -> Real {
+        self.impulse
+    }
+
+    /// the impulse given to a dynamic body right at the center, a negative impulse pulls things in
+    /// 0 by default, so the explosion only finds things
+    pub fn set_impulse(&mut self, impulse: Real) {
+        self.impulse = impulse;
+    }
+
+    pub fn with_impulse(mut self, impulse: Real) -> Self {
+        self.impulse = impulse;
+        self
+    }
+
+    /// how quickly the explosion gets weaker with distance, the multiplier is (1 - distance / radius)^falloff
+    pub fn get_falloff(&self) -> Real {
+        self.falloff
+    }
+
+    /// how quickly the explosion gets weaker with distance, the multiplier is (1 - distance / radius)^falloff
+    /// 0 is the same everywhere, 1 (the default) is linear and 2 drops off faster
+    pub fn set_falloff(&mut self, falloff: Real) {
+        self.falloff = falloff;
+    }
+
+    pub fn with_falloff(mut self, falloff: Real) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    /// only things that a ray from the center reaches without hitting a collider that matches the filter are affected
+    /// the center should not be inside one of those colliders, or everything will be blocked
+    pub fn with_line_of_sight(mut self, blockers: EntityFilter<'a>) -> Self {
+        self.line_of_sight = Some(blockers);
+        self
+    }
+
+    /// the physics world the explosion happens in, the default world if this isn't called
+    pub fn with_world(mut self, world_id: PhysicsWorldId) -> Self {
+        self.world_id = world_id;
+        self
+    }
+
+    /// the multiplier at the given distance from the center, between 0 and 1
+    pub fn get_falloff_at(&self, distance: Real) -> Real {
+        (1.0 - distance / self.radius)
+            .clamp(0.0, 1.0)
+            .powf(self.falloff)
+    }
+}
+
+/// something that was caught in an explosion
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExplosionHit {
+    /// the entity with the collider that was hit
+    pub entity: Entity,
+    /// how far the closest point of the collider is from the center, 0 if the center is inside of it
+    pub distance: Real,
+    /// the multiplier from the explosion's falloff at that distance, between 0 and 1
+    pub falloff: Real,
+}
+
+/// Finds every collider matching the filter within the radius of the explosion, and queues an impulse
+/// away from the center for every dynamic body that was hit, scaled by the falloff of its closest collider.
+/// The impulses are applied by the physics system on the next frame, like RapierPhysicsInfo::apply_impulse.
+/// Returns what was hit, closest first, so that damage can be dealt with the distance or falloff.
+pub fn explode(
+    entities_and_components: &mut EntitiesAndComponents,
+    explosion: &Explosion,
+    filter: &EntityFilter,
+) -> Vec<ExplosionHit> {
+    let mut hits = vec![];
+    // the body entity, its center of mass and the strongest falloff of any of its colliders
+    let mut pushed_bodies: Vec<(Entity, Vector<Real>, Real)> = vec![];
+
+    {
+        let query = EntityQuery::new_in_world(entities_and_components, explosion.world_id);
+        let physics_info = get_physics_info(entities_and_components, explosion.world_id).expect(
+            "failed to get rapier physics info, add the physics systems and the world first",
+        );
+
+        let mut hit_entities = vec![];
+        query.intersections_with_shape(
+            &Isometry::translation(explosion.center.x, explosion.center.y),
+            &Ball::new(explosion.radius),
+            filter,
+            |entity| {
+                hit_entities.push(entity);
+                true
+            },
+        );
+
+        let center_in_meters = Point::from(explosion.center / physics_info.pixels_per_meter);
+
+        for entity in hit_entities {
+            let Some(collider_handle) = entities_and_components
+                .try_get_components::<(ColliderHandle,)>(entity)
+                .0
+            else {
+                continue;
+            };
+            let Some(collider) = physics_info.collider_set.get(collider_handle.0) else {
+                continue;
+            };
+
+            let projection =
+                collider
+                    .shape()
+                    .project_point(collider.position(), &center_in_meters, true);
+            let closest_point = projection.point.coords * physics_info.pixels_per_meter;
+            let distance = if projection.is_inside {
+                0.0
+            } else {
+                (closest_point - explosion.center).norm()
+            };
+
+            if distance > explosion.radius {
+                continue;
+            }
+
+            let body_entity = collider.parent().and_then(|rb_handle| {
+                physics_info
+                    .get_associated_entity_with_rigid_body_handle(RigidBodyHandle(rb_handle))
+            });
+
+            if let (Some(blockers), false) = (&explosion.line_of_sight, projection.is_inside) {
+                let ray = Ray::new(
+                    Point::from(explosion.center),
+                    closest_point - explosion.center,
+                );
+                // the other colliders of the same body can't shield it from the explosion
+                let mut blockers = blockers.clone().exclude_entity(entity);
+                if let Some(body_entity) = body_entity {
+                    blockers = blockers.exclude_entity(body_entity);
+                }
+                // the ray ends on the collider, so only things hit a bit before the end are in the way
+                let blocked = query
+                    .cast_ray(&ray, 1.0, true, &blockers)
+                    .is_some_and(|(_, toi)| toi < 0.999);
+                if blocked {
+                    continue;
+                }
+            }
+
+            let falloff = explosion.get_falloff_at(distance);
+            hits.push(ExplosionHit {
+                entity,
+                distance,
+                falloff,
+            });
+
+            let Some(rb_handle) = collider.parent() else {
+                continue;
+            };
+            if !physics_info.rigid_body_set[rb_handle].is_dynamic() {
+                continue;
+            }
+            let Some(body_entity) = body_entity else {
+                continue;
+            };
+
+            // a body with more than one collider is only pushed once
+            match pushed_bodies
+                .iter_mut()
+                .find(|(pushed_entity, _, _)| *pushed_entity == body_entity)
+            {
+                Some((_, _, body_falloff)) => *body_falloff = body_falloff.max(falloff),
+                None => {
+                    let center_of_mass = physics_info.rigid_body_set[rb_handle]
+                        .center_of_mass()
+                        .coords
+                        * physics_info.pixels_per_meter;
+                    pushed_bodies.push((body_entity, center_of_mass, falloff));
+                }
+            }
+        }
+    }
+
+    if explosion.impulse != 0.0 {
+        let physics_info = get_physics_info_mut(entities_and_components, explosion.world_id)
+            .expect(
+                "failed to get rapier physics info, add the physics systems and the world first",
+            );
+
+        for (body_entity, center_of_mass, falloff) in pushed_bodies {
+            let offset = center_of_mass - explosion.center;
+            let distance = offset.norm();
+            // something exactly at the center has no direction to be pushed in
+            if distance <= Real::EPSILON {
+                continue;
+            }
+
+            physics_info
+                .apply_impulse(body_entity, offset / distance * explosion.impulse * falloff);
+        }
+    }
+
+    hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::physics_commands::PhysicsCommand;
+    use crate::physics::physics_test_world;
+    use crate::Transform;
+    use ABC_ECS::World;
+
+    struct Wall;
+
+    /// nothing falls, so it doesn't matter if a step is taken
+    fn world_without_gravity() -> World {
+        let mut world = physics_test_world();
+        get_physics_info_mut(&mut world.entities_and_components, PhysicsWorldId::DEFAULT)
+            .unwrap()
+            .set_gravity(vector![0.0, 0.0]);
+        world
+    }
+
+    fn add_ball(world: &mut World, x: f64, y: f64, rigid_body: RigidBody) -> Entity {
+        world.entities_and_components.add_entity_with((
+            Transform {
+                x,
+                y,
+                ..Default::default()
+            },
+            rigid_body,
+            ColliderBuilder::ball(1.0).build(),
+        ))
+    }
+
+    fn queued_impulse(world: &World, entity: Entity) -> Option<Vector<Real>> {
+        get_physics_info(&world.entities_and_components, PhysicsWorldId::DEFAULT)
+            .unwrap()
+            .queued_commands
+            .iter()
+            .find_map(|(commanded, command)| match command {
+                PhysicsCommand::ApplyImpulse(impulse) if *commanded == entity => Some(*impulse),
+                _ => None,
+            })
+    }
+
+    #[test]
+    fn the_impulse_gets_weaker_further_from_the_center() {
+        let mut world = world_without_gravity();
+        let near = add_ball(&mut world, 10.0, 0.0, RigidBodyBuilder::dynamic().build());
+        let far = add_ball(&mut world, 0.0, -30.0, RigidBodyBuilder::dynamic().build());
+        let outside = add_ball(&mut world, 60.0, 0.0, RigidBodyBuilder::dynamic().build());
+        world.run();
+
+        let explosion = Explosion::new(vector![0.0, 0.0], 50.0).with_impulse(100.0);
+        let hits = explode(
+            &mut world.entities_and_components,
+            &explosion,
+            &EntityFilter::new(),
+        );
+
+        // the distance is to the edge of the ball
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].entity, near);
+        assert!((hits[0].distance - 9.0).abs() < 1.0e-3);
+        assert!((hits[0].falloff - 0.82).abs() < 1.0e-3);
+        assert_eq!(hits[1].entity, far);
+        assert!((hits[1].falloff - 0.42).abs() < 1.0e-3);
+
+        // pushed away from the center
+        let near_impulse = queued_impulse(&world, near).unwrap();
+        assert!((near_impulse - vector![82.0, 0.0]).norm() < 1.0e-2);
+        let far_impulse = queued_impulse(&world, far).unwrap();
+        assert!((far_impulse - vector![0.0, -42.0]).norm() < 1.0e-2);
+        assert!(queued_impulse(&world, outside).is_none());
+    }
+
+    #[test]
+    fn bodies_behind_a_blocker_are_not_hit() {
+        let mut world = world_without_gravity();
+        let wall = world.entities_and_components.add_entity_with((
+            Transform {
+                x: 10.0,
+                ..Default::default()
+            },
+            ColliderBuilder::cuboid(1.0, 5.0).build(),
+            Wall,
+        ));
+        let hidden = add_ball(&mut world, 20.0, 0.0, RigidBodyBuilder::dynamic().build());
+        let seen = add_ball(&mut world, 0.0, 20.0, RigidBodyBuilder::dynamic().build());
+        world.run();
+
+        let explosion = Explosion::new(vector![0.0, 0.0], 50.0)
+            .with_impulse(100.0)
+            .with_line_of_sight(EntityFilter::new().with_component::<Wall>());
+        let hits = explode(
+            &mut world.entities_and_components,
+            &explosion,
+            &EntityFilter::new(),
+        );
+
+        // the wall doesn't block itself
+        let hit_entities = hits.iter().map(|hit| hit.entity).collect::<Vec<Entity>>();
+        assert_eq!(hit_entities, vec![wall, seen]);
+        assert!(queued_impulse(&world, hidden).is_none());
+        assert!(queued_impulse(&world, seen).is_some());
+    }
+
+    #[test]
+    fn the_colliders_of_a_body_do_not_shield_each_other() {
+        let mut world = world_without_gravity();
+        let body = add_ball(&mut world, 20.0, 0.0, RigidBodyBuilder::dynamic().build());
+        // behind the ball of the body, seen from the center
+        let part = world.entities_and_components.add_entity_with((
+            Transform {
+                x: 5.0,
+                ..Default::default()
+            },
+            ColliderBuilder::ball(1.0).build(),
+        ));
+        world.entities_and_components.set_parent(part, body);
+        world.run();
+
+        // every collider blocks the line of sight
+        let explosion = Explosion::new(vector![0.0, 0.0], 50.0)
+            .with_impulse(100.0)
+            .with_line_of_sight(EntityFilter::new());
+        let hits = explode(
+            &mut world.entities_and_components,
+            &explosion,
+            &EntityFilter::new(),
+        );
+
+        let hit_entities = hits.iter().map(|hit| hit.entity).collect::<Vec<Entity>>();
+        assert_eq!(hit_entities, vec![body, part]);
+        // the body is pushed once, with the falloff of its closest collider
+        let impulse = queued_impulse(&world, body).unwrap();
+        assert!((impulse.norm() - 100.0 * explosion.get_falloff_at(19.0)).abs() < 1.0e-2);
+        assert_eq!(
+            get_physics_info(&world.entities_and_components, PhysicsWorldId::DEFAULT)
+                .unwrap()
+                .queued_commands
+                .len(),
+            1
+        );
+    }
+}
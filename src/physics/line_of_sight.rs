@@ -0,0 +1,319 @@
+use rapier2d::prelude::*;
+use ABC_ECS::EntitiesAndComponents;
+use ABC_ECS::Entity;
+
+use crate::get_transform;
+use crate::physics::physics_system::RapierPhysicsInfo;
+
+/// true if the entity is the given ancestor or one of its descendants
+fn is_self_or_descendant_of(
+    entity: Entity,
+    ancestor: Entity,
+    entities_and_components: &EntitiesAndComponents,
+) -> bool {
+    let mut current = Some(entity);
+    while let Some(current_entity) = current {
+        if current_entity == ancestor {
+            return true;
+        }
+
+        current = entities_and_components.get_parent(current_entity);
+    }
+
+    false
+}
+
+/// the position of the entity in the world, in pixels
+fn get_world_position(
+    entity: Entity,
+    entities_and_components: &EntitiesAndComponents,
+) -> Vector<Real> {
+    let transform = get_transform(entity, entities_and_components);
+    vector![transform.x as Real, transform.y as Real]
+}
+
+// these use the world transforms of the entities, so they work for entities without a rigidbody too
+impl RapierPhysicsInfo {
+    /// runs the query with a filter that also skips every collider on the observer and its children
+    fn with_observer_excluded<R>(
+        &self,
+        entities_and_components: &EntitiesAndComponents,
+        observer: Entity,
+        filter: QueryFilter,
+        query: impl FnOnce(QueryFilter) -> R,
+    ) -> R {
+        let user_predicate = filter.predicate;
+        let predicate = |handle: ColliderHandle, collider: &Collider| {
+            let is_observer = self
+                .get_associated_entity_with_collider_handle(handle.into())
+                .is_some_and(|entity| {
+                    is_self_or_descendant_of(entity, observer, entities_and_components)
+                });
+
+            !is_observer && user_predicate.map_or(true, |predicate| predicate(handle, collider))
+        };
+
+        query(QueryFilter {
+            flags: filter.flags,
+            groups: filter.groups,
+            exclude_collider: filter.exclude_collider,
+            exclude_rigid_body: filter.exclude_rigid_body,
+            predicate: Some(&predicate),
+        })
+    }
+
+    /// Checks if a straight line from the observer to the target is not blocked by any collider that passes the filter.
+    /// The colliders of the observer and the target, including the ones on their children, never block the line.
+    /// The other colliders of the target's rigidbody do, so when the target is a part of a body, like a weak spot
+    /// on a child entity, it is only seen if the rest of the body isn't in front of it.
+    pub fn has_line_of_sight(
+        &self,
+        entities_and_components: &EntitiesAndComponents,
+        observer: Entity,
+        target: Entity,
+        filter: QueryFilter,
+    ) -> bool {
+        let from = get_world_position(observer, entities_and_components);
+        let to = get_world_position(target, entities_and_components);
+        if from == to {
+            return true;
+        }
+
+        // the ray is as long as the distance, so a max toi of 1 stops it at the target
+        let ray = Ray::new(Point::from(from), to - from);
+        self.with_observer_excluded(
+            entities_and_components,
+            observer,
+            filter,
+            |filter| match self.cast_ray(&ray, 1.0, true, filter) {
+                Some((hit_entity, _)) => {
+                    is_self_or_descendant_of(hit_entity, target, entities_and_components)
+                }
+                None => true,
+            },
+        )
+    }
+
+    /// Finds every entity with a collider that the observer can see, closest first.
+    /// The observer looks along its local x axis, angle is the full width of the cone in radians,
+    /// and an entity is in the cone if its position is within range and the angle.
+    /// The target filter decides what can be seen and the occluder filter decides what blocks the view,
+    /// so walls can block the view without being seen themselves, see has_line_of_sight.
+    pub fn entities_in_vision_cone(
+        &self,
+        entities_and_components: &EntitiesAndComponents,
+        observer: Entity,
+        angle: Real,
+        range: Real,
+        target_filter: QueryFilter,
+        occluder_filter: QueryFilter,
+    ) -> Vec<Entity> {
+        let observer_transform = get_transform(observer, entities_and_components);
+        let origin = vector![observer_transform.x as Real, observer_transform.y as Real];
+        let rotation = observer_transform.rotation as Real;
+        let forward = vector![rotation.cos(), rotation.sin()];
+
+        let mut candidates = vec![];
+        self.with_observer_excluded(entities_and_components, observer, target_filter, |filter| {
+            self.intersections_with_shape(
+                &Isometry::translation(origin.x, origin.y),
+                &Ball::new(range),
+                filter,
+                |entity| {
+                    if !candidates.contains(&entity) {
+                        candidates.push(entity);
+                    }
+                    true
+                },
+            )
+        });
+
+        let mut seen = candidates
+            .into_iter()
+            .filter_map(|entity| {
+                let offset = get_world_position(entity, entities_and_components) - origin;
+                let distance = offset.norm();
+                if distance > range {
+                    return None;
+                }
+                if distance > 0.0 && forward.angle(&offset) > angle / 2.0 {
+                    return None;
+                }
+                if !self.has_line_of_sight(
+                    entities_and_components,
+                    observer,
+                    entity,
+                    occluder_filter,
+                ) {
+                    return None;
+                }
+
+                Some((entity, distance))
+            })
+            .collect::<Vec<(Entity, Real)>>();
+
+        seen.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        seen.into_iter().map(|(entity, _)| entity).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::physics::physics_queries::{EntityFilter, EntityQuery};
+    use crate::physics::physics_test_world;
+    use crate::Transform;
+    use rapier2d::prelude::*;
+    use ABC_ECS::Entity;
+    use ABC_ECS::World;
+
+    struct Wall;
+    struct Glass;
+
+    fn add_fixed(world: &mut World, x: f64, y: f64, collider: Collider) -> Entity {
+        world.entities_and_components.add_entity_with((
+            Transform {
+                x,
+                y,
+                ..Default::default()
+            },
+            RigidBodyBuilder::fixed().build(),
+            collider,
+        ))
+    }
+
+    fn add_observer(world: &mut World) -> Entity {
+        world
+            .entities_and_components
+            .add_entity_with((Transform::default(),))
+    }
+
+    #[test]
+    fn only_colliders_that_match_the_filter_block_the_line() {
+        let mut world = physics_test_world();
+        let observer = add_observer(&mut world);
+        let glass = add_fixed(
+            &mut world,
+            10.0,
+            0.0,
+            ColliderBuilder::cuboid(1.0, 5.0).build(),
+        );
+        world.entities_and_components.add_component_to(glass, Glass);
+        let behind_glass = add_fixed(&mut world, 20.0, 0.0, ColliderBuilder::ball(1.0).build());
+        let wall = add_fixed(
+            &mut world,
+            0.0,
+            10.0,
+            ColliderBuilder::cuboid(5.0, 1.0).build(),
+        );
+        world.entities_and_components.add_component_to(wall, Wall);
+        let behind_wall = add_fixed(&mut world, 0.0, 20.0, ColliderBuilder::ball(1.0).build());
+        world.run();
+
+        let query = EntityQuery::new(&world.entities_and_components);
+        let see_through_glass = EntityFilter::new().without_component::<Glass>();
+        assert!(query.has_line_of_sight(observer, behind_glass, &see_through_glass));
+        assert!(!query.has_line_of_sight(observer, behind_glass, &EntityFilter::new()));
+        assert!(!query.has_line_of_sight(observer, behind_wall, &see_through_glass));
+        // the target doesn't block itself
+        assert!(query.has_line_of_sight(observer, wall, &EntityFilter::new()));
+    }
+
+    #[test]
+    fn the_colliders_of_the_observer_do_not_block_the_line() {
+        let mut world = physics_test_world();
+        let observer = add_fixed(&mut world, 0.0, 0.0, ColliderBuilder::ball(5.0).build());
+        let hand = world.entities_and_components.add_entity_with((
+            Transform {
+                x: 6.0,
+                ..Default::default()
+            },
+            ColliderBuilder::ball(1.0).build(),
+        ));
+        world.entities_and_components.set_parent(hand, observer);
+        let target = add_fixed(&mut world, 20.0, 0.0, ColliderBuilder::ball(1.0).build());
+        world.run();
+
+        let query = EntityQuery::new(&world.entities_and_components);
+        assert!(query.has_line_of_sight(observer, target, &EntityFilter::new()));
+    }
+
+    #[test]
+    fn the_rest_of_the_body_blocks_a_part_of_it() {
+        let mut world = physics_test_world();
+        let observer = add_observer(&mut world);
+        let body = add_fixed(&mut world, 20.0, 0.0, ColliderBuilder::ball(2.0).build());
+        // on the far side of the body
+        let weak_spot = world.entities_and_components.add_entity_with((
+            Transform {
+                x: 3.0,
+                ..Default::default()
+            },
+            ColliderBuilder::ball(0.5).build(),
+        ));
+        world.entities_and_components.set_parent(weak_spot, body);
+        world.run();
+
+        let query = EntityQuery::new(&world.entities_and_components);
+        assert!(!query.has_line_of_sight(observer, weak_spot, &EntityFilter::new()));
+        // the weak spot is a child of the body, so it never blocks the body itself
+        assert!(query.has_line_of_sight(observer, body, &EntityFilter::new()));
+    }
+
+    #[test]
+    fn the_vision_cone_sees_targets_and_is_blocked_by_occluders() {
+        let mut world = physics_test_world();
+        let observer = add_observer(&mut world);
+        // 42 degrees to the left of where the observer looks
+        let inside_edge = add_fixed(&mut world, 10.0, 9.0, ColliderBuilder::ball(1.0).build());
+        // 48 degrees
+        add_fixed(&mut world, 10.0, 11.0, ColliderBuilder::ball(1.0).build());
+        add_fixed(&mut world, -10.0, 0.0, ColliderBuilder::ball(1.0).build());
+        add_fixed(&mut world, 200.0, 0.0, ColliderBuilder::ball(1.0).build());
+        let glass = add_fixed(&mut world, 15.0, 0.0, ColliderBuilder::ball(1.0).build());
+        world.entities_and_components.add_component_to(glass, Glass);
+        let behind_glass = add_fixed(&mut world, 20.0, 0.0, ColliderBuilder::ball(1.0).build());
+        let wall = add_fixed(
+            &mut world,
+            30.0,
+            0.0,
+            ColliderBuilder::cuboid(1.0, 5.0).build(),
+        );
+        world.entities_and_components.add_component_to(wall, Wall);
+        add_fixed(&mut world, 40.0, 0.0, ColliderBuilder::ball(1.0).build());
+        world.run();
+
+        let query = EntityQuery::new(&world.entities_and_components);
+        let seen = query.entities_in_vision_cone(
+            observer,
+            std::f32::consts::FRAC_PI_2,
+            100.0,
+            &EntityFilter::new().without_component::<Wall>(),
+            &EntityFilter::new().with_component::<Wall>(),
+        );
+
+        // the wall blocks the view without being seen, the glass is seen without blocking the view
+        assert_eq!(seen, vec![inside_edge, glass, behind_glass]);
+    }
+
+    #[test]
+    fn the_vision_cone_turns_with_the_observer() {
+        let mut world = physics_test_world();
+        let observer = world.entities_and_components.add_entity_with((Transform {
+            rotation: std::f64::consts::FRAC_PI_2,
+            ..Default::default()
+        },));
+        let above = add_fixed(&mut world, 0.0, 10.0, ColliderBuilder::ball(1.0).build());
+        add_fixed(&mut world, 10.0, 0.0, ColliderBuilder::ball(1.0).build());
+        world.run();
+
+        let query = EntityQuery::new(&world.entities_and_components);
+        let seen = query.entities_in_vision_cone(
+            observer,
+            0.5,
+            100.0,
+            &EntityFilter::new(),
+            &EntityFilter::new(),
+        );
+        assert_eq!(seen, vec![above]);
+    }
+}
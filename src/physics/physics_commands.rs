@@ -0,0 +1,387 @@
+use fxhash::FxHashSet;
+use rapier2d::prelude::*;
+use tracing::event;
+use tracing::Level;
+use ABC_ECS::EntitiesAndComponents;
+use ABC_ECS::Entity;
+
+use crate::physics::physics_system::{RapierPhysicsInfo, RapierRigidBodyHandle, RigidBodyHandle};
+
+/// a force that is applied to a rigidbody for the steps taken in one frame
+pub(crate) type QueuedForce = (Entity, RigidBodyHandle, PhysicsCommand);
+
+/// The user force and torque a rigidbody had before forces were added to it for some steps.
+/// putting these back afterwards is exact, taking the added forces off again isn't because of float rounding
+pub(crate) struct SavedUserForce {
+    rigid_body: RapierRigidBodyHandle,
+    force: Vector<Real>,
+    torque: Real,
+}
+
+impl SavedUserForce {
+    pub(crate) fn save(rigid_body_handle: RapierRigidBodyHandle, rigid_body: &RigidBody) -> Self {
+        Self {
+            rigid_body: rigid_body_handle,
+            force: rigid_body.user_force(),
+            torque: rigid_body.user_torque(),
+        }
+    }
+}
+
+/// puts the user forces of the rigidbodies back to what they were saved as
+pub(crate) fn restore_user_forces(
+    rigid_body_set: &mut RigidBodySet,
+    saved_forces: Vec<SavedUserForce>,
+) {
+    for saved_force in saved_forces {
+        if let Some(rigid_body) = rigid_body_set.get_mut(saved_force.rigid_body) {
+            rigid_body.reset_forces(false);
+            rigid_body.add_force(saved_force.force, false);
+            rigid_body.add_torque(saved_force.torque, false);
+        }
+    }
+}
+
+/// something to do to the rigidbody on an entity, queued with the methods on RapierPhysicsInfo
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum PhysicsCommand {
+    ApplyImpulse(Vector<Real>),
+    ApplyImpulseAtPoint(Vector<Real>, Point<Real>),
+    ApplyTorqueImpulse(Real),
+    ApplyForce(Vector<Real>),
+    ApplyTorque(Real),
+    SetLinearVelocity(Vector<Real>),
+    SetAngularVelocity(Real),
+    Teleport(Isometry<Real>),
+}
+
+impl PhysicsCommand {
+    /// forces only do anything while the world is being stepped, so they are kept until a step is taken
+    fn is_force(&self) -> bool {
+        matches!(
+            self,
+            PhysicsCommand::ApplyForce(_) | PhysicsCommand::ApplyTorque(_)
+        )
+    }
+}
+
+// these only queue the command, the physics system applies them after it has read the components
+// and before it steps, so they never get overwritten by the RigidBody component or the pull back into it
+impl RapierPhysicsInfo {
+    /// applies an impulse at the center of mass of the rigidbody on the entity
+    pub fn apply_impulse(&mut self, entity: Entity, impulse: Vector<Real>) {
+        self.queue_command(entity, PhysicsCommand::ApplyImpulse(impulse));
+    }
+
+    /// applies an impulse at the given point (in world space) of the rigidbody on the entity
+    pub fn apply_impulse_at_point(
+        &mut self,
+        entity: Entity,
+        impulse: Vector<Real>,
+        point: Point<Real>,
+    ) {
+        self.queue_command(entity, PhysicsCommand::ApplyImpulseAtPoint(impulse, point));
+    }
+
+    /// applies an angular impulse to the rigidbody on the entity
+    pub fn apply_torque_impulse(&mut self, entity: Entity, torque_impulse: Real) {
+        self.queue_command(entity, PhysicsCommand::ApplyTorqueImpulse(torque_impulse));
+    }
+
+    /// applies a force to the rigidbody on the entity for every step taken in the next frame that steps the world
+    pub fn apply_force(&mut self, entity: Entity, force: Vector<Real>) {
+        self.queue_command(entity, PhysicsCommand::ApplyForce(force));
+    }
+
+    /// applies a torque to the rigidbody on the entity for every step taken in the next frame that steps the world
+    pub fn apply_torque(&mut self, entity: Entity, torque: Real) {
+        self.queue_command(entity, PhysicsCommand::ApplyTorque(torque));
+    }
+
+    pub fn set_linear_velocity(&mut self, entity: Entity, velocity: Vector<Real>) {
+        self.queue_command(entity, PhysicsCommand::SetLinearVelocity(velocity));
+    }
+
+    pub fn set_angular_velocity(&mut self, entity: Entity, velocity: Real) {
+        self.queue_command(entity, PhysicsCommand::SetAngularVelocity(velocity));
+    }
+
+    /// moves the rigidbody on the entity to the given world position and rotation without sweeping it there
+    /// the Transform of the entity is updated to match at the end of the frame
+    pub fn teleport(&mut self, entity: Entity, position: Vector<Real>, rotation: Real) {
+        self.queue_command(
+            entity,
+            PhysicsCommand::Teleport(Isometry::new(position, rotation)),
+        );
+    }
+
+    fn queue_command(&mut self, entity: Entity, command: PhysicsCommand) {
+        self.queued_commands.push((entity, command));
+    }
+
+    /// applies every queued command except forces, forces are added to the returned list instead
+    /// the handles of every body that was changed are added to out_rb_handles
+    pub(crate) fn apply_queued_commands(
+        &mut self,
+        entities_and_components: &EntitiesAndComponents,
+        out_rb_handles: &mut Vec<RigidBodyHandle>,
+    ) -> Vec<QueuedForce> {
+        let mut forces = vec![];
+
+        for (entity, command) in std::mem::take(&mut self.queued_commands) {
+            let rb_handle = entities_and_components
+                .try_get_components::<(RigidBodyHandle,)>(entity)
+                .0
+                .copied()
+                .filter(|rb_handle| self.rigid_body_handle_map.get(rb_handle) == Some(&entity));
+
+            let Some(rb_handle) = rb_handle else {
+                event!(
+                    Level::WARN,
+                    "physics command sent to an entity without a rigidbody in this physics world, the command will be ignored"
+                );
+                continue;
+            };
+
+            if command.is_force() {
+                forces.push((entity, rb_handle, command));
+                continue;
+            }
+
+            // commands are given in pixels, angular values don't change but torques scale with distance squared
+            let ppm = self.pixels_per_meter;
+            let rigidbody = &mut self.rigid_body_set[rb_handle.0];
+            match command {
+                PhysicsCommand::ApplyImpulse(impulse) => {
+                    rigidbody.apply_impulse(impulse / ppm, true)
+                }
+                PhysicsCommand::ApplyImpulseAtPoint(impulse, point) => {
+                    rigidbody.apply_impulse_at_point(impulse / ppm, point / ppm, true)
+                }
+                PhysicsCommand::ApplyTorqueImpulse(torque_impulse) => {
+                    rigidbody.apply_torque_impulse(torque_impulse / (ppm * ppm), true)
+                }
+                PhysicsCommand::SetLinearVelocity(velocity) => {
+                    rigidbody.set_linvel(velocity / ppm, true)
+                }
+                PhysicsCommand::SetAngularVelocity(velocity) => {
+                    rigidbody.set_angvel(velocity, true)
+                }
+                PhysicsCommand::Teleport(mut position) => {
+                    position.translation.vector /= ppm;
+                    rigidbody.set_position(position, true)
+                }
+                PhysicsCommand::ApplyForce(_) | PhysicsCommand::ApplyTorque(_) => unreachable!(),
+            }
+
+            out_rb_handles.push(rb_handle);
+        }
+
+        forces
+    }
+
+    /// adds the forces to their rigidbodies, the user forces they had before are returned
+    /// so they can be put back with restore_user_forces after the steps
+    pub(crate) fn apply_forces(&mut self, forces: &[QueuedForce]) -> Vec<SavedUserForce> {
+        let ppm = self.pixels_per_meter;
+        let mut saved_forces = vec![];
+        let mut saved_rigid_bodies = FxHashSet::default();
+
+        for (_, rb_handle, command) in forces {
+            let Some(rigidbody) = self.rigid_body_set.get_mut(rb_handle.0) else {
+                continue;
+            };
+
+            if saved_rigid_bodies.insert(*rb_handle) {
+                saved_forces.push(SavedUserForce::save(rb_handle.0, rigidbody));
+            }
+
+            match command {
+                PhysicsCommand::ApplyForce(force) => rigidbody.add_force(*force / ppm, true),
+                PhysicsCommand::ApplyTorque(torque) => {
+                    rigidbody.add_torque(*torque / (ppm * ppm), true)
+                }
+                _ => {}
+            }
+        }
+
+        saved_forces
+    }
+
+    /// puts forces back in the queue, used when a frame doesn't step the world
+    pub(crate) fn requeue_forces(&mut self, forces: Vec<QueuedForce>) {
+        self.queued_commands.extend(
+            forces
+                .into_iter()
+                .map(|(entity, _, command)| (entity, command)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::physics_settings::PhysicsSettings;
+    use crate::physics::physics_system::RapierPhysicsSystem;
+    use crate::physics::physics_test_world;
+    use crate::Transform;
+    use ABC_ECS::System;
+    use ABC_ECS::World;
+
+    fn world_with_a_body() -> (World, Entity) {
+        let mut world = physics_test_world();
+        physics_info(&mut world).set_gravity(vector![0.0, 0.0]);
+
+        let entity = world.entities_and_components.add_entity_with((
+            Transform::default(),
+            RigidBodyBuilder::dynamic().build(),
+            ColliderBuilder::ball(1.0).build(),
+        ));
+        run_without_stepping(&mut world);
+
+        (world, entity)
+    }
+
+    /// the delta time isn't updated when the system is run on its own, so no steps are taken
+    fn run_without_stepping(world: &mut World) {
+        RapierPhysicsSystem {}.run(&mut world.entities_and_components);
+    }
+
+    fn physics_info(world: &mut World) -> &mut RapierPhysicsInfo {
+        world
+            .entities_and_components
+            .get_resource_mut::<RapierPhysicsInfo>()
+            .unwrap()
+    }
+
+    fn rapier_body(world: &World, entity: Entity) -> &RigidBody {
+        let (handle,) = world
+            .entities_and_components
+            .get_components::<(RigidBodyHandle,)>(entity);
+        &world
+            .entities_and_components
+            .get_resource::<RapierPhysicsInfo>()
+            .unwrap()
+            .rigid_body_set[handle.0]
+    }
+
+    #[test]
+    fn forces_wait_for_a_frame_that_steps() {
+        let (mut world, entity) = world_with_a_body();
+
+        physics_info(&mut world).apply_force(entity, vector![10.0, 0.0]);
+        physics_info(&mut world).apply_impulse(entity, vector![0.0, 2.0]);
+        run_without_stepping(&mut world);
+
+        // the impulse is applied right away, the force is kept for the next step
+        let rigid_body = rapier_body(&world, entity);
+        assert_eq!(rigid_body.linvel().x, 0.0);
+        assert!(rigid_body.linvel().y > 0.0);
+        assert_eq!(rigid_body.user_force(), vector![0.0, 0.0]);
+        assert_eq!(physics_info(&mut world).queued_commands.len(), 1);
+
+        // make sure the next frame steps
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        world.run();
+
+        // the force was used and taken off again
+        let rigid_body = rapier_body(&world, entity);
+        assert!(rigid_body.linvel().x > 0.0);
+        assert_eq!(rigid_body.user_force(), vector![0.0, 0.0]);
+        assert!(physics_info(&mut world).queued_commands.is_empty());
+    }
+
+    #[test]
+    fn teleports_are_pulled_back_without_a_step() {
+        let (mut world, entity) = world_with_a_body();
+
+        physics_info(&mut world).teleport(entity, vector![3.0, 4.0], 0.0);
+        run_without_stepping(&mut world);
+
+        assert_eq!(
+            *rapier_body(&world, entity).translation(),
+            vector![3.0, 4.0]
+        );
+        let (transform,) = world
+            .entities_and_components
+            .get_components::<(Transform,)>(entity);
+        assert_eq!((transform.x, transform.y), (3.0, 4.0));
+    }
+
+    #[test]
+    fn commands_to_entities_without_a_rigidbody_are_dropped() {
+        let (mut world, _) = world_with_a_body();
+        let empty = world
+            .entities_and_components
+            .add_entity_with((Transform::default(),));
+
+        physics_info(&mut world).apply_force(empty, vector![1.0, 0.0]);
+        physics_info(&mut world).apply_impulse(empty, vector![1.0, 0.0]);
+        run_without_stepping(&mut world);
+
+        assert!(physics_info(&mut world).queued_commands.is_empty());
+    }
+
+    fn paused_world_with_a_body(user_force: Vector<Real>) -> (World, Entity) {
+        let mut world = physics_test_world();
+        world
+            .entities_and_components
+            .get_resource_mut::<PhysicsSettings>()
+            .unwrap()
+            .set_pixels_per_meter(2.0);
+        physics_info(&mut world).pause();
+        physics_info(&mut world).set_gravity(vector![0.0, 0.0]);
+
+        let mut rigid_body = RigidBodyBuilder::dynamic().build();
+        rigid_body.add_force(user_force, false);
+        let entity = world.entities_and_components.add_entity_with((
+            Transform::default(),
+            rigid_body,
+            ColliderBuilder::ball(1.0).build(),
+        ));
+        world.run();
+
+        (world, entity)
+    }
+
+    #[test]
+    fn forces_only_last_for_the_steps_of_one_frame() {
+        let (mut world, entity) = paused_world_with_a_body(vector![0.0, 0.0]);
+
+        physics_info(&mut world).apply_force(entity, vector![10.0, 0.0]);
+        physics_info(&mut world).step_once();
+        world.run();
+
+        // the force is in pixels, so it is halved before it reaches rapier
+        let rigid_body = rapier_body(&world, entity);
+        let expected_velocity = 5.0 / rigid_body.mass() / 60.0;
+        assert!((rigid_body.linvel().x - expected_velocity).abs() < 1.0e-4);
+        assert_eq!(rigid_body.user_force(), vector![0.0, 0.0]);
+
+        // the force was taken off again, so the next step doesn't speed the body up
+        physics_info(&mut world).step_once();
+        world.run();
+        let rigid_body = rapier_body(&world, entity);
+        assert!((rigid_body.linvel().x - expected_velocity).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn the_user_force_of_the_rigidbody_is_put_back_exactly() {
+        let user_force = vector![0.1, 0.0];
+        let (mut world, entity) = paused_world_with_a_body(user_force);
+
+        // subtracting a force this big again would round the user force away
+        physics_info(&mut world).apply_force(entity, vector![1.0e7, 0.0]);
+        physics_info(&mut world).apply_torque(entity, 1.0e7);
+        physics_info(&mut world).step_once();
+        world.run();
+
+        let rigid_body = rapier_body(&world, entity);
+        assert_eq!(rigid_body.user_force(), user_force);
+        assert_eq!(rigid_body.user_torque(), 0.0);
+        let (ecs_rigid_body,) = world
+            .entities_and_components
+            .get_components::<(RigidBody,)>(entity);
+        assert_eq!(ecs_rigid_body.user_force(), user_force);
+    }
+}
@@ -0,0 +1,167 @@
+use rapier2d::prelude::*;
+use ABC_ECS::EntitiesAndComponents;
+use ABC_ECS::Entity;
+
+use crate::physics::physics_system::{ColliderHandle, RapierPhysicsInfo};
+
+/// A contact between the collider on an entity and another collider, see RapierPhysicsInfo::get_contacts
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhysicsContact {
+    /// the entity with the other collider
+    pub other: Entity,
+    /// points from the collider towards the other collider
+    pub normal: Vector<Real>,
+    /// where the colliders touch in the world, in pixels
+    pub points: Vec<Point<Real>>,
+    /// how far the colliders overlap at the deepest point, in pixels
+    pub penetration: Real,
+}
+
+// pausing is per physics world and ignores DeltaTime completely, so the time scale doesn't matter.
+// everything is still synced and the query pipeline is still updated while paused,
+// so new colliders show up in queries and raycasts keep working
+impl RapierPhysicsInfo {
+    /// stops stepping the physics world until resume is called
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// starts stepping the physics world again, steps that were asked for and not taken yet are dropped
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.requested_steps = 0;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// takes exactly one fixed step on the next frame, only does anything while paused or in deterministic mode
+    pub fn step_once(&mut self) {
+        self.step_n(1);
+    }
+
+    /// Takes exactly the given number of fixed steps on the next frame, only does anything while paused or in deterministic mode.
+    /// in deterministic mode this is how the physics world is moved forward, see PhysicsSettings::set_deterministic
+    pub fn step_n(&mut self, steps: usize) {
+        self.requested_steps += steps;
+    }
+
+    /// Every collider touching the collider on the given entity, as of the last step.
+    /// only contacts between solid colliders are found, use the intersection queries for sensors
+    pub fn get_contacts(
+        &self,
+        entities_and_components: &EntitiesAndComponents,
+        entity: Entity,
+    ) -> Vec<PhysicsContact> {
+        let collider_handle = entities_and_components
+            .try_get_components::<(ColliderHandle,)>(entity)
+            .0
+            .copied()
+            .filter(|collider_handle| {
+                self.collider_handle_map.get(collider_handle) == Some(&entity)
+            });
+        let Some(collider_handle) = collider_handle else {
+            return vec![];
+        };
+
+        let mut contacts = vec![];
+        for contact_pair in self.narrow_phase.contact_pairs_with(collider_handle.0) {
+            if !contact_pair.has_any_active_contact {
+                continue;
+            }
+
+            // the normals point from the first collider to the second
+            let (other_handle, sign) = if contact_pair.collider1 == collider_handle.0 {
+                (contact_pair.collider2, 1.0)
+            } else {
+                (contact_pair.collider1, -1.0)
+            };
+            let Some(other) = self
+                .collider_handle_map
+                .get(&ColliderHandle(other_handle))
+                .copied()
+            else {
+                continue;
+            };
+
+            for manifold in &contact_pair.manifolds {
+                if manifold.data.solver_contacts.is_empty() {
+                    continue;
+                }
+
+                let points = manifold
+                    .data
+                    .solver_contacts
+                    .iter()
+                    .map(|contact| contact.point * self.pixels_per_meter)
+                    .collect();
+                let penetration = manifold
+                    .data
+                    .solver_contacts
+                    .iter()
+                    .map(|contact| -contact.dist)
+                    .fold(0.0, Real::max)
+                    * self.pixels_per_meter;
+
+                contacts.push(PhysicsContact {
+                    other,
+                    normal: manifold.data.normal * sign,
+                    points,
+                    penetration,
+                });
+            }
+        }
+
+        contacts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::physics::physics_test_world;
+    use crate::physics::physics_worlds::{get_physics_info_mut, PhysicsWorldId};
+    use crate::Transform;
+    use rapier2d::prelude::*;
+    use ABC_ECS::World;
+
+    fn physics_info(world: &mut World) -> &mut crate::physics::physics_system::RapierPhysicsInfo {
+        get_physics_info_mut(&mut world.entities_and_components, PhysicsWorldId::DEFAULT).unwrap()
+    }
+
+    #[test]
+    fn only_the_steps_asked_for_are_taken_while_paused() {
+        let mut world = physics_test_world();
+        physics_info(&mut world).pause();
+        let ball = world.entities_and_components.add_entity_with((
+            Transform::default(),
+            RigidBodyBuilder::dynamic().build(),
+            ColliderBuilder::ball(1.0).build(),
+        ));
+        let step_time = 1.0 / 60.0;
+
+        for _ in 0..3 {
+            world.run();
+        }
+        assert_eq!(physics_info(&mut world).simulated_time, 0.0);
+
+        physics_info(&mut world).step_once();
+        world.run();
+        assert!((physics_info(&mut world).simulated_time - step_time).abs() < 1.0e-6);
+        let (transform,) = world
+            .entities_and_components
+            .get_components::<(Transform,)>(ball);
+        assert!(transform.y < 0.0);
+
+        physics_info(&mut world).step_n(3);
+        world.run();
+        world.run();
+        assert!((physics_info(&mut world).simulated_time - 4.0 * step_time).abs() < 1.0e-6);
+
+        // steps that weren't taken yet are dropped when resuming
+        physics_info(&mut world).step_n(2);
+        physics_info(&mut world).resume();
+        assert!(!physics_info(&mut world).is_paused());
+        assert_eq!(physics_info(&mut world).requested_steps, 0);
+    }
+}
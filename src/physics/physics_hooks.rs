@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+
+use rapier2d::prelude::*;
+use ABC_ECS::EntitiesAndComponents;
+use ABC_ECS::Entity;
+
+use crate::physics::physics_system::ColliderHandle;
+
+/// The info passed to a physics hook when deciding if two entities should collide.
+pub struct EntityPairContext<'a> {
+    pub entity1: Entity,
+    pub entity2: Entity,
+    /// the world the entities are in, the bodies and colliders of the world being stepped are taken out of its physics info during the step
+    pub entities_and_components: &'a EntitiesAndComponents,
+    /// the raw rapier context, for access to the rigid bodies and colliders
+    pub raw: &'a PairFilterContext<'a>,
+}
+
+/// The info passed to a physics hook when modifying the contacts between two entities.
+pub struct EntityContactContext<'a, 'b> {
+    pub entity1: Entity,
+    pub entity2: Entity,
+    /// the world the entities are in, the bodies and colliders of the world being stepped are taken out of its physics info during the step
+    pub entities_and_components: &'a EntitiesAndComponents,
+    /// the raw rapier context, this is where the solver contacts are modified
+    pub raw: &'a mut ContactModificationContext<'b>,
+}
+
+/// A hook that is run by rapier during the physics step, register it with RapierPhysicsInfo::add_physics_hook
+/// hooks are only run for colliders that have the matching ActiveHooks flags, see PhysicsHook::active_hooks
+/// rapier needs its hooks to be Send and Sync, so physics hooks have to be too
+pub trait PhysicsHook: Send + Sync + 'static {
+    /// decides which hooks are enabled for the collider on the given entity
+    /// this is called every frame, so adding a component to an entity will enable the hook on the next frame,
+    /// and removing it will disable the hook again
+    /// by default every hook is enabled for every collider
+    fn active_hooks(
+        &self,
+        _entity: Entity,
+        _entities_and_components: &EntitiesAndComponents,
+    ) -> ActiveHooks {
+        ActiveHooks::FILTER_CONTACT_PAIRS
+            | ActiveHooks::FILTER_INTERSECTION_PAIR
+            | ActiveHooks::MODIFY_SOLVER_CONTACTS
+    }
+
+    /// return None to ignore the contact between the two entities
+    fn filter_contact_pair(&self, _context: &EntityPairContext) -> Option<SolverFlags> {
+        Some(SolverFlags::COMPUTE_IMPULSES)
+    }
+
+    /// return false to ignore the intersection between the two entities, only called if one of them is a sensor
+    fn filter_intersection_pair(&self, _context: &EntityPairContext) -> bool {
+        true
+    }
+
+    /// modify the solver contacts between the two entities, for example to make a one way platform or a conveyor belt
+    fn modify_solver_contacts(&self, _context: &mut EntityContactContext) {}
+}
+
+/// A component that makes the collider on the entity only block things coming from one side
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OneWayPlatform {
+    /// the direction (local to the platform) that things are allowed to land on, (0, 1) is the top
+    pub allowed_normal: Vector<Real>,
+    /// how far from the allowed normal (in radians) a contact can be and still be blocked
+    pub allowed_angle: Real,
+}
+
+impl Default for OneWayPlatform {
+    fn default() -> Self {
+        Self {
+            allowed_normal: vector![0.0, 1.0],
+            allowed_angle: 0.1,
+        }
+    }
+}
+
+impl OneWayPlatform {
+    pub fn new(allowed_normal: Vector<Real>, allowed_angle: Real) -> Self {
+        Self {
+            allowed_normal,
+            allowed_angle,
+        }
+    }
+}
+
+/// The hook behind the OneWayPlatform component, this is added by default
+pub(crate) struct OneWayPlatformHook;
+
+impl PhysicsHook for OneWayPlatformHook {
+    fn active_hooks(
+        &self,
+        entity: Entity,
+        entities_and_components: &EntitiesAndComponents,
+    ) -> ActiveHooks {
+        match entities_and_components
+            .try_get_components::<(OneWayPlatform,)>(entity)
+            .0
+        {
+            Some(_) => ActiveHooks::MODIFY_SOLVER_CONTACTS,
+            None => ActiveHooks::empty(),
+        }
+    }
+
+    fn modify_solver_contacts(&self, context: &mut EntityContactContext) {
+        let (platform1,) = context
+            .entities_and_components
+            .try_get_components::<(OneWayPlatform,)>(context.entity1);
+        let (platform2,) = context
+            .entities_and_components
+            .try_get_components::<(OneWayPlatform,)>(context.entity2);
+
+        let (platform, platform_collider, platform_is_collider2) = match (platform1, platform2) {
+            (Some(platform), _) => (*platform, context.raw.collider1, false),
+            (None, Some(platform)) => (*platform, context.raw.collider2, true),
+            (None, None) => return,
+        };
+
+        // rapier wants the normal local to collider 1 and pointing away from it,
+        // so it is moved from the platform's frame to the world and then into collider 1's frame
+        let colliders = context.raw.colliders;
+        let world_normal = colliders[platform_collider].rotation() * platform.allowed_normal;
+        let mut allowed_normal = colliders[context.raw.collider1]
+            .rotation()
+            .inverse_transform_vector(&world_normal);
+        if platform_is_collider2 {
+            allowed_normal = -allowed_normal;
+        }
+
+        context
+            .raw
+            .update_as_oneway_platform(&allowed_normal, platform.allowed_angle);
+    }
+}
+
+/// Connects the entity based physics hooks to rapier
+pub(crate) struct PhysicsHookAdapter<'a> {
+    pub(crate) hooks: &'a [Box<dyn PhysicsHook>],
+    pub(crate) collider_handle_map: &'a HashMap<ColliderHandle, Entity>,
+    pub(crate) entities_and_components: OwnerThreadOnly<'a>,
+}
+
+/// A reference to the world that can only be used on the thread it was made on.
+/// rapier wants its hooks to be Send and Sync but the world isn't, so the thread is checked on every use instead
+pub(crate) struct OwnerThreadOnly<'a> {
+    entities_and_components: &'a EntitiesAndComponents,
+    owner: std::thread::ThreadId,
+}
+
+impl<'a> OwnerThreadOnly<'a> {
+    pub(crate) fn new(entities_and_components: &'a EntitiesAndComponents) -> Self {
+        Self {
+            entities_and_components,
+            owner: std::thread::current().id(),
+        }
+    }
+
+    fn get(&self) -> &'a EntitiesAndComponents {
+        assert!(
+            std::thread::current().id() == self.owner,
+            "physics hooks can only be run on the thread that steps the physics world"
+        );
+        self.entities_and_components
+    }
+}
+
+// SAFETY: the reference is only ever handed out on the thread that made it, see OwnerThreadOnly::get
+unsafe impl Send for OwnerThreadOnly<'_> {}
+unsafe impl Sync for OwnerThreadOnly<'_> {}
+
+impl PhysicsHookAdapter<'_> {
+    fn get_entities(
+        &self,
+        collider1: rapier2d::prelude::ColliderHandle,
+        collider2: rapier2d::prelude::ColliderHandle,
+    ) -> Option<(Entity, Entity)> {
+        let entity1 = self.collider_handle_map.get(&ColliderHandle(collider1))?;
+        let entity2 = self.collider_handle_map.get(&ColliderHandle(collider2))?;
+
+        Some((*entity1, *entity2))
+    }
+}
+
+impl PhysicsHooks for PhysicsHookAdapter<'_> {
+    fn filter_contact_pair(&self, context: &PairFilterContext) -> Option<SolverFlags> {
+        let Some((entity1, entity2)) = self.get_entities(context.collider1, context.collider2)
+        else {
+            return Some(SolverFlags::COMPUTE_IMPULSES);
+        };
+
+        let entity_context = EntityPairContext {
+            entity1,
+            entity2,
+            entities_and_components: self.entities_and_components.get(),
+            raw: context,
+        };
+
+        // every hook has to agree for the contact to be kept
+        let mut solver_flags = SolverFlags::COMPUTE_IMPULSES;
+        for hook in self.hooks {
+            solver_flags &= hook.filter_contact_pair(&entity_context)?;
+        }
+
+        Some(solver_flags)
+    }
+
+    fn filter_intersection_pair(&self, context: &PairFilterContext) -> bool {
+        let Some((entity1, entity2)) = self.get_entities(context.collider1, context.collider2)
+        else {
+            return true;
+        };
+
+        let entity_context = EntityPairContext {
+            entity1,
+            entity2,
+            entities_and_components: self.entities_and_components.get(),
+            raw: context,
+        };
+
+        self.hooks
+            .iter()
+            .all(|hook| hook.filter_intersection_pair(&entity_context))
+    }
+
+    fn modify_solver_contacts(&self, context: &mut ContactModificationContext) {
+        let Some((entity1, entity2)) = self.get_entities(context.collider1, context.collider2)
+        else {
+            return;
+        };
+
+        let mut entity_context = EntityContactContext {
+            entity1,
+            entity2,
+            entities_and_components: self.entities_and_components.get(),
+            raw: context,
+        };
+
+        for hook in self.hooks {
+            hook.modify_solver_contacts(&mut entity_context);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::physics_test_world;
+    use crate::physics::physics_worlds::{get_physics_info, get_physics_info_mut, PhysicsWorldId};
+    use crate::Transform;
+
+    fn rapier_active_hooks(
+        entities_and_components: &EntitiesAndComponents,
+        entity: Entity,
+    ) -> ActiveHooks {
+        let (handle,) = entities_and_components.get_components::<(ColliderHandle,)>(entity);
+        get_physics_info(entities_and_components, PhysicsWorldId::DEFAULT)
+            .unwrap()
+            .collider_set[handle.0]
+            .active_hooks()
+    }
+
+    #[test]
+    fn removing_a_one_way_platform_disables_the_hook() {
+        let mut world = physics_test_world();
+        let platform = world.entities_and_components.add_entity_with((
+            Transform::default(),
+            ColliderBuilder::cuboid(10.0, 1.0)
+                .active_hooks(ActiveHooks::FILTER_CONTACT_PAIRS)
+                .build(),
+            OneWayPlatform::default(),
+        ));
+        world.run();
+        assert_eq!(
+            rapier_active_hooks(&world.entities_and_components, platform),
+            ActiveHooks::FILTER_CONTACT_PAIRS | ActiveHooks::MODIFY_SOLVER_CONTACTS
+        );
+
+        world
+            .entities_and_components
+            .remove_component_from::<OneWayPlatform>(platform);
+        world.run();
+        // the flags the user set themselves are kept
+        assert_eq!(
+            rapier_active_hooks(&world.entities_and_components, platform),
+            ActiveHooks::FILTER_CONTACT_PAIRS
+        );
+    }
+
+    /// the heights a ball thrown up from below the platform is at for the given number of steps
+    fn ball_heights(
+        platform: OneWayPlatform,
+        platform_collider: Collider,
+        platform_rotation: f64,
+        steps: usize,
+    ) -> Vec<f64> {
+        let mut world = physics_test_world();
+        get_physics_info_mut(&mut world.entities_and_components, PhysicsWorldId::DEFAULT)
+            .unwrap()
+            .pause();
+        world.entities_and_components.add_entity_with((
+            Transform {
+                rotation: platform_rotation,
+                ..Default::default()
+            },
+            platform_collider,
+            platform,
+        ));
+        let ball = world.entities_and_components.add_entity_with((
+            Transform {
+                y: -2.0,
+                ..Default::default()
+            },
+            RigidBodyBuilder::dynamic()
+                .linvel(vector![0.0, 10.0])
+                .build(),
+            ColliderBuilder::ball(0.5).build(),
+        ));
+        world.run();
+
+        (0..steps)
+            .map(|_| {
+                get_physics_info_mut(&mut world.entities_and_components, PhysicsWorldId::DEFAULT)
+                    .unwrap()
+                    .step_once();
+                world.run();
+                world
+                    .entities_and_components
+                    .get_components::<(Transform,)>(ball)
+                    .0
+                    .y
+            })
+            .collect()
+    }
+
+    #[test]
+    fn one_way_platforms_are_passed_from_below_and_landed_on_from_above() {
+        let heights = ball_heights(
+            OneWayPlatform::default(),
+            ColliderBuilder::cuboid(10.0, 0.5).build(),
+            0.0,
+            180,
+        );
+
+        // the ball went up through the platform, fell back down and landed on top of it
+        assert!(heights.iter().any(|height| *height > 2.0));
+        let landed = heights.last().unwrap();
+        assert!((landed - 1.0).abs() < 0.05, "the ball is at {landed}");
+    }
+
+    #[test]
+    fn the_allowed_normal_is_local_to_the_platform() {
+        // turned a quarter to the left, the local right of the platform is the world up
+        let platform = OneWayPlatform::new(vector![1.0, 0.0], 0.1);
+        let heights = ball_heights(
+            platform,
+            ColliderBuilder::cuboid(0.5, 10.0).build(),
+            std::f64::consts::FRAC_PI_2,
+            180,
+        );
+
+        assert!(heights.iter().any(|height| *height > 2.0));
+        let landed = heights.last().unwrap();
+        assert!((landed - 1.0).abs() < 0.05, "the ball is at {landed}");
+    }
+}
@@ -0,0 +1,619 @@
+use rapier2d::parry::query::NonlinearRigidMotion;
+use rapier2d::parry::query::ShapeCastOptions;
+use rapier2d::prelude::*;
+use ABC_ECS::Component;
+use ABC_ECS::EntitiesAndComponents;
+use ABC_ECS::Entity;
+
+use crate::physics::physics_system::{RapierPhysicsInfo, RigidBodyHandle};
+use crate::physics::physics_worlds::{get_physics_info, PhysicsWorldId};
+
+type ComponentCheck = fn(Entity, &EntitiesAndComponents) -> bool;
+
+fn has_component<T: Component>(
+    entity: Entity,
+    entities_and_components: &EntitiesAndComponents,
+) -> bool {
+    entities_and_components
+        .try_get_components::<(T,)>(entity)
+        .0
+        .is_some()
+}
+
+/// Decides which entities are taken into account by an EntityQuery.
+/// The checks are made against the entity with the collider and the entity with the rigidbody it is attached to,
+/// so a component on the root of an enemy also matches the colliders on its children.
+#[derive(Clone)]
+pub struct EntityFilter<'a> {
+    flags: QueryFilterFlags,
+    groups: Option<InteractionGroups>,
+    excluded_entities: Vec<Entity>,
+    required_components: Vec<ComponentCheck>,
+    excluded_components: Vec<ComponentCheck>,
+    predicate: Option<&'a dyn Fn(Entity, &EntitiesAndComponents) -> bool>,
+}
+
+impl Default for EntityFilter<'_> {
+    fn default() -> Self {
+        Self {
+            flags: QueryFilterFlags::empty(),
+            groups: None,
+            excluded_entities: vec![],
+            required_components: vec![],
+            excluded_components: vec![],
+            predicate: None,
+        }
+    }
+}
+
+impl<'a> EntityFilter<'a> {
+    /// a filter that lets every entity through
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// only entities with the given component are taken into account
+    pub fn with_component<T: Component>(mut self) -> Self {
+        self.required_components.push(has_component::<T>);
+        self
+    }
+
+    /// entities with the given component are ignored
+    pub fn without_component<T: Component>(mut self) -> Self {
+        self.excluded_components.push(has_component::<T>);
+        self
+    }
+
+    /// the given entity, and every collider attached to its rigidbody, is ignored
+    pub fn exclude_entity(mut self, entity: Entity) -> Self {
+        self.excluded_entities.push(entity);
+        self
+    }
+
+    /// only entities that the predicate returns true for are taken into account
+    pub fn with_predicate(
+        mut self,
+        predicate: &'a dyn Fn(Entity, &EntitiesAndComponents) -> bool,
+    ) -> Self {
+        self.predicate = Some(predicate);
+        self
+    }
+
+    /// only colliders in the given collision groups are taken into account
+    pub fn with_groups(mut self, groups: InteractionGroups) -> Self {
+        self.groups = Some(groups);
+        self
+    }
+
+    /// the same flags as rapier's QueryFilter, for example QueryFilterFlags::EXCLUDE_SENSORS
+    pub fn with_flags(mut self, flags: QueryFilterFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn exclude_sensors(mut self) -> Self {
+        self.flags |= QueryFilterFlags::EXCLUDE_SENSORS;
+        self
+    }
+
+    pub fn exclude_fixed(mut self) -> Self {
+        self.flags |= QueryFilterFlags::EXCLUDE_FIXED;
+        self
+    }
+
+    pub fn exclude_dynamic(mut self) -> Self {
+        self.flags |= QueryFilterFlags::EXCLUDE_DYNAMIC;
+        self
+    }
+
+    pub fn exclude_kinematic(mut self) -> Self {
+        self.flags |= QueryFilterFlags::EXCLUDE_KINEMATIC;
+        self
+    }
+}
+
+/// Physics queries that take and return entities, so game code never has to deal with collider handles.
+/// This borrows the world immutably, so it can't be kept around while components are being changed.
+///
+/// the entity returned by a query is always the entity with the collider that was hit
+pub struct EntityQuery<'a> {
+    physics_info: &'a RapierPhysicsInfo,
+    entities_and_components: &'a EntitiesAndComponents,
+}
+
+impl<'a> EntityQuery<'a> {
+    /// queries the default physics world
+    pub fn new(entities_and_components: &'a EntitiesAndComponents) -> Self {
+        Self::new_in_world(entities_and_components, PhysicsWorldId::DEFAULT)
+    }
+
+    /// queries the physics world with the given id
+    pub fn new_in_world(
+        entities_and_components: &'a EntitiesAndComponents,
+        world_id: PhysicsWorldId,
+    ) -> Self {
+        let physics_info = get_physics_info(entities_and_components, world_id).expect(
+            "failed to get rapier physics info, add the physics systems and the world first",
+        );
+
+        Self {
+            physics_info,
+            entities_and_components,
+        }
+    }
+
+    /// the entities a collider belongs to, the entity with the collider and the entity with its rigidbody
+    fn get_entities_of(&self, handle: ColliderHandle, collider: &Collider) -> [Option<Entity>; 2] {
+        let collider_entity = self
+            .physics_info
+            .get_associated_entity_with_collider_handle(handle.into());
+        let rigid_body_entity = collider.parent().and_then(|rb_handle| {
+            self.physics_info
+                .get_associated_entity_with_rigid_body_handle(RigidBodyHandle(rb_handle))
+        });
+
+        [collider_entity, rigid_body_entity]
+    }
+
+    /// checks if the collider passes the ecs side of the filter, the rapier side is checked by rapier
+    fn matches(&self, filter: &EntityFilter, handle: ColliderHandle, collider: &Collider) -> bool {
+        let entities = self.get_entities_of(handle, collider);
+        let entities = entities.iter().flatten().copied();
+        let world = self.entities_and_components;
+
+        if entities
+            .clone()
+            .any(|entity| filter.excluded_entities.contains(&entity))
+        {
+            return false;
+        }
+
+        let has = |check: &ComponentCheck| entities.clone().any(|entity| check(entity, world));
+
+        filter.required_components.iter().all(has)
+            && !filter.excluded_components.iter().any(has)
+            && filter.predicate.map_or(true, |predicate| {
+                entities.clone().any(|entity| predicate(entity, world))
+            })
+    }
+
+    /// same as matches but also checks the parts of the filter rapier would check, used where rapier takes no filter
+    fn matches_fully(&self, filter: &EntityFilter, handle: ColliderHandle) -> bool {
+        let Some(collider) = self.physics_info.collider_set.get(handle) else {
+            return false;
+        };
+
+        let query_filter = QueryFilter {
+            flags: filter.flags,
+            groups: filter.groups,
+            ..Default::default()
+        };
+
+        query_filter.test(&self.physics_info.rigid_body_set, handle, collider)
+            && self.matches(filter, handle, collider)
+    }
+
+    /// runs the given function with a rapier filter that does the same thing as the entity filter
+    fn with_query_filter<R>(
+        &self,
+        filter: &EntityFilter,
+        query: impl FnOnce(QueryFilter) -> R,
+    ) -> R {
+        let predicate =
+            |handle: ColliderHandle, collider: &Collider| self.matches(filter, handle, collider);
+
+        query(QueryFilter {
+            flags: filter.flags,
+            groups: filter.groups,
+            exclude_collider: None,
+            exclude_rigid_body: None,
+            predicate: Some(&predicate),
+        })
+    }
+
+    /// Find the closest entity hit by a ray, and how far along the ray it was hit.
+    /// see RapierPhysicsInfo::cast_ray
+    pub fn cast_ray(
+        &self,
+        ray: &Ray,
+        max_toi: Real,
+        solid: bool,
+        filter: &EntityFilter,
+    ) -> Option<(Entity, Real)> {
+        self.with_query_filter(filter, |query_filter| {
+            self.physics_info
+                .cast_ray(ray, max_toi, solid, query_filter)
+        })
+    }
+
+    /// Find the closest entity hit by a ray, and the normal where it was hit.
+    /// see RapierPhysicsInfo::cast_ray_and_get_normal
+    pub fn cast_ray_and_get_normal(
+        &self,
+        ray: &Ray,
+        max_toi: Real,
+        solid: bool,
+        filter: &EntityFilter,
+    ) -> Option<(Entity, RayIntersection)> {
+        self.with_query_filter(filter, |query_filter| {
+            self.physics_info
+                .cast_ray_and_get_normal(ray, max_toi, solid, query_filter)
+        })
+    }
+
+    /// Find every entity hit by a ray, the callback returns false to stop early.
+    /// see RapierPhysicsInfo::intersections_with_ray
+    pub fn intersections_with_ray(
+        &self,
+        ray: &Ray,
+        max_toi: Real,
+        solid: bool,
+        filter: &EntityFilter,
+        callback: impl FnMut(Entity, RayIntersection) -> bool,
+    ) {
+        self.with_query_filter(filter, |query_filter| {
+            self.physics_info
+                .intersections_with_ray(ray, max_toi, solid, query_filter, callback)
+        })
+    }
+
+    /// Find up to one entity intersecting the given shape.
+    /// see RapierPhysicsInfo::intersection_with_shape
+    pub fn intersection_with_shape(
+        &self,
+        shape_pos: &Isometry<Real>,
+        shape: &dyn Shape,
+        filter: &EntityFilter,
+    ) -> Option<Entity> {
+        self.with_query_filter(filter, |query_filter| {
+            self.physics_info
+                .intersection_with_shape(shape_pos, shape, query_filter)
+        })
+    }
+
+    /// Find every entity intersecting the given shape, the callback returns false to stop early.
+    /// see RapierPhysicsInfo::intersections_with_shape
+    pub fn intersections_with_shape(
+        &self,
+        shape_pos: &Isometry<Real>,
+        shape: &dyn Shape,
+        filter: &EntityFilter,
+        callback: impl FnMut(Entity) -> bool,
+    ) {
+        self.with_query_filter(filter, |query_filter| {
+            self.physics_info
+                .intersections_with_shape(shape_pos, shape, query_filter, callback)
+        })
+    }
+
+    /// Find the closest entity to a point, and where the point projects onto it.
+    /// see RapierPhysicsInfo::project_point
+    pub fn project_point(
+        &self,
+        point: &Point<Real>,
+        solid: bool,
+        filter: &EntityFilter,
+    ) -> Option<(Entity, PointProjection)> {
+        self.with_query_filter(filter, |query_filter| {
+            self.physics_info.project_point(point, solid, query_filter)
+        })
+    }
+
+    /// Find the closest entity to a point, where the point projects onto it and the feature it projects onto.
+    /// see RapierPhysicsInfo::project_point_and_get_feature
+    pub fn project_point_and_get_feature(
+        &self,
+        point: &Point<Real>,
+        filter: &EntityFilter,
+    ) -> Option<(Entity, PointProjection, FeatureId)> {
+        self.with_query_filter(filter, |query_filter| {
+            self.physics_info
+                .project_point_and_get_feature(point, query_filter)
+        })
+    }
+
+    /// Find every entity containing the given point, the callback returns false to stop early.
+    /// see RapierPhysicsInfo::intersections_with_point
+    pub fn intersections_with_point(
+        &self,
+        point: &Point<Real>,
+        filter: &EntityFilter,
+        callback: impl FnMut(Entity) -> bool,
+    ) {
+        self.with_query_filter(filter, |query_filter| {
+            self.physics_info
+                .intersections_with_point(point, query_filter, callback)
+        })
+    }
+
+    /// Find every entity with an Aabb intersecting the given Aabb, the callback returns false to stop early.
+    /// see RapierPhysicsInfo::colliders_with_aabb_intersecting_aabb
+    pub fn colliders_with_aabb_intersecting_aabb(
+        &self,
+        aabb: &Aabb,
+        filter: &EntityFilter,
+        mut callback: impl FnMut(Entity) -> bool,
+    ) {
+        let aabb = aabb.scaled(&Vector::repeat(self.physics_info.to_meters(1.0)));
+
+        // rapier doesn't filter this query, so the filter is checked here
+        self.physics_info
+            .query_pipeline
+            .colliders_with_aabb_intersecting_aabb(&aabb, |handle| {
+                if !self.matches_fully(filter, *handle) {
+                    return true;
+                }
+
+                let entity = self
+                    .physics_info
+                    .get_associated_entity_with_collider_handle((*handle).into())
+                    .expect("failed to get entity associated with collider handle, this is a bug");
+                callback(entity)
+            });
+    }
+
+    /// Casts a shape in a straight line and finds the first entity it hits.
+    /// see RapierPhysicsInfo::cast_shape
+    pub fn cast_shape(
+        &self,
+        shape_pos: &Isometry<Real>,
+        shape_vel: &Vector<Real>,
+        shape: &dyn Shape,
+        options: ShapeCastOptions,
+        filter: &EntityFilter,
+    ) -> Option<(Entity, ShapeCastHit)> {
+        self.with_query_filter(filter, |query_filter| {
+            self.physics_info
+                .cast_shape(shape_pos, shape_vel, shape, options, query_filter)
+        })
+    }
+
+    /// Casts a shape along any motion and finds the first entity it hits.
+    /// see RapierPhysicsInfo::nonlinear_cast_shape
+    pub fn nonlinear_cast_shape(
+        &self,
+        shape_motion: &NonlinearRigidMotion,
+        shape: &dyn Shape,
+        start_time: Real,
+        end_time: Real,
+        stop_at_penetration: bool,
+        filter: &EntityFilter,
+    ) -> Option<(Entity, ShapeCastHit)> {
+        self.with_query_filter(filter, |query_filter| {
+            self.physics_info.nonlinear_cast_shape(
+                shape_motion,
+                shape,
+                start_time,
+                end_time,
+                stop_at_penetration,
+                query_filter,
+            )
+        })
+    }
+
+    /// Checks if nothing that matches the filter blocks the line between the observer and the target.
+    /// see RapierPhysicsInfo::has_line_of_sight
+    pub fn has_line_of_sight(
+        &self,
+        observer: Entity,
+        target: Entity,
+        filter: &EntityFilter,
+    ) -> bool {
+        self.with_query_filter(filter, |query_filter| {
+            self.physics_info.has_line_of_sight(
+                self.entities_and_components,
+                observer,
+                target,
+                query_filter,
+            )
+        })
+    }
+
+    /// Finds every entity that matches the target filter and that the observer can see, closest first.
+    /// only entities that match the occluder filter block the view
+    /// see RapierPhysicsInfo::entities_in_vision_cone
+    pub fn entities_in_vision_cone(
+        &self,
+        observer: Entity,
+        angle: Real,
+        range: Real,
+        target_filter: &EntityFilter,
+        occluder_filter: &EntityFilter,
+    ) -> Vec<Entity> {
+        self.with_query_filter(target_filter, |target_query_filter| {
+            self.with_query_filter(occluder_filter, |occluder_query_filter| {
+                self.physics_info.entities_in_vision_cone(
+                    self.entities_and_components,
+                    observer,
+                    angle,
+                    range,
+                    target_query_filter,
+                    occluder_query_filter,
+                )
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::physics_settings::PhysicsSettings;
+    use crate::physics::physics_test_world;
+    use crate::Transform;
+    use ABC_ECS::World;
+
+    struct Wall;
+    struct Glass;
+
+    /// a ball on the x axis at each of the given x positions, in that order
+    fn world_with_balls(xs: &[f64]) -> (World, Vec<Entity>) {
+        let mut world = physics_test_world();
+        let entities = xs
+            .iter()
+            .map(|x| {
+                world.entities_and_components.add_entity_with((
+                    Transform {
+                        x: *x,
+                        ..Default::default()
+                    },
+                    RigidBodyBuilder::fixed().build(),
+                    ColliderBuilder::ball(1.0).build(),
+                ))
+            })
+            .collect();
+        // every body is fixed so it doesn't matter if a step is taken
+        world.run();
+        (world, entities)
+    }
+
+    fn first_hit(world: &World, filter: &EntityFilter) -> Option<Entity> {
+        let ray = Ray::new(point![0.0, 0.0], vector![1.0, 0.0]);
+        EntityQuery::new(&world.entities_and_components)
+            .cast_ray(&ray, 100.0, true, filter)
+            .map(|(entity, _)| entity)
+    }
+
+    #[test]
+    fn component_filters() {
+        let (mut world, entities) = world_with_balls(&[10.0, 20.0, 30.0]);
+        world
+            .entities_and_components
+            .add_component_to(entities[0], Glass);
+        world
+            .entities_and_components
+            .add_component_to(entities[1], Wall);
+        world
+            .entities_and_components
+            .add_component_to(entities[2], Wall);
+
+        assert_eq!(first_hit(&world, &EntityFilter::new()), Some(entities[0]));
+        assert_eq!(
+            first_hit(&world, &EntityFilter::new().with_component::<Wall>()),
+            Some(entities[1])
+        );
+        assert_eq!(
+            first_hit(&world, &EntityFilter::new().without_component::<Glass>()),
+            Some(entities[1])
+        );
+        assert_eq!(
+            first_hit(
+                &world,
+                &EntityFilter::new()
+                    .with_component::<Wall>()
+                    .without_component::<Glass>()
+                    .exclude_entity(entities[1])
+            ),
+            Some(entities[2])
+        );
+        assert_eq!(
+            first_hit(
+                &world,
+                &EntityFilter::new()
+                    .with_component::<Glass>()
+                    .with_component::<Wall>()
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn predicate_filter() {
+        let (world, entities) = world_with_balls(&[10.0, 20.0, 30.0]);
+
+        let far_away = |entity: Entity, entities_and_components: &EntitiesAndComponents| {
+            entities_and_components
+                .try_get_components::<(Transform,)>(entity)
+                .0
+                .map_or(false, |transform| transform.x > 15.0)
+        };
+        assert_eq!(
+            first_hit(&world, &EntityFilter::new().with_predicate(&far_away)),
+            Some(entities[1])
+        );
+
+        let nothing = |_: Entity, _: &EntitiesAndComponents| false;
+        assert_eq!(
+            first_hit(&world, &EntityFilter::new().with_predicate(&nothing)),
+            None
+        );
+    }
+
+    #[test]
+    fn group_filter() {
+        let (mut world, entities) = world_with_balls(&[10.0, 20.0]);
+        let (collider,) = world
+            .entities_and_components
+            .get_components_mut::<(Collider,)>(entities[0]);
+        collider.set_collision_groups(InteractionGroups::new(Group::GROUP_2, Group::ALL));
+        world.run();
+
+        let only_group_1 = InteractionGroups::new(Group::ALL, Group::GROUP_1);
+        assert_eq!(
+            first_hit(&world, &EntityFilter::new().with_groups(only_group_1)),
+            Some(entities[1])
+        );
+        let only_group_2 = InteractionGroups::new(Group::ALL, Group::GROUP_2);
+        assert_eq!(
+            first_hit(&world, &EntityFilter::new().with_groups(only_group_2)),
+            Some(entities[0])
+        );
+
+        // the entity filter is checked too where rapier doesn't take a filter
+        let query = EntityQuery::new(&world.entities_and_components);
+        let mut found = vec![];
+        query.colliders_with_aabb_intersecting_aabb(
+            &Aabb::new(point![0.0, -5.0], point![25.0, 5.0]),
+            &EntityFilter::new().with_groups(only_group_1),
+            |entity| {
+                found.push(entity);
+                true
+            },
+        );
+        assert_eq!(found, vec![entities[1]]);
+    }
+
+    #[test]
+    fn queries_are_in_pixels() {
+        let mut world = physics_test_world();
+        world
+            .entities_and_components
+            .get_resource_mut::<PhysicsSettings>()
+            .unwrap()
+            .set_pixels_per_meter(10.0);
+        // 20 pixels wide and centered 100 pixels to the right, so its left side is at 90
+        let wall = world.entities_and_components.add_entity_with((
+            Transform {
+                x: 100.0,
+                ..Default::default()
+            },
+            ColliderBuilder::cuboid(10.0, 10.0).build(),
+        ));
+        world.run();
+
+        let query = EntityQuery::new(&world.entities_and_components);
+        let filter = EntityFilter::new();
+
+        let ray = Ray::new(point![0.0, 0.0], vector![1.0, 0.0]);
+        let (hit, toi) = query.cast_ray(&ray, 1000.0, true, &filter).unwrap();
+        assert_eq!(hit, wall);
+        assert!((toi - 90.0).abs() < 1.0e-3);
+        assert!(query.cast_ray(&ray, 80.0, true, &filter).is_none());
+
+        let (hit, projection) = query
+            .project_point(&point![0.0, 0.0], true, &filter)
+            .unwrap();
+        assert_eq!(hit, wall);
+        assert!((projection.point - point![90.0, 0.0]).norm() < 1.0e-3);
+
+        let ball = Ball::new(5.0);
+        assert_eq!(
+            query.intersection_with_shape(&Isometry::translation(86.0, 0.0), &ball, &filter),
+            Some(wall)
+        );
+        assert_eq!(
+            query.intersection_with_shape(&Isometry::translation(84.0, 0.0), &ball, &filter),
+            None
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use std::num::NonZeroUsize;
+
+use rapier2d::prelude::*;
+use ABC_ECS::Resource;
+
+/// Controls how often and how precisely the physics world is stepped.
+/// This is added as a resource by the physics system, changes take effect on the next frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhysicsSettings {
+    steps_per_second: f64,
+    substeps: usize,
+    max_catch_up_steps: usize,
+    follow_time_scale: bool,
+    solver_iterations: usize,
+    ccd_substeps: usize,
+    pixels_per_meter: f64,
+    deterministic: bool,
+}
+
+impl Default for PhysicsSettings {
+    fn default() -> Self {
+        Self {
+            steps_per_second: 60.0,
+            substeps: 1,
+            max_catch_up_steps: 4,
+            follow_time_scale: true,
+            solver_iterations: 4,
+            ccd_substeps: 1,
+            pixels_per_meter: 1.0,
+            deterministic: false,
+        }
+    }
+}
+
+impl PhysicsSettings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// how many fixed steps are taken per second of (scaled) game time
+    pub fn get_steps_per_second(&self) -> f64 {
+        self.steps_per_second
+    }
+
+    /// how many fixed steps are taken per second of (scaled) game time, must be greater than 0
+    pub fn set_steps_per_second(&mut self, steps_per_second: f64) {
+        assert!(
+            steps_per_second > 0.0,
+            "steps per second must be greater than 0"
+        );
+        self.steps_per_second = steps_per_second;
+    }
+
+    pub fn with_steps_per_second(mut self, steps_per_second: f64) -> Self {
+        self.set_steps_per_second(steps_per_second);
+        self
+    }
+
+    /// the length of one fixed step in seconds
+    pub fn get_step_time(&self) -> f64 {
+        1.0 / self.steps_per_second
+    }
+
+    /// how many times each fixed step is split up, more substeps means more accurate but slower physics
+    pub fn get_substeps(&self) -> usize {
+        self.substeps
+    }
+
+    /// how many times each fixed step is split up, values lower than 1 are treated as 1
+    pub fn set_substeps(&mut self, substeps: usize) {
+        self.substeps = substeps.max(1);
+    }
+
+    pub fn with_substeps(mut self, substeps: usize) -> Self {
+        self.set_substeps(substeps);
+        self
+    }
+
+    /// the most steps that will be taken in a single frame when the game falls behind
+    pub fn get_max_catch_up_steps(&self) -> usize {
+        self.max_catch_up_steps
+    }
+
+    /// the most steps that will be taken in a single frame when the game falls behind
+    /// any time past this is dropped so that a slow frame doesn't cause an even slower frame after it
+    /// values lower than 1 are treated as 1
+    pub fn set_max_catch_up_steps(&mut self, max_catch_up_steps: usize) {
+        self.max_catch_up_steps = max_catch_up_steps.max(1);
+    }
+
+    pub fn with_max_catch_up_steps(mut self, max_catch_up_steps: usize) -> Self {
+        self.set_max_catch_up_steps(max_catch_up_steps);
+        self
+    }
+
+    /// if true the physics world is slowed down and sped up with DeltaTime::set_time_scale
+    pub fn get_follow_time_scale(&self) -> bool {
+        self.follow_time_scale
+    }
+
+    /// if true the physics world is slowed down and sped up with DeltaTime::set_time_scale
+    pub fn set_follow_time_scale(&mut self, follow_time_scale: bool) {
+        self.follow_time_scale = follow_time_scale;
+    }
+
+    pub fn with_follow_time_scale(mut self, follow_time_scale: bool) -> Self {
+        self.follow_time_scale = follow_time_scale;
+        self
+    }
+
+    /// the number of solver iterations run by rapier each substep
+    pub fn get_solver_iterations(&self) -> usize {
+        self.solver_iterations
+    }
+
+    /// the number of solver iterations run by rapier each substep, values lower than 1 are treated as 1
+    pub fn set_solver_iterations(&mut self, solver_iterations: usize) {
+        self.solver_iterations = solver_iterations.max(1);
+    }
+
+    pub fn with_solver_iterations(mut self, solver_iterations: usize) -> Self {
+        self.set_solver_iterations(solver_iterations);
+        self
+    }
+
+    /// the maximum number of substeps rapier takes for bodies with continuous collision detection enabled
+    pub fn get_ccd_substeps(&self) -> usize {
+        self.ccd_substeps
+    }
+
+    /// the maximum number of substeps rapier takes for bodies with continuous collision detection enabled
+    /// set to 0 to disable ccd for every body
+    pub fn set_ccd_substeps(&mut self, ccd_substeps: usize) {
+        self.ccd_substeps = ccd_substeps;
+    }
+
+    pub fn with_ccd_substeps(mut self, ccd_substeps: usize) -> Self {
+        self.ccd_substeps = ccd_substeps;
+        self
+    }
+
+    /// how many units of Transform position make up one meter in the physics world
+    pub fn get_pixels_per_meter(&self) -> f64 {
+        self.pixels_per_meter
+    }
+
+    /// how many units of Transform position make up one meter in the physics world, must be greater than 0
+    /// rapier is tuned for objects that are around a meter in size, so a 16 pixel tall character
+    /// works best with something like 16 pixels per meter.
+    /// positions, collider shapes, query inputs and results and physics commands are all converted,
+    /// gravity and the RigidBody component are in meters.
+    /// this should be set before any rigidbodies are added, velocities are not converted when it changes
+    pub fn set_pixels_per_meter(&mut self, pixels_per_meter: f64) {
+        assert!(
+            pixels_per_meter > 0.0,
+            "pixels per meter must be greater than 0"
+        );
+        self.pixels_per_meter = pixels_per_meter;
+    }
+
+    pub fn with_pixels_per_meter(mut self, pixels_per_meter: f64) -> Self {
+        self.set_pixels_per_meter(pixels_per_meter);
+        self
+    }
+
+    /// if true the physics world only takes the steps asked for with RapierPhysicsInfo::step_n, no matter how long the frame took
+    pub fn get_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// if true the physics world steps the same way every time it is given the same inputs, for lockstep and replays.
+    /// DeltaTime comes from the wall clock, so instead of following it the physics world only takes the steps asked for
+    /// with RapierPhysicsInfo::step_n, once for every tick of the game. that way how fast it runs doesn't depend on the frame rate,
+    /// and rigidbodies and colliders are added to the world in a stable order.
+    /// this is enough on a single machine, for the same results on different platforms turn on the deterministic feature.
+    /// compare runs with RapierPhysicsInfo::state_hash
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// writes these settings into rapier's integration parameters
+    pub(crate) fn apply_to(&self, integration_parameters: &mut IntegrationParameters) {
+        integration_parameters.dt = (self.get_step_time() / self.substeps as f64) as Real;
+        integration_parameters.num_solver_iterations =
+            NonZeroUsize::new(self.solver_iterations.max(1)).unwrap();
+        integration_parameters.max_ccd_substeps = self.ccd_substeps;
+    }
+}
+
+impl Resource for PhysicsSettings {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
@@ -0,0 +1,463 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use fxhash::FxHasher64;
+use rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+use ABC_ECS::EntitiesAndComponents;
+use ABC_ECS::Entity;
+
+use crate::physics::physics_system::{
+    remove_all_components_of_type, set_all_rigid_bodies_and_colliders, ColliderHandle,
+    ColliderSyncState, RBHandleChanged, RapierColliderHandle, RapierPhysicsInfo,
+    RapierRigidBodyHandle, RigidBodyHandle, RigidBodySyncState, ScaledColliderShape,
+};
+use crate::physics::physics_worlds::{get_physics_info, get_physics_info_mut, PhysicsWorldId};
+
+/// A copy of the full state of the physics world, taken with RapierPhysicsInfo::take_snapshot
+/// and put back with restore_physics_snapshot.
+///
+/// The rapier state is stored as bytes, the entities are stored next to it because they only mean
+/// something in the world they were taken from.
+/// Any entity in the snapshot must still exist when the snapshot is restored.
+/// A snapshot is always restored into the physics world it was taken from.
+#[derive(Clone, Debug)]
+pub struct PhysicsSnapshot {
+    bytes: Vec<u8>,
+    entities: Vec<Entity>,
+    world_id: PhysicsWorldId,
+}
+
+impl PhysicsSnapshot {
+    /// the serialized rapier state
+    pub fn get_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// the entities referenced by the snapshot
+    pub fn get_entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// the physics world the snapshot was taken from
+    pub fn get_world_id(&self) -> PhysicsWorldId {
+        self.world_id
+    }
+}
+
+// the two structs below must have the same fields in the same order, one is for writing and one is for reading
+#[derive(Serialize)]
+struct PhysicsStateRef<'a> {
+    gravity: &'a Vector<Real>,
+    integration_parameters: &'a IntegrationParameters,
+    island_manager: &'a IslandManager,
+    broad_phase: &'a BroadPhaseMultiSap,
+    narrow_phase: &'a NarrowPhase,
+    impulse_joint_set: &'a ImpulseJointSet,
+    multibody_joint_set: &'a MultibodyJointSet,
+    ccd_solver: &'a CCDSolver,
+    rigid_body_set: &'a RigidBodySet,
+    collider_set: &'a ColliderSet,
+    accumulated_time: f64,
+    simulated_time: f64,
+    // the usize is an index into PhysicsSnapshot::entities
+    rigid_body_handles: Vec<(RapierRigidBodyHandle, usize)>,
+    collider_handles: Vec<(RapierColliderHandle, usize)>,
+    // the colliders in the collider set are already scaled, so the shapes they were scaled from are kept too
+    collider_base_shapes: Vec<(usize, &'a SharedShape, Real)>,
+    // the deterministic order of the entities, so a restored world goes through them in the same order
+    entity_order: Vec<(usize, u64)>,
+    next_entity_order: u64,
+}
+
+#[derive(Deserialize)]
+struct PhysicsState {
+    gravity: Vector<Real>,
+    integration_parameters: IntegrationParameters,
+    island_manager: IslandManager,
+    broad_phase: BroadPhaseMultiSap,
+    narrow_phase: NarrowPhase,
+    impulse_joint_set: ImpulseJointSet,
+    multibody_joint_set: MultibodyJointSet,
+    ccd_solver: CCDSolver,
+    rigid_body_set: RigidBodySet,
+    collider_set: ColliderSet,
+    accumulated_time: f64,
+    simulated_time: f64,
+    rigid_body_handles: Vec<(RapierRigidBodyHandle, usize)>,
+    collider_handles: Vec<(RapierColliderHandle, usize)>,
+    collider_base_shapes: Vec<(usize, SharedShape, Real)>,
+    entity_order: Vec<(usize, u64)>,
+    next_entity_order: u64,
+}
+
+impl RapierPhysicsInfo {
+    /// Copies the full state of the physics world, this can be restored with restore_physics_snapshot.
+    /// useful for rollback, replays and resetting a level
+    pub fn take_snapshot(&self) -> PhysicsSnapshot {
+        let mut entities = vec![];
+        let mut entity_indices = HashMap::new();
+        let mut index_of = |entity: Entity| {
+            *entity_indices.entry(entity).or_insert_with(|| {
+                entities.push(entity);
+                entities.len() - 1
+            })
+        };
+
+        let rigid_body_handles = self
+            .rigid_body_handle_map
+            .iter()
+            .map(|(handle, entity)| (handle.0, index_of(*entity)))
+            .collect();
+        let collider_handles = self
+            .collider_handle_map
+            .iter()
+            .map(|(handle, entity)| (handle.0, index_of(*entity)))
+            .collect();
+        let collider_base_shapes = self
+            .collider_base_shapes
+            .iter()
+            .map(|(entity, (base_shape, scale))| (index_of(*entity), base_shape, *scale))
+            .collect();
+        let entity_order = self
+            .entity_order
+            .iter()
+            .map(|(entity, order)| (index_of(*entity), *order))
+            .collect();
+
+        let state = PhysicsStateRef {
+            gravity: &self.gravity,
+            integration_parameters: &self.integration_parameters,
+            island_manager: &self.island_manager,
+            broad_phase: &self.broad_phase,
+            narrow_phase: &self.narrow_phase,
+            impulse_joint_set: &self.impulse_joint_set,
+            multibody_joint_set: &self.multibody_joint_set,
+            ccd_solver: &self.ccd_solver,
+            rigid_body_set: &self.rigid_body_set,
+            collider_set: &self.collider_set,
+            accumulated_time: self.accumulated_time,
+            simulated_time: self.simulated_time,
+            rigid_body_handles,
+            collider_handles,
+            collider_base_shapes,
+            entity_order,
+            next_entity_order: self.next_entity_order,
+        };
+
+        let bytes = bincode::serialize(&state)
+            .expect("failed to serialize the physics world, report this as a bug");
+
+        PhysicsSnapshot {
+            bytes,
+            entities,
+            world_id: self.world_id,
+        }
+    }
+
+    /// A hash of where everything in the physics world is and how it is moving.
+    /// two runs in deterministic mode that are given the same inputs have the same hash after every frame,
+    /// so comparing hashes is a cheap way to find out when and where lockstep peers or replays went out of sync
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = FxHasher64::default();
+
+        hasher.write_u64(self.simulated_time.to_bits());
+        hash_vector(&mut hasher, &self.gravity);
+
+        // the sets are iterated in handle order, which only depends on what was added and removed
+        for (handle, rigid_body) in self.rigid_body_set.iter() {
+            handle.into_raw_parts().hash(&mut hasher);
+            hash_isometry(&mut hasher, rigid_body.position());
+            hash_vector(&mut hasher, rigid_body.linvel());
+            hasher.write_u32(rigid_body.angvel().to_bits());
+            rigid_body.is_sleeping().hash(&mut hasher);
+        }
+
+        for (handle, collider) in self.collider_set.iter() {
+            handle.into_raw_parts().hash(&mut hasher);
+            hash_isometry(&mut hasher, collider.position());
+        }
+
+        hasher.finish()
+    }
+}
+
+// floats are hashed by their bits, so -0.0 and 0.0 are different, which is fine for comparing runs
+fn hash_vector(hasher: &mut FxHasher64, vector: &Vector<Real>) {
+    hasher.write_u32(vector.x.to_bits());
+    hasher.write_u32(vector.y.to_bits());
+}
+
+fn hash_isometry(hasher: &mut FxHasher64, isometry: &Isometry<Real>) {
+    hash_vector(hasher, &isometry.translation.vector);
+    hasher.write_u32(isometry.rotation.re.to_bits());
+    hasher.write_u32(isometry.rotation.im.to_bits());
+}
+
+/// Puts the physics world the snapshot was taken from back to the state it was in when the snapshot was taken.
+/// The RigidBody, Collider, Transform and handle components of the entities in the snapshot are restored too,
+/// entities that got a rigidbody or collider after the snapshot was taken will be re-added on the next frame.
+pub fn restore_physics_snapshot(
+    entities_and_components: &mut EntitiesAndComponents,
+    snapshot: &PhysicsSnapshot,
+) -> Result<(), bincode::Error> {
+    let state: PhysicsState = bincode::deserialize(&snapshot.bytes)?;
+    let world_id = snapshot.world_id;
+
+    // every handle currently in the ecs is about to be invalid
+    remove_all_components_of_type::<RigidBodyHandle>(entities_and_components, world_id);
+    remove_all_components_of_type::<ColliderHandle>(entities_and_components, world_id);
+    remove_all_components_of_type::<RBHandleChanged>(entities_and_components, world_id);
+    // and so is everything the physics system remembers about what it last synced
+    remove_all_components_of_type::<RigidBodySyncState>(entities_and_components, world_id);
+    remove_all_components_of_type::<ColliderSyncState>(entities_and_components, world_id);
+    remove_all_components_of_type::<ScaledColliderShape>(entities_and_components, world_id);
+
+    let mut rigid_bodies = vec![];
+    let mut colliders = vec![];
+    let mut scaled_collider_shapes = vec![];
+    {
+        let physics_info = get_physics_info_mut(entities_and_components, world_id)
+            .expect("the physics world the snapshot was taken from doesn't exist anymore");
+
+        physics_info.gravity = state.gravity;
+        physics_info.integration_parameters = state.integration_parameters;
+        physics_info.island_manager = state.island_manager;
+        physics_info.broad_phase = state.broad_phase;
+        physics_info.narrow_phase = state.narrow_phase;
+        physics_info.impulse_joint_set = state.impulse_joint_set;
+        physics_info.multibody_joint_set = state.multibody_joint_set;
+        physics_info.ccd_solver = state.ccd_solver;
+        physics_info.rigid_body_set = state.rigid_body_set;
+        physics_info.collider_set = state.collider_set;
+        physics_info.accumulated_time = state.accumulated_time;
+        physics_info.simulated_time = state.simulated_time;
+        physics_info.entity_order = state
+            .entity_order
+            .into_iter()
+            .map(|(entity_index, order)| (snapshot.entities[entity_index], order))
+            .collect();
+        physics_info.next_entity_order = state.next_entity_order;
+
+        physics_info.rigid_body_handle_map.clear();
+        for (handle, entity_index) in state.rigid_body_handles {
+            let entity = snapshot.entities[entity_index];
+            physics_info
+                .rigid_body_handle_map
+                .insert(RigidBodyHandle(handle), entity);
+
+            let rigid_body = physics_info.rigid_body_set[handle].clone();
+            rigid_bodies.push((entity, RigidBodyHandle(handle), rigid_body));
+        }
+
+        physics_info.collider_handle_map.clear();
+        for (handle, entity_index) in state.collider_handles {
+            let entity = snapshot.entities[entity_index];
+            physics_info
+                .collider_handle_map
+                .insert(ColliderHandle(handle), entity);
+
+            let collider = physics_info.collider_set[handle].clone();
+            colliders.push((entity, ColliderHandle(handle), collider));
+        }
+
+        physics_info.collider_base_shapes = state
+            .collider_base_shapes
+            .into_iter()
+            .map(|(entity_index, base_shape, scale)| {
+                (snapshot.entities[entity_index], (base_shape, scale))
+            })
+            .collect();
+        for (entity, _, collider) in &colliders {
+            if let Some((base_shape, scale)) = physics_info.collider_base_shapes.get(entity) {
+                scaled_collider_shapes.push((
+                    *entity,
+                    ScaledColliderShape {
+                        base_shape: base_shape.clone(),
+                        // the restored collider is the scaled shape, so it isn't scaled again
+                        scaled_shape: collider.shared_shape().clone(),
+                        scale: *scale,
+                    },
+                ));
+            }
+        }
+
+        physics_info
+            .query_pipeline
+            .update(&physics_info.rigid_body_set, &physics_info.collider_set);
+    }
+
+    for (entity, handle, rigid_body) in rigid_bodies {
+        entities_and_components.add_component_to(entity, rigid_body);
+        entities_and_components.add_component_to(entity, handle);
+    }
+
+    for (entity, handle, collider) in colliders {
+        entities_and_components.add_component_to(entity, collider);
+        entities_and_components.add_component_to(entity, handle);
+    }
+
+    for (entity, scaled_collider_shape) in scaled_collider_shapes {
+        entities_and_components.add_component_to(entity, scaled_collider_shape);
+    }
+
+    // move the transforms back to where the bodies are
+    let physics_info;
+    {
+        let physics_info_ref = get_physics_info(entities_and_components, world_id)
+            .expect("the physics world the snapshot was taken from doesn't exist anymore");
+
+        let physics_info_ptr = physics_info_ref as *const RapierPhysicsInfo;
+        unsafe {
+            // SAFETY: set_all_rigid_bodies_and_colliders doesn't access the physics info through the world
+            physics_info = &*physics_info_ptr;
+        }
+    }
+    set_all_rigid_bodies_and_colliders(physics_info, entities_and_components);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::physics_settings::PhysicsSettings;
+    use crate::physics::physics_test_world;
+    use crate::Transform;
+
+    fn physics_info(entities_and_components: &mut EntitiesAndComponents) -> &mut RapierPhysicsInfo {
+        entities_and_components
+            .get_resource_mut::<RapierPhysicsInfo>()
+            .unwrap()
+    }
+
+    #[test]
+    fn restoring_a_snapshot_puts_the_bodies_back() {
+        let mut world = physics_test_world();
+        let entity = world.entities_and_components.add_entity_with((
+            Transform::default(),
+            RigidBodyBuilder::dynamic().build(),
+            ColliderBuilder::ball(1.0).build(),
+        ));
+        world.run();
+
+        let entities_and_components = &mut world.entities_and_components;
+        let handle = *entities_and_components
+            .get_components::<(RigidBodyHandle,)>(entity)
+            .0;
+        let snapshot = physics_info(entities_and_components).take_snapshot();
+        let position =
+            *physics_info(entities_and_components).rigid_body_set[handle.0].translation();
+        let (transform,) = entities_and_components.get_components::<(Transform,)>(entity);
+        let transform_y = transform.y;
+
+        physics_info(entities_and_components).rigid_body_set[handle.0]
+            .set_translation(position + vector![5.0, 5.0], true);
+
+        restore_physics_snapshot(entities_and_components, &snapshot).unwrap();
+        let handle = *entities_and_components
+            .get_components::<(RigidBodyHandle,)>(entity)
+            .0;
+        assert_eq!(
+            *physics_info(entities_and_components).rigid_body_set[handle.0].translation(),
+            position
+        );
+        let (transform,) = entities_and_components.get_components::<(Transform,)>(entity);
+        assert_eq!(transform.y, transform_y);
+    }
+
+    #[test]
+    fn bodies_added_after_the_snapshot_are_added_again() {
+        let mut world = physics_test_world();
+        world
+            .entities_and_components
+            .add_entity_with((Transform::default(), RigidBodyBuilder::fixed().build()));
+        world.run();
+        let snapshot = physics_info(&mut world.entities_and_components).take_snapshot();
+
+        let added_later = world
+            .entities_and_components
+            .add_entity_with((Transform::default(), RigidBodyBuilder::fixed().build()));
+        world.run();
+        assert_eq!(
+            physics_info(&mut world.entities_and_components)
+                .rigid_body_set
+                .len(),
+            2
+        );
+
+        restore_physics_snapshot(&mut world.entities_and_components, &snapshot).unwrap();
+        assert_eq!(
+            physics_info(&mut world.entities_and_components)
+                .rigid_body_set
+                .len(),
+            1
+        );
+        assert!(world
+            .entities_and_components
+            .try_get_components::<(RigidBodyHandle,)>(added_later)
+            .0
+            .is_none());
+
+        world.run();
+        assert_eq!(
+            physics_info(&mut world.entities_and_components)
+                .rigid_body_set
+                .len(),
+            2
+        );
+    }
+
+    fn ball_radius(entities_and_components: &EntitiesAndComponents, entity: Entity) -> Real {
+        let handle = *entities_and_components
+            .get_components::<(ColliderHandle,)>(entity)
+            .0;
+        let physics_info = get_physics_info(entities_and_components, PhysicsWorldId::DEFAULT)
+            .expect("the physics system adds the physics info");
+
+        physics_info.collider_set[handle.0]
+            .shape()
+            .as_ball()
+            .unwrap()
+            .radius
+    }
+
+    #[test]
+    fn restoring_a_snapshot_does_not_scale_colliders_again() {
+        let mut world = physics_test_world();
+        world
+            .entities_and_components
+            .get_resource_mut::<PhysicsSettings>()
+            .unwrap()
+            .set_pixels_per_meter(2.0);
+        let entity = world.entities_and_components.add_entity_with((
+            Transform {
+                scale: 3.0,
+                ..Default::default()
+            },
+            RigidBodyBuilder::fixed().build(),
+            ColliderBuilder::ball(1.0).build(),
+        ));
+        world.run();
+        assert_eq!(ball_radius(&world.entities_and_components, entity), 1.5);
+
+        let snapshot = get_physics_info(&world.entities_and_components, PhysicsWorldId::DEFAULT)
+            .unwrap()
+            .take_snapshot();
+        for _ in 0..3 {
+            restore_physics_snapshot(&mut world.entities_and_components, &snapshot).unwrap();
+            world.run();
+            world.run();
+            assert_eq!(ball_radius(&world.entities_and_components, entity), 1.5);
+        }
+
+        // the shape the user gave the collider is still used when the scale changes after a restore
+        world
+            .entities_and_components
+            .get_components_mut::<(Transform,)>(entity)
+            .0
+            .scale = 4.0;
+        world.run();
+        assert_eq!(ball_radius(&world.entities_and_components, entity), 2.0);
+    }
+}
@@ -1,17 +1,30 @@
 use std::ops::Deref;
 use std::ops::DerefMut;
 
+use fxhash::{FxHashMap, FxHashSet};
 use rapier2d::parry::query::NonlinearRigidMotion;
 pub use rapier2d::prelude::ColliderHandle as RapierColliderHandle;
 pub use rapier2d::prelude::RigidBodyHandle as RapierRigidBodyHandle;
 use rapier2d::prelude::*;
 use tracing::Level;
+use ABC_ECS::Component;
 use ABC_ECS::EntitiesAndComponents;
 use ABC_ECS::Entity;
 use ABC_ECS::Resource;
 use ABC_ECS::System;
 
 use crate::delta_time;
+use crate::physics::area_effectors::apply_area_effector_forces;
+use crate::physics::physics_commands::{restore_user_forces, PhysicsCommand};
+use crate::physics::physics_hooks::{
+    OneWayPlatformHook, OwnerThreadOnly, PhysicsHook, PhysicsHookAdapter,
+};
+use crate::physics::physics_settings::PhysicsSettings;
+use crate::physics::physics_worlds::{
+    get_physics_info, get_physics_info_mut, get_physics_settings, PhysicsWorldId,
+    PhysicsWorldIdCache, PhysicsWorlds,
+};
+use crate::physics::shape_scaling::{sanitize_scale, scale_shape};
 use crate::Transform;
 use tracing::event;
 
@@ -19,31 +32,99 @@ use tracing::event;
 /// this is a wrapper around the rapier physics pipeline that allows for easy access to the physics engine
 /// all of the docs are 95% copy pasted from the rapier docs
 pub struct RapierPhysicsInfo {
-    query_pipeline: QueryPipeline,
-    rigid_body_handle_map: std::collections::HashMap<RigidBodyHandle, Entity>,
-    collider_handle_map: std::collections::HashMap<ColliderHandle, Entity>,
-    gravity: Vector<Real>,
-    integration_parameters: IntegrationParameters,
-    physics_pipeline: PhysicsPipeline,
-    island_manager: IslandManager,
-    broad_phase: BroadPhaseMultiSap,
-    narrow_phase: NarrowPhase,
-    impulse_joint_set: ImpulseJointSet,
-    multibody_joint_set: MultibodyJointSet,
-    ccd_solver: CCDSolver,
-    physics_hooks: (),
-    event_handler: (),
-    rigid_body_set: RigidBodySet,
-    collider_set: ColliderSet,
-    time_started: std::time::Instant,
-    time_elapsed_before_this_frame: std::time::Duration,
+    pub(crate) query_pipeline: QueryPipeline,
+    pub(crate) rigid_body_handle_map: std::collections::HashMap<RigidBodyHandle, Entity>,
+    pub(crate) collider_handle_map: std::collections::HashMap<ColliderHandle, Entity>,
+    pub(crate) gravity: Vector<Real>,
+    pub(crate) integration_parameters: IntegrationParameters,
+    pub(crate) physics_pipeline: PhysicsPipeline,
+    pub(crate) island_manager: IslandManager,
+    pub(crate) broad_phase: BroadPhaseMultiSap,
+    pub(crate) narrow_phase: NarrowPhase,
+    pub(crate) impulse_joint_set: ImpulseJointSet,
+    pub(crate) multibody_joint_set: MultibodyJointSet,
+    pub(crate) ccd_solver: CCDSolver,
+    pub(crate) physics_hooks: Vec<Box<dyn PhysicsHook>>,
+    pub(crate) event_handler: (),
+    pub(crate) rigid_body_set: RigidBodySet,
+    pub(crate) collider_set: ColliderSet,
+    // time that has passed but has not been simulated yet
+    pub(crate) accumulated_time: f64,
+    pub(crate) queued_commands: Vec<(Entity, PhysicsCommand)>,
+    // copied from PhysicsSettings every frame
+    pub(crate) pixels_per_meter: Real,
+    // the total time the physics world has been stepped for
+    pub(crate) simulated_time: f64,
+    pub(crate) world_id: PhysicsWorldId,
+    // copied from PhysicsSettings every frame
+    pub(crate) deterministic: bool,
+    pub(crate) paused: bool,
+    // steps to take on the next frame while paused
+    pub(crate) requested_steps: usize,
+    // the shapes users gave the scaled colliders and the scale they are at, so snapshots can put them back
+    pub(crate) collider_base_shapes: std::collections::HashMap<Entity, (SharedShape, Real)>,
+    // in deterministic mode, the order entities were first seen in, entities are always gone through in this order
+    pub(crate) entity_order: std::collections::HashMap<Entity, u64>,
+    pub(crate) next_entity_order: u64,
 }
 
 impl RapierPhysicsInfo {
+    /// the physics world this is the info of
+    pub fn get_world_id(&self) -> PhysicsWorldId {
+        self.world_id
+    }
+
     pub fn set_gravity(&mut self, gravity: Vector<Real>) {
         self.gravity = gravity;
     }
 
+    /// Adds a hook that can filter and modify contacts between entities during the physics step.
+    /// hooks are run in the order they are added, the OneWayPlatform hook is always added first
+    pub fn add_physics_hook(&mut self, hook: impl PhysicsHook) {
+        self.physics_hooks.push(Box::new(hook));
+    }
+
+    /// converts a length in pixels to meters, see PhysicsSettings::set_pixels_per_meter
+    pub(crate) fn to_meters(&self, length: Real) -> Real {
+        length / self.pixels_per_meter
+    }
+
+    /// converts a length in meters to pixels, see PhysicsSettings::set_pixels_per_meter
+    pub(crate) fn to_pixels(&self, length: Real) -> Real {
+        length * self.pixels_per_meter
+    }
+
+    // the direction is scaled too, so the time of impact stays the same
+    fn ray_to_meters(&self, ray: &Ray) -> Ray {
+        Ray::new(
+            ray.origin / self.pixels_per_meter,
+            ray.dir / self.pixels_per_meter,
+        )
+    }
+
+    fn isometry_to_meters(&self, isometry: &Isometry<Real>) -> Isometry<Real> {
+        let mut isometry = *isometry;
+        isometry.translation.vector /= self.pixels_per_meter;
+        isometry
+    }
+
+    /// None if the shape doesn't need to be scaled
+    fn shape_to_meters(&self, shape: &dyn Shape) -> Option<SharedShape> {
+        if self.pixels_per_meter == 1.0 {
+            return None;
+        }
+
+        let shape = SharedShape(shape.clone_box().into());
+        Some(scale_shape(&shape, 1.0 / self.pixels_per_meter))
+    }
+
+    // the witnesses are local to the shapes, so they are scaled like the shapes were
+    fn shape_cast_hit_to_pixels(&self, mut hit: ShapeCastHit) -> ShapeCastHit {
+        hit.witness1 *= self.pixels_per_meter;
+        hit.witness2 *= self.pixels_per_meter;
+        hit
+    }
+
     /// Find the associated entity with a rigid body handle.
     pub fn get_associated_entity_with_rigid_body_handle(
         &self,
@@ -80,7 +161,7 @@ impl RapierPhysicsInfo {
         let intersection = self.query_pipeline.cast_ray(
             &self.rigid_body_set,
             &self.collider_set,
-            &ray,
+            &self.ray_to_meters(ray),
             max_toi,
             solid,
             filter,
@@ -117,7 +198,7 @@ impl RapierPhysicsInfo {
         let intersection = self.query_pipeline.cast_ray_and_get_normal(
             &self.rigid_body_set,
             &self.collider_set,
-            ray,
+            &self.ray_to_meters(ray),
             max_toi,
             solid,
             filter,
@@ -158,7 +239,7 @@ impl RapierPhysicsInfo {
         self.query_pipeline.intersections_with_ray(
             &self.rigid_body_set,
             &self.collider_set,
-            ray,
+            &self.ray_to_meters(ray),
             max_toi,
             solid,
             filter,
@@ -183,11 +264,12 @@ impl RapierPhysicsInfo {
         shape: &dyn Shape,
         filter: QueryFilter,
     ) -> Option<Entity> {
+        let scaled_shape = self.shape_to_meters(shape);
         let intersection = self.query_pipeline.intersection_with_shape(
             &self.rigid_body_set,
             &self.collider_set,
-            shape_pos,
-            shape,
+            &self.isometry_to_meters(shape_pos),
+            scaled_shape.as_deref().unwrap_or(shape),
             filter,
         );
 
@@ -221,16 +303,17 @@ impl RapierPhysicsInfo {
         let projection = self.query_pipeline.project_point(
             &self.rigid_body_set,
             &self.collider_set,
-            point,
+            &(*point / self.pixels_per_meter),
             solid,
             filter,
         );
 
         match projection {
-            Some((handle, projection)) => {
+            Some((handle, mut projection)) => {
                 let entity = self
                     .get_associated_entity_with_collider_handle(ColliderHandle(handle))
                     .expect("failed to get entity associated with collider handle, this is a bug");
+                projection.point *= self.pixels_per_meter;
                 Some((entity, projection))
             }
             None => None,
@@ -253,7 +336,7 @@ impl RapierPhysicsInfo {
         self.query_pipeline.intersections_with_point(
             &self.rigid_body_set,
             &self.collider_set,
-            point,
+            &(*point / self.pixels_per_meter),
             filter,
             |handle| {
                 let entity = self
@@ -284,15 +367,16 @@ impl RapierPhysicsInfo {
         let projection = self.query_pipeline.project_point_and_get_feature(
             &self.rigid_body_set,
             &self.collider_set,
-            point,
+            &(*point / self.pixels_per_meter),
             filter,
         );
 
         match projection {
-            Some((handle, projection, feature)) => {
+            Some((handle, mut projection, feature)) => {
                 let entity = self
                     .get_associated_entity_with_collider_handle(ColliderHandle(handle))
                     .expect("failed to get entity associated with collider handle, this is a bug");
+                projection.point *= self.pixels_per_meter;
                 Some((entity, projection, feature))
             }
             None => None,
@@ -305,8 +389,9 @@ impl RapierPhysicsInfo {
         aabb: &Aabb,
         mut callback: impl FnMut(&Entity) -> bool,
     ) {
+        let aabb = aabb.scaled(&Vector::repeat(1.0 / self.pixels_per_meter));
         self.query_pipeline
-            .colliders_with_aabb_intersecting_aabb(aabb, |handle| {
+            .colliders_with_aabb_intersecting_aabb(&aabb, |handle| {
                 let entity = self
                     .get_associated_entity_with_collider_handle(ColliderHandle(*handle))
                     .expect("failed to get entity associated with collider handle, this is a bug");
@@ -338,12 +423,15 @@ impl RapierPhysicsInfo {
         options: rapier2d::parry::query::ShapeCastOptions,
         filter: QueryFilter,
     ) -> Option<(Entity, ShapeCastHit)> {
+        let scaled_shape = self.shape_to_meters(shape);
+        let mut options = options;
+        options.target_distance = self.to_meters(options.target_distance);
         let intersection = self.query_pipeline.cast_shape(
             &self.rigid_body_set,
             &self.collider_set,
-            shape_pos,
-            shape_vel,
-            shape,
+            &self.isometry_to_meters(shape_pos),
+            &(*shape_vel / self.pixels_per_meter),
+            scaled_shape.as_deref().unwrap_or(shape),
             options,
             filter,
         );
@@ -353,7 +441,7 @@ impl RapierPhysicsInfo {
                 let entity = self
                     .get_associated_entity_with_collider_handle(ColliderHandle(handle))
                     .expect("failed to get entity associated with collider handle, this is a bug");
-                Some((entity, intersection))
+                Some((entity, self.shape_cast_hit_to_pixels(intersection)))
             }
             None => None,
         }
@@ -386,11 +474,16 @@ impl RapierPhysicsInfo {
         stop_at_penetration: bool,
         filter: QueryFilter,
     ) -> Option<(Entity, ShapeCastHit)> {
+        let scaled_shape = self.shape_to_meters(shape);
+        let mut shape_motion = *shape_motion;
+        shape_motion.start = self.isometry_to_meters(&shape_motion.start);
+        shape_motion.local_center /= self.pixels_per_meter;
+        shape_motion.linvel /= self.pixels_per_meter;
         let intersection = self.query_pipeline.nonlinear_cast_shape(
             &self.rigid_body_set,
             &self.collider_set,
-            shape_motion,
-            shape,
+            &shape_motion,
+            scaled_shape.as_deref().unwrap_or(shape),
             start_time,
             end_time,
             stop_at_penetration,
@@ -402,7 +495,7 @@ impl RapierPhysicsInfo {
                 let entity = self
                     .get_associated_entity_with_collider_handle(ColliderHandle(handle))
                     .expect("failed to get entity associated with collider handle, this is a bug");
-                Some((entity, intersection))
+                Some((entity, self.shape_cast_hit_to_pixels(intersection)))
             }
             None => None,
         }
@@ -423,11 +516,12 @@ impl RapierPhysicsInfo {
         filter: QueryFilter,
         mut callback: impl FnMut(Entity) -> bool,
     ) {
+        let scaled_shape = self.shape_to_meters(shape);
         self.query_pipeline.intersections_with_shape(
             &self.rigid_body_set,
             &self.collider_set,
-            shape_pos,
-            shape,
+            &self.isometry_to_meters(shape_pos),
+            scaled_shape.as_deref().unwrap_or(shape),
             filter,
             |handle| {
                 let entity = self
@@ -463,10 +557,8 @@ impl Resource for RapierPhysicsInfo {
     }
 }
 
-pub struct RapierPhysicsSystem {}
-
-impl RapierPhysicsSystem {
-    pub fn new(world: &mut EntitiesAndComponents) -> RapierPhysicsSystem {
+impl RapierPhysicsInfo {
+    pub(crate) fn new(world_id: PhysicsWorldId) -> Self {
         /* Create other structures necessary for the simulation. */
         let gravity = vector![0.0, -9.81];
         let integration_parameters = IntegrationParameters::default();
@@ -480,11 +572,11 @@ impl RapierPhysicsSystem {
         let impulse_joint_set = ImpulseJointSet::new();
         let multibody_joint_set = MultibodyJointSet::new();
         let ccd_solver = CCDSolver::new();
-        let physics_hooks = ();
+        let physics_hooks: Vec<Box<dyn PhysicsHook>> = vec![Box::new(OneWayPlatformHook)];
         let event_handler = ();
         let query_pipeline = QueryPipeline::new();
 
-        let rapier_physics_info = RapierPhysicsInfo {
+        RapierPhysicsInfo {
             query_pipeline,
             rigid_body_handle_map: std::collections::HashMap::new(),
             collider_handle_map: std::collections::HashMap::new(),
@@ -501,76 +593,315 @@ impl RapierPhysicsSystem {
             event_handler,
             rigid_body_set: RigidBodySet::new(),
             collider_set: ColliderSet::new(),
-            time_started: std::time::Instant::now(),
-            time_elapsed_before_this_frame: std::time::Duration::new(0, 0),
-        };
-        // add the physics info to the world
-        world.add_resource(rapier_physics_info);
+            accumulated_time: 0.0,
+            queued_commands: vec![],
+            pixels_per_meter: 1.0,
+            simulated_time: 0.0,
+            world_id,
+            deterministic: false,
+            paused: false,
+            requested_steps: 0,
+            collider_base_shapes: std::collections::HashMap::new(),
+            entity_order: std::collections::HashMap::new(),
+            next_entity_order: 0,
+        }
+    }
+
+    /// gives every entity that doesn't have a place in the deterministic order yet the next one.
+    /// entities that are new at the same time are numbered in the order of their hash,
+    /// fxhash isn't seeded so that is the same on every run no matter what order the ecs gave them in
+    pub(crate) fn order_new_entities(&mut self, entities: &[Entity]) {
+        let mut new_entities = entities
+            .iter()
+            .copied()
+            .filter(|entity| !self.entity_order.contains_key(entity))
+            .collect::<Vec<Entity>>();
+        new_entities.sort_by_cached_key(|entity| fxhash::hash64(entity));
+
+        for entity in new_entities {
+            if let std::collections::hash_map::Entry::Vacant(entry) =
+                self.entity_order.entry(entity)
+            {
+                entry.insert(self.next_entity_order);
+                self.next_entity_order += 1;
+            }
+        }
+    }
+
+    /// puts the entities in the order they were first seen in by the physics world, which is the same on every run.
+    /// entities without a place in the order go last, see order_new_entities
+    pub(crate) fn sort_entities_deterministically(&self, entities: &mut [Entity]) {
+        entities.sort_by_cached_key(|entity| {
+            (
+                self.entity_order.get(entity).copied().unwrap_or(u64::MAX),
+                fxhash::hash64(entity),
+            )
+        });
+    }
+}
+
+/// The parts of the physics info that a step changes.
+/// these are taken out of the physics info while stepping, so the hooks and area effectors can read the world
+/// (and the rest of the physics info through it) without aliasing what is being stepped
+#[derive(Default)]
+pub(crate) struct SimulationState {
+    pub(crate) physics_pipeline: PhysicsPipeline,
+    pub(crate) island_manager: IslandManager,
+    pub(crate) broad_phase: BroadPhaseMultiSap,
+    pub(crate) narrow_phase: NarrowPhase,
+    pub(crate) impulse_joint_set: ImpulseJointSet,
+    pub(crate) multibody_joint_set: MultibodyJointSet,
+    pub(crate) ccd_solver: CCDSolver,
+    pub(crate) query_pipeline: QueryPipeline,
+    pub(crate) rigid_body_set: RigidBodySet,
+    pub(crate) collider_set: ColliderSet,
+}
+
+impl SimulationState {
+    fn take_from(physics_info: &mut RapierPhysicsInfo) -> Self {
+        Self {
+            physics_pipeline: std::mem::take(&mut physics_info.physics_pipeline),
+            island_manager: std::mem::take(&mut physics_info.island_manager),
+            broad_phase: std::mem::take(&mut physics_info.broad_phase),
+            narrow_phase: std::mem::take(&mut physics_info.narrow_phase),
+            impulse_joint_set: std::mem::take(&mut physics_info.impulse_joint_set),
+            multibody_joint_set: std::mem::take(&mut physics_info.multibody_joint_set),
+            ccd_solver: std::mem::take(&mut physics_info.ccd_solver),
+            query_pipeline: std::mem::take(&mut physics_info.query_pipeline),
+            rigid_body_set: std::mem::take(&mut physics_info.rigid_body_set),
+            collider_set: std::mem::take(&mut physics_info.collider_set),
+        }
+    }
+
+    fn put_back(self, physics_info: &mut RapierPhysicsInfo) {
+        physics_info.physics_pipeline = self.physics_pipeline;
+        physics_info.island_manager = self.island_manager;
+        physics_info.broad_phase = self.broad_phase;
+        physics_info.narrow_phase = self.narrow_phase;
+        physics_info.impulse_joint_set = self.impulse_joint_set;
+        physics_info.multibody_joint_set = self.multibody_joint_set;
+        physics_info.ccd_solver = self.ccd_solver;
+        physics_info.query_pipeline = self.query_pipeline;
+        physics_info.rigid_body_set = self.rigid_body_set;
+        physics_info.collider_set = self.collider_set;
+    }
+}
+
+pub struct RapierPhysicsSystem {}
+
+impl RapierPhysicsSystem {
+    /// Adds the resources the physics system needs if they aren't there yet.
+    /// If the physics system is being re-added, for example by remove_all_non_internal_systems, the physics worlds
+    /// and their settings are kept, the entities still hold handles into them so a new world would lose their bodies.
+    pub fn new(world: &mut EntitiesAndComponents) -> RapierPhysicsSystem {
+        if world.get_resource::<RapierPhysicsInfo>().is_none() {
+            world.add_resource(RapierPhysicsInfo::new(PhysicsWorldId::DEFAULT));
+        }
+        if world.get_resource::<PhysicsSettings>().is_none() {
+            world.add_resource(PhysicsSettings::default());
+        }
+        if world.get_resource::<PhysicsWorlds>().is_none() {
+            world.add_resource(PhysicsWorlds::new());
+        }
 
         RapierPhysicsSystem {}
     }
 
-    fn step(&mut self, world: &mut EntitiesAndComponents) {
-        let physics_info = &mut world
-            .get_resource_mut::<RapierPhysicsInfo>()
+    /// takes one fixed step, split up into the given number of substeps
+    fn step(
+        &mut self,
+        world: &mut EntitiesAndComponents,
+        world_id: PhysicsWorldId,
+        substeps: usize,
+    ) {
+        let mut state = SimulationState::take_from(
+            get_physics_info_mut(world, world_id)
+                .expect("failed to get rapier physics info, report this as a bug"),
+        );
+        let entities_and_components = &*world;
+        let physics_info = get_physics_info(entities_and_components, world_id)
+            .expect("failed to get rapier physics info, report this as a bug");
+        let mut simulated_time = physics_info.simulated_time;
+
+        for _ in 0..substeps {
+            let saved_forces = apply_area_effector_forces(
+                physics_info,
+                &mut state,
+                simulated_time,
+                entities_and_components,
+            );
+
+            let physics_hooks = PhysicsHookAdapter {
+                hooks: &physics_info.physics_hooks,
+                collider_handle_map: &physics_info.collider_handle_map,
+                entities_and_components: OwnerThreadOnly::new(entities_and_components),
+            };
+
+            state.physics_pipeline.step(
+                &physics_info.gravity,
+                &physics_info.integration_parameters,
+                &mut state.island_manager,
+                &mut state.broad_phase,
+                &mut state.narrow_phase,
+                &mut state.rigid_body_set,
+                &mut state.collider_set,
+                &mut state.impulse_joint_set,
+                &mut state.multibody_joint_set,
+                &mut state.ccd_solver,
+                Some(&mut state.query_pipeline),
+                &physics_hooks,
+                &physics_info.event_handler,
+            );
+
+            restore_user_forces(&mut state.rigid_body_set, saved_forces);
+            simulated_time += physics_info.integration_parameters.dt as f64;
+        }
+
+        let physics_info = get_physics_info_mut(world, world_id)
+            .expect("failed to get rapier physics info, report this as a bug");
+        state.put_back(physics_info);
+        physics_info.simulated_time = simulated_time;
+    }
+
+    /// returns true if the pixels per meter changed
+    fn set_pixels_per_meter(
+        entities_and_components: &mut EntitiesAndComponents,
+        world_id: PhysicsWorldId,
+        pixels_per_meter: Real,
+    ) -> bool {
+        let physics_info = get_physics_info_mut(entities_and_components, world_id)
+            .expect("failed to get rapier physics info, report this as a bug");
+
+        let changed = physics_info.pixels_per_meter != pixels_per_meter;
+        physics_info.pixels_per_meter = pixels_per_meter;
+        changed
+    }
+
+    /// Asks the hooks which hooks should be enabled for the collider on every entity in the physics world.
+    fn collect_active_hooks(
+        entities_and_components: &EntitiesAndComponents,
+        world_id: PhysicsWorldId,
+        world_ids: &mut PhysicsWorldIdCache,
+    ) -> FxHashMap<Entity, ActiveHooks> {
+        let physics_hooks = &get_physics_info(entities_and_components, world_id)
+            .expect("failed to get rapier physics info, report this as a bug")
+            .physics_hooks;
+
+        let collider_entities = entities_and_components
+            .get_entities_with_component::<Collider>()
+            .into_iter()
+            .copied()
+            .filter(|entity| world_ids.of(*entity, entities_and_components) == world_id)
+            .collect::<Vec<Entity>>();
+
+        let mut active_hooks = FxHashMap::default();
+        for entity in collider_entities {
+            let entity_active_hooks =
+                physics_hooks
+                    .iter()
+                    .fold(ActiveHooks::empty(), |entity_active_hooks, hook| {
+                        entity_active_hooks | hook.active_hooks(entity, entities_and_components)
+                    });
+            active_hooks.insert(entity, entity_active_hooks);
+        }
+
+        active_hooks
+    }
+
+    /// adds the handles of every awake dynamic and kinematic body to the set
+    fn collect_active_bodies(
+        entities_and_components: &EntitiesAndComponents,
+        world_id: PhysicsWorldId,
+        out_rb_handles: &mut FxHashSet<RigidBodyHandle>,
+    ) {
+        let physics_info = get_physics_info(entities_and_components, world_id)
             .expect("failed to get rapier physics info, report this as a bug");
-        let query_pipeline = &mut physics_info.query_pipeline;
-
-        physics_info.physics_pipeline.step(
-            &physics_info.gravity,
-            &physics_info.integration_parameters,
-            &mut physics_info.island_manager,
-            &mut physics_info.broad_phase,
-            &mut physics_info.narrow_phase,
-            &mut physics_info.rigid_body_set,
-            &mut physics_info.collider_set,
-            &mut physics_info.impulse_joint_set,
-            &mut physics_info.multibody_joint_set,
-            &mut physics_info.ccd_solver,
-            Some(query_pipeline),
-            &physics_info.physics_hooks,
-            &physics_info.event_handler,
+
+        let island_manager = &physics_info.island_manager;
+        out_rb_handles.extend(
+            island_manager
+                .active_dynamic_bodies()
+                .iter()
+                .chain(island_manager.active_kinematic_bodies())
+                .map(|handle| RigidBodyHandle(*handle)),
         );
     }
-}
 
-impl System for RapierPhysicsSystem {
-    fn run(&mut self, entities_and_components: &mut EntitiesAndComponents) {
-        let is_fixed_timestep;
+    /// figures out how many fixed steps should be taken this frame
+    fn steps_to_take(
+        entities_and_components: &mut EntitiesAndComponents,
+        world_id: PhysicsWorldId,
+        settings: &PhysicsSettings,
+    ) -> usize {
         {
-            let physics_info = entities_and_components
-                .get_resource_mut::<RapierPhysicsInfo>()
+            let physics_info = get_physics_info_mut(entities_and_components, world_id)
                 .expect("failed to get rapier physics info, report this as a bug");
-            let time_started = &mut physics_info.time_started;
-            let time_elapsed_before_this_frame = &mut physics_info.time_elapsed_before_this_frame;
 
-            let time_since_last_step = time_started.elapsed() - *time_elapsed_before_this_frame;
-            if time_since_last_step.as_secs_f64() >= 1.0 / 60.0 {
-                *time_elapsed_before_this_frame = time_started.elapsed();
-                is_fixed_timestep = true;
-            } else {
-                is_fixed_timestep = false;
+            // while paused only the steps asked for are taken, time doesn't build up
+            // the frame time comes from the wall clock, so in deterministic mode the caller decides when to step too
+            if physics_info.paused || settings.get_deterministic() {
+                physics_info.accumulated_time = 0.0;
+                return std::mem::take(&mut physics_info.requested_steps);
             }
+
+            // steps can only be asked for while paused or in deterministic mode
+            physics_info.requested_steps = 0;
         }
 
-        // set the delta time scale
-        {
-            let delta_time_scale = entities_and_components
+        let frame_time = {
+            let delta_time = entities_and_components
                 .get_resource::<delta_time::DeltaTime>()
-                .expect("failed to get delta time, report this as a bug")
-                .get_time_scale();
+                .expect("failed to get delta time, report this as a bug");
+
+            if settings.get_follow_time_scale() {
+                delta_time.get_delta_time()
+            } else {
+                delta_time.get_unscaled_delta_time()
+            }
+        };
 
-            let physics_info = entities_and_components
-                .get_resource_mut::<RapierPhysicsInfo>()
+        let physics_info = get_physics_info_mut(entities_and_components, world_id)
+            .expect("failed to get rapier physics info, report this as a bug");
+
+        take_fixed_steps(
+            &mut physics_info.accumulated_time,
+            frame_time,
+            settings.get_step_time(),
+            settings.get_max_catch_up_steps(),
+        )
+    }
+
+    /// syncs, steps and pulls back a single physics world
+    fn run_world(
+        &mut self,
+        entities_and_components: &mut EntitiesAndComponents,
+        world_id: PhysicsWorldId,
+        world_ids: &mut PhysicsWorldIdCache,
+    ) {
+        let settings = get_physics_settings(entities_and_components, world_id);
+
+        let steps = Self::steps_to_take(entities_and_components, world_id, &settings);
+
+        {
+            let physics_info = get_physics_info_mut(entities_and_components, world_id)
                 .expect("failed to get rapier physics info, report this as a bug");
-            physics_info.integration_parameters.dt = (1.0 / 60.0) * delta_time_scale as f32;
+            settings.apply_to(&mut physics_info.integration_parameters);
+            physics_info.deterministic = settings.get_deterministic();
         }
 
-        {
+        let pixels_per_meter = settings.get_pixels_per_meter() as Real;
+        if Self::set_pixels_per_meter(entities_and_components, world_id, pixels_per_meter) {
+            // everything has to be moved and resized, so forget what was synced last
+            remove_all_components_of_type::<RigidBodySyncState>(entities_and_components, world_id);
+            remove_all_components_of_type::<ColliderSyncState>(entities_and_components, world_id);
+        }
+
+        let active_hooks = Self::collect_active_hooks(entities_and_components, world_id, world_ids);
+
+        let (commanded_rb_handles, forces, saved_forces) = {
             let physics_info;
             {
-                let physics_info_ref = entities_and_components
-                    .get_resource_mut::<RapierPhysicsInfo>()
+                let physics_info_ref = get_physics_info_mut(entities_and_components, world_id)
                     .expect("failed to get rapier physics info, report this as a bug");
 
                 let physics_info_ptr = physics_info_ref as *mut RapierPhysicsInfo;
@@ -586,6 +917,8 @@ impl System for RapierPhysicsSystem {
             get_all_rigid_bodies_and_colliders(
                 physics_info,
                 entities_and_components,
+                &active_hooks,
+                world_ids,
                 &mut rb_handles_found_this_frame,
                 &mut collider_handles_found_this_frame,
             );
@@ -595,29 +928,54 @@ impl System for RapierPhysicsSystem {
                 &mut rb_handles_found_this_frame,
                 &mut collider_handles_found_this_frame,
             );
-        }
 
-        if is_fixed_timestep {
-            self.step(entities_and_components);
+            // commands go after the components are read so that they aren't overwritten by them
+            let mut commanded_rb_handles = vec![];
+            let forces = physics_info
+                .apply_queued_commands(entities_and_components, &mut commanded_rb_handles);
+            let saved_forces = if steps > 0 {
+                physics_info.apply_forces(&forces)
+            } else {
+                vec![]
+            };
+
+            (commanded_rb_handles, forces, saved_forces)
+        };
+
+        // only bodies that were awake at some point during this frame can have moved
+        let mut moved_rb_handles = FxHashSet::default();
+        for _ in 0..steps {
+            Self::collect_active_bodies(entities_and_components, world_id, &mut moved_rb_handles);
+            self.step(entities_and_components, world_id, settings.get_substeps());
         }
+        if steps > 0 {
+            Self::collect_active_bodies(entities_and_components, world_id, &mut moved_rb_handles);
+        }
+        // teleported bodies should be pulled back even if no step was taken
+        moved_rb_handles.extend(commanded_rb_handles);
 
         {
-            let physics_info = &mut entities_and_components
-                .get_resource_mut::<RapierPhysicsInfo>()
+            let physics_info = &mut get_physics_info_mut(entities_and_components, world_id)
                 .expect("failed to get rapier physics info, report this as a bug");
 
+            // forces only last for the steps taken this frame
+            if steps > 0 {
+                restore_user_forces(&mut physics_info.rigid_body_set, saved_forces);
+            } else {
+                physics_info.requeue_forces(forces);
+            }
+
             let query_pipeline = &mut physics_info.query_pipeline;
             query_pipeline.update(&physics_info.rigid_body_set, &physics_info.collider_set);
         }
 
-        // if anything is changed between the physics system and the set_all_rigid_bodies_and_colliders call, it will break it don't do that
+        // if anything is changed between the physics system and the set_rigid_bodies_and_colliders call, it will break it don't do that
 
         // it's not that this is a bad idea, it's just that it's not necessary so no need to waste performance unless a step is taken
-        if is_fixed_timestep {
+        if !moved_rb_handles.is_empty() {
             let physics_info;
             {
-                let physics_info_ref = entities_and_components
-                    .get_resource::<RapierPhysicsInfo>()
+                let physics_info_ref = get_physics_info(entities_and_components, world_id)
                     .expect("failed to get rapier physics info, report this as a bug");
 
                 let physics_info_ptr = physics_info_ref as *const RapierPhysicsInfo;
@@ -627,7 +985,23 @@ impl System for RapierPhysicsSystem {
                     physics_info = &*physics_info_ptr;
                 }
             }
-            set_all_rigid_bodies_and_colliders(physics_info, entities_and_components);
+            set_rigid_bodies_and_colliders(physics_info, entities_and_components, moved_rb_handles);
+        }
+    }
+}
+
+impl System for RapierPhysicsSystem {
+    fn run(&mut self, entities_and_components: &mut EntitiesAndComponents) {
+        // the default world is stepped first, then the others in order of their ids
+        let mut world_ids = vec![PhysicsWorldId::DEFAULT];
+        if let Some(physics_worlds) = entities_and_components.get_resource::<PhysicsWorlds>() {
+            world_ids.extend(physics_worlds.get_ids());
+        }
+
+        // nothing changes which world an entity is in while the physics system runs
+        let mut world_id_cache = PhysicsWorldIdCache::new();
+        for world_id in world_ids {
+            self.run_world(entities_and_components, world_id, &mut world_id_cache);
         }
     }
 }
@@ -642,7 +1016,7 @@ pub struct RigidBodyHandle(pub RapierRigidBodyHandle);
 pub struct ColliderHandle(pub RapierColliderHandle);
 
 // a tag to temporarily store in an entity that the rigidbody has changed
-struct RBHandleChanged;
+pub(crate) struct RBHandleChanged;
 
 impl From<rapier2d::prelude::RigidBodyHandle> for RigidBodyHandle {
     fn from(handle: rapier2d::prelude::RigidBodyHandle) -> Self {
@@ -661,14 +1035,21 @@ impl From<rapier2d::prelude::ColliderHandle> for ColliderHandle {
 fn get_all_rigid_bodies_and_colliders(
     physics_info: &mut RapierPhysicsInfo,
     world: &mut EntitiesAndComponents,
+    active_hooks: &FxHashMap<Entity, ActiveHooks>,
+    world_ids: &mut PhysicsWorldIdCache,
     rb_handles_found: &mut Vec<RigidBodyHandle>,
     collider_handles_found: &mut Vec<ColliderHandle>,
 ) {
-    let rigidbody_entities = world
+    let mut rigidbody_entities = world
         .get_entities_with_component::<RigidBody>()
         .into_iter()
         .copied()
         .collect::<Vec<Entity>>();
+    rigidbody_entities.retain(|entity| world_ids.of(*entity, world) == physics_info.world_id);
+    if physics_info.deterministic {
+        physics_info.order_new_entities(&rigidbody_entities);
+        physics_info.sort_entities_deterministically(&mut rigidbody_entities);
+    }
 
     for rigidbody_entity in rigidbody_entities {
         update_rb(physics_info, world, rigidbody_entity);
@@ -682,14 +1063,40 @@ fn get_all_rigid_bodies_and_colliders(
         }
     }
 
-    let collider_entities = world
+    let mut collider_entities = world
         .get_entities_with_component::<Collider>()
         .into_iter()
         .copied()
         .collect::<Vec<Entity>>();
+    collider_entities.retain(|entity| world_ids.of(*entity, world) == physics_info.world_id);
+    if physics_info.deterministic {
+        physics_info.order_new_entities(&collider_entities);
+        physics_info.sort_entities_deterministically(&mut collider_entities);
+
+        // entities that left the physics world don't need a place in the order anymore
+        let rigid_body_handle_map = &physics_info.rigid_body_handle_map;
+        let in_physics_world = rigid_body_handle_map
+            .values()
+            .chain(collider_entities.iter())
+            .copied()
+            .collect::<FxHashSet<Entity>>();
+        physics_info
+            .entity_order
+            .retain(|entity, _| in_physics_world.contains(entity));
+    }
 
     for collider_entity in collider_entities {
-        update_collider(physics_info, world, collider_entity);
+        let collider_active_hooks = active_hooks
+            .get(&collider_entity)
+            .copied()
+            .unwrap_or(ActiveHooks::empty());
+        update_collider(
+            physics_info,
+            world,
+            world_ids,
+            collider_entity,
+            collider_active_hooks,
+        );
 
         // the entity should have a handle now
         if let Some(collider_handle) = world
@@ -701,6 +1108,28 @@ fn get_all_rigid_bodies_and_colliders(
     }
 }
 
+/// adds the frame time to the accumulated time and takes as many whole steps out of it as fit, up to max_steps
+fn take_fixed_steps(
+    accumulated_time: &mut f64,
+    frame_time: f64,
+    step_time: f64,
+    max_steps: usize,
+) -> usize {
+    *accumulated_time += frame_time.max(0.0);
+
+    let steps = (*accumulated_time / step_time).floor() as usize;
+    let max_steps = max_steps.max(1);
+
+    if steps > max_steps {
+        // we are too far behind to catch up, drop the extra time instead of spiraling
+        *accumulated_time %= step_time;
+        max_steps
+    } else {
+        *accumulated_time -= steps as f64 * step_time;
+        steps
+    }
+}
+
 fn handle_removed_entities(
     physics_info: &mut RapierPhysicsInfo,
     rb_handles_found: &mut Vec<RigidBodyHandle>,
@@ -708,13 +1137,17 @@ fn handle_removed_entities(
 ) {
     // handle cases where entities are removed or rb's are removed
     {
-        let mut rigidbody_entities_in_physics_copy = physics_info.rigid_body_handle_map.clone();
-
-        for rb_handle in rb_handles_found {
-            rigidbody_entities_in_physics_copy.remove(&rb_handle);
-        }
-
-        for (rb_handle, rb_entity) in rigidbody_entities_in_physics_copy {
+        let rb_handles_found = rb_handles_found.iter().copied().collect::<FxHashSet<_>>();
+        let mut removed_rb_handles = physics_info
+            .rigid_body_handle_map
+            .keys()
+            .filter(|rb_handle| !rb_handles_found.contains(rb_handle))
+            .copied()
+            .collect::<Vec<RigidBodyHandle>>();
+        // the order things are removed in decides which handles get reused, so it can't depend on the map
+        removed_rb_handles.sort_by_key(|rb_handle| rb_handle.0.into_raw_parts());
+
+        for rb_handle in removed_rb_handles {
             physics_info.rigid_body_set.remove(
                 rb_handle.0,
                 &mut physics_info.island_manager,
@@ -727,13 +1160,20 @@ fn handle_removed_entities(
             physics_info.rigid_body_handle_map.remove(&rb_handle);
         }
 
-        let mut collider_entities_in_physics_copy = physics_info.collider_handle_map.clone();
-
-        for collider_handle in collider_handles_found.clone() {
-            collider_entities_in_physics_copy.remove(&collider_handle);
-        }
-
-        for (collider_handle, collider_path) in collider_entities_in_physics_copy {
+        let collider_handles_found = collider_handles_found
+            .iter()
+            .copied()
+            .collect::<FxHashSet<_>>();
+        let mut removed_collider_handles = physics_info
+            .collider_handle_map
+            .keys()
+            .filter(|collider_handle| !collider_handles_found.contains(collider_handle))
+            .copied()
+            .collect::<Vec<ColliderHandle>>();
+        removed_collider_handles.sort_by_key(|collider_handle| collider_handle.0.into_raw_parts());
+        let removed_collider_handles_is_empty = removed_collider_handles.is_empty();
+
+        for collider_handle in removed_collider_handles {
             physics_info.collider_set.remove(
                 collider_handle.0,
                 &mut physics_info.island_manager,
@@ -743,9 +1183,137 @@ fn handle_removed_entities(
 
             physics_info.collider_handle_map.remove(&collider_handle);
         }
+
+        if !removed_collider_handles_is_empty {
+            let collider_entities = physics_info
+                .collider_handle_map
+                .values()
+                .copied()
+                .collect::<FxHashSet<Entity>>();
+            physics_info
+                .collider_base_shapes
+                .retain(|entity, _| collider_entities.contains(entity));
+        }
     }
 }
 
+/// the parts of a rigidbody that a user can change through the RigidBody component
+/// these are compared every frame so that the rigidbody is only copied into the physics world when it changes
+#[derive(Clone, Copy, PartialEq)]
+struct RigidBodyProperties {
+    body_type: RigidBodyType,
+    enabled: bool,
+    sleeping: bool,
+    linvel: Vector<Real>,
+    angvel: Real,
+    user_force: Vector<Real>,
+    user_torque: Real,
+    gravity_scale: Real,
+    linear_damping: Real,
+    angular_damping: Real,
+    dominance_group: i8,
+    ccd_enabled: bool,
+    locked_axes: LockedAxes,
+    mass: Real,
+    user_data: u128,
+}
+
+impl RigidBodyProperties {
+    fn new(rigidbody: &RigidBody) -> Self {
+        Self {
+            body_type: rigidbody.body_type(),
+            enabled: rigidbody.is_enabled(),
+            sleeping: rigidbody.is_sleeping(),
+            linvel: *rigidbody.linvel(),
+            angvel: rigidbody.angvel(),
+            user_force: rigidbody.user_force(),
+            user_torque: rigidbody.user_torque(),
+            gravity_scale: rigidbody.gravity_scale(),
+            linear_damping: rigidbody.linear_damping(),
+            angular_damping: rigidbody.angular_damping(),
+            dominance_group: rigidbody.dominance_group(),
+            ccd_enabled: rigidbody.is_ccd_enabled(),
+            locked_axes: rigidbody.locked_axes(),
+            mass: rigidbody.mass(),
+            user_data: rigidbody.user_data,
+        }
+    }
+}
+
+/// what the physics world last knew about the rigidbody on an entity
+pub(crate) struct RigidBodySyncState {
+    properties: RigidBodyProperties,
+    // the world transform of the entity
+    transform: Transform,
+}
+
+/// the parts of a collider that a user can change through the Collider component
+#[derive(Clone, Copy, PartialEq)]
+struct ColliderProperties {
+    // the address of the shape, a new shape means a new address
+    shape: usize,
+    enabled: bool,
+    sensor: bool,
+    friction: Real,
+    restitution: Real,
+    density: Real,
+    mass: Real,
+    collision_groups: InteractionGroups,
+    solver_groups: InteractionGroups,
+    active_events: ActiveEvents,
+    active_collision_types: ActiveCollisionTypes,
+    active_hooks: ActiveHooks,
+    contact_force_event_threshold: Real,
+    user_data: u128,
+}
+
+impl ColliderProperties {
+    fn new(collider: &Collider) -> Self {
+        Self {
+            shape: std::sync::Arc::as_ptr(&collider.shared_shape().0) as *const () as usize,
+            enabled: collider.is_enabled(),
+            sensor: collider.is_sensor(),
+            friction: collider.friction(),
+            restitution: collider.restitution(),
+            density: collider.density(),
+            mass: collider.mass(),
+            collision_groups: collider.collision_groups(),
+            solver_groups: collider.solver_groups(),
+            active_events: collider.active_events(),
+            active_collision_types: collider.active_collision_types(),
+            active_hooks: collider.active_hooks(),
+            contact_force_event_threshold: collider.contact_force_event_threshold(),
+            user_data: collider.user_data,
+        }
+    }
+}
+
+/// the active hooks that the physics hooks turned on for the collider on an entity, so they can be turned off again
+pub(crate) struct HookActiveHooks(ActiveHooks);
+
+/// Gives the collider the active hooks the physics hooks want, and takes away the ones they wanted before but don't anymore.
+/// flags the user set on the collider are kept, returns the flags that were turned on by the hooks
+fn apply_hook_flags(
+    collider: &mut Collider,
+    active_hooks: ActiveHooks,
+    previous_active_hooks: ActiveHooks,
+) -> ActiveHooks {
+    let user_active_hooks = collider.active_hooks() & !previous_active_hooks;
+    let new_active_hooks = user_active_hooks | active_hooks;
+    if collider.active_hooks() != new_active_hooks {
+        collider.set_active_hooks(new_active_hooks);
+    }
+
+    active_hooks & !user_active_hooks
+}
+
+/// what the physics world last knew about the collider on an entity
+pub(crate) struct ColliderSyncState {
+    properties: ColliderProperties,
+    // where the collider was placed, see ColliderAttachment::get_placement
+    placement: Option<Isometry<Real>>,
+}
+
 /// this fn promises to not access the physics info in any way other than the given reference
 fn update_rb(
     physics_info: &mut RapierPhysicsInfo,
@@ -754,8 +1322,10 @@ fn update_rb(
 ) {
     let transform = crate::get_transform(rigidbody_entity, world);
 
-    let (rigidbody, rigidbody_handle) =
-        world.try_get_components_mut::<(RigidBody, RigidBodyHandle)>(rigidbody_entity);
+    let (rigidbody, rigidbody_handle, sync_state) =
+        world.try_get_components_mut::<(RigidBody, RigidBodyHandle, RigidBodySyncState)>(
+            rigidbody_entity,
+        );
 
     let out_rigid_body_set = &mut physics_info.rigid_body_set;
     let out_rigid_body_entity_map = &mut physics_info.rigid_body_handle_map;
@@ -766,37 +1336,73 @@ fn update_rb(
             // get the rigidbody from the handle
             let rigidbody = out_rigid_body_set.get_mut(rigidbody_handle.0);
             if let Some(rigidbody) = rigidbody {
-                // i think this is better than copying and then changing the non-ecs-rb
-                ecs_rigidbody.set_position(abc_transform_to_rapier_transform(transform), false);
+                let properties = RigidBodyProperties::new(ecs_rigidbody);
+                let (properties_changed, transform_changed) = match &sync_state {
+                    Some(sync_state) => (
+                        sync_state.properties != properties,
+                        sync_state.transform != transform,
+                    ),
+                    None => (true, true),
+                };
+
+                // only touch the rapier rigidbody if something changed, otherwise rapier has to redo work for it
+                if properties_changed {
+                    rigidbody.copy_from(ecs_rigidbody);
+                }
+
+                if properties_changed || transform_changed {
+                    let position =
+                        abc_transform_to_rapier_transform(transform, physics_info.pixels_per_meter);
+                    ecs_rigidbody.set_position(position, false);
+                    rigidbody.set_position(position, transform_changed);
+                }
 
-                rigidbody.copy_from(&ecs_rigidbody.clone());
+                match sync_state {
+                    Some(sync_state) => {
+                        sync_state.properties = properties;
+                        sync_state.transform = transform;
+                    }
+                    None => {
+                        world.add_component_to(
+                            rigidbody_entity,
+                            RigidBodySyncState {
+                                properties,
+                                transform,
+                            },
+                        );
+                    }
+                }
             } else {
-                let (new_rb_handle, rb_handle_changed) = add_new_rb(
+                let (new_rb_handle, rb_handle_changed, sync_state) = add_new_rb(
                     rigidbody_entity,
                     ecs_rigidbody,
                     out_rigid_body_set,
                     out_rigid_body_entity_map,
                     transform,
+                    physics_info.pixels_per_meter,
                 );
 
                 // add a handle to the rigidbody to the entity, overwriting the old handle
                 world.add_component_to(rigidbody_entity, new_rb_handle);
                 world.add_component_to(rigidbody_entity, rb_handle_changed);
+                world.add_component_to(rigidbody_entity, sync_state);
             }
         }
         (Some(ecs_rigidbody), None) => {
             // if the rigidbody doesn't have a handle, insert it into the set, and add a handle to the entity
 
-            let (new_rb_handle, rb_handle_changed) = add_new_rb(
+            let (new_rb_handle, rb_handle_changed, sync_state) = add_new_rb(
                 rigidbody_entity,
                 ecs_rigidbody,
                 out_rigid_body_set,
                 out_rigid_body_entity_map,
                 transform,
+                physics_info.pixels_per_meter,
             );
 
             world.add_component_to(rigidbody_entity, new_rb_handle);
             world.add_component_to(rigidbody_entity, rb_handle_changed);
+            world.add_component_to(rigidbody_entity, sync_state);
         }
         _ => {}
     }
@@ -808,8 +1414,12 @@ fn add_new_rb(
     out_rigid_body_set: &mut RigidBodySet,
     out_rigid_body_entity_map: &mut std::collections::HashMap<RigidBodyHandle, Entity>,
     transform: Transform,
-) -> (RigidBodyHandle, RBHandleChanged) {
-    rigidbody.set_position(abc_transform_to_rapier_transform(transform), true);
+    pixels_per_meter: Real,
+) -> (RigidBodyHandle, RBHandleChanged, RigidBodySyncState) {
+    rigidbody.set_position(
+        abc_transform_to_rapier_transform(transform, pixels_per_meter),
+        true,
+    );
 
     // insert the rigidbody into the set
     let new_rb_handle = out_rigid_body_set.insert(rigidbody.clone());
@@ -817,79 +1427,324 @@ fn add_new_rb(
     // add new one to the map
     out_rigid_body_entity_map.insert(RigidBodyHandle(new_rb_handle), entity);
 
-    (RigidBodyHandle(new_rb_handle), RBHandleChanged)
+    let sync_state = RigidBodySyncState {
+        properties: RigidBodyProperties::new(rigidbody),
+        transform,
+    };
+
+    (RigidBodyHandle(new_rb_handle), RBHandleChanged, sync_state)
+}
+
+/// where a collider should be placed in the physics world
+enum ColliderAttachment {
+    /// the collider isn't attached to any rigidbody, it is placed at the world transform of its entity
+    None,
+    /// the collider is on the same entity as its rigidbody
+    OwnBody(RigidBodyHandle),
+    /// the collider is on a child of the entity with the rigidbody, the isometry is relative to that rigidbody
+    AncestorBody(RigidBodyHandle, Isometry<Real>),
+}
+
+impl ColliderAttachment {
+    fn get_body_handle(&self) -> Option<RigidBodyHandle> {
+        match self {
+            ColliderAttachment::None => None,
+            ColliderAttachment::OwnBody(handle) => Some(*handle),
+            ColliderAttachment::AncestorBody(handle, _) => Some(*handle),
+        }
+    }
+
+    /// where the collider is placed, None if it just follows its own rigidbody
+    fn get_placement(
+        &self,
+        world_transform: Transform,
+        pixels_per_meter: Real,
+    ) -> Option<Isometry<Real>> {
+        match self {
+            ColliderAttachment::None => Some(abc_transform_to_rapier_transform(
+                world_transform,
+                pixels_per_meter,
+            )),
+            ColliderAttachment::OwnBody(_) => None,
+            ColliderAttachment::AncestorBody(_, relative_position) => Some(*relative_position),
+        }
+    }
+
+    /// moves the collider to where it should be, this doesn't change the parent of the collider
+    fn place_collider(
+        &self,
+        collider: &mut Collider,
+        world_transform: Transform,
+        pixels_per_meter: Real,
+    ) {
+        match self {
+            ColliderAttachment::None => collider.set_position(abc_transform_to_rapier_transform(
+                world_transform,
+                pixels_per_meter,
+            )),
+            // the collider just follows the rigidbody
+            ColliderAttachment::OwnBody(_) => {}
+            ColliderAttachment::AncestorBody(_, relative_position) => {
+                collider.set_position_wrt_parent(*relative_position)
+            }
+        }
+    }
+}
+
+/// finds the rigidbody that the collider on the given entity should be attached to
+/// this is either the rigidbody on the entity itself or the closest ancestor with a rigidbody in the same physics world
+fn find_collider_attachment(
+    entity: Entity,
+    world: &EntitiesAndComponents,
+    world_ids: &mut PhysicsWorldIdCache,
+    world_transform: &Transform,
+    pixels_per_meter: Real,
+) -> ColliderAttachment {
+    if let Some(rb_handle) = world.try_get_components::<(RigidBodyHandle,)>(entity).0 {
+        return ColliderAttachment::OwnBody(*rb_handle);
+    }
+
+    let world_id = world_ids.of(entity, world);
+    let mut ancestor = world.get_parent(entity);
+    while let Some(ancestor_entity) = ancestor {
+        // a body in another world can't hold this collider
+        if world_ids.of(ancestor_entity, world) != world_id {
+            break;
+        }
+
+        if let Some(rb_handle) = world
+            .try_get_components::<(RigidBodyHandle,)>(ancestor_entity)
+            .0
+        {
+            let ancestor_transform = crate::get_transform(ancestor_entity, world);
+            let relative_transform = world_transform - &ancestor_transform;
+
+            return ColliderAttachment::AncestorBody(
+                *rb_handle,
+                abc_transform_to_rapier_transform(relative_transform, pixels_per_meter),
+            );
+        }
+
+        ancestor = world.get_parent(ancestor_entity);
+    }
+
+    ColliderAttachment::None
+}
+
+/// keeps track of the shape the user gave a collider, so that it can be scaled without losing precision
+pub(crate) struct ScaledColliderShape {
+    pub(crate) base_shape: SharedShape,
+    pub(crate) scaled_shape: SharedShape,
+    pub(crate) scale: Real,
+}
+
+/// scales the shape of the collider on the entity to match the scale of the entity
+/// the shape is only rebuilt when the scale changes or the user gives the collider a new shape
+fn update_collider_scale(
+    world: &mut EntitiesAndComponents,
+    entity: Entity,
+    scale: Real,
+    collider_base_shapes: &mut std::collections::HashMap<Entity, (SharedShape, Real)>,
+) {
+    let scale = sanitize_scale(scale);
+    let (collider, scaled_collider_shape) =
+        world.try_get_components_mut::<(Collider, ScaledColliderShape)>(entity);
+
+    let Some(collider) = collider else {
+        return;
+    };
+
+    let base_shape = match scaled_collider_shape {
+        Some(scaled_collider_shape)
+            if std::sync::Arc::ptr_eq(
+                &scaled_collider_shape.scaled_shape.0,
+                &collider.shared_shape().0,
+            ) =>
+        {
+            if scaled_collider_shape.scale == scale {
+                return;
+            }
+            scaled_collider_shape.base_shape.clone()
+        }
+        Some(_) => collider.shared_shape().clone(),
+        None => {
+            if scale == 1.0 {
+                // nothing has ever been scaled, so there is nothing to keep track of
+                return;
+            }
+            collider.shared_shape().clone()
+        }
+    };
+
+    let scaled_shape = if scale == 1.0 {
+        base_shape.clone()
+    } else {
+        scale_shape(&base_shape, scale)
+    };
+
+    collider.set_shape(scaled_shape.clone());
+    collider_base_shapes.insert(entity, (base_shape.clone(), scale));
+
+    world.add_component_to(
+        entity,
+        ScaledColliderShape {
+            base_shape,
+            scaled_shape,
+            scale,
+        },
+    );
 }
 
 fn update_collider(
     physics_info: &mut RapierPhysicsInfo,
     world: &mut EntitiesAndComponents,
+    world_ids: &mut PhysicsWorldIdCache,
     entity: Entity,
+    active_hooks: ActiveHooks,
 ) {
-    let (collider, transform, collider_handle, rb_handle, handle_has_changed) = world
-        .try_get_components::<(
-            Collider,
-            Transform,
-            ColliderHandle,
-            RigidBodyHandle,
-            RBHandleChanged,
-        )>(entity);
+    let world_transform = crate::get_transform(entity, world);
+    let pixels_per_meter = physics_info.pixels_per_meter;
+    let attachment =
+        find_collider_attachment(entity, world, world_ids, &world_transform, pixels_per_meter);
+    // colliders are built in pixels, so they are scaled down to meters along with the transform scale
+    update_collider_scale(
+        world,
+        entity,
+        world_transform.scale as Real / pixels_per_meter,
+        &mut physics_info.collider_base_shapes,
+    );
+
+    let has_transform = world.try_get_components::<(Transform,)>(entity).0.is_some();
+    let previous_active_hooks = world
+        .try_get_components::<(HookActiveHooks,)>(entity)
+        .0
+        .map(|hook_active_hooks| hook_active_hooks.0);
+    let mut hook_active_hooks = previous_active_hooks;
+    let (collider, collider_handle, handle_has_changed, sync_state) = world
+        .try_get_components_mut::<(Collider, ColliderHandle, RBHandleChanged, ColliderSyncState)>(
+            entity,
+        );
+    let handle_has_changed = handle_has_changed.is_some();
 
     let collider_set = &mut physics_info.collider_set;
     let rigid_body_set = &mut physics_info.rigid_body_set;
 
     let out_collider_entity_map = &mut physics_info.collider_handle_map;
 
-    match (collider, transform, collider_handle) {
-        (Some(ecs_collider), Some(_), Some(collider_handle)) => {
-            if let Some(_) = handle_has_changed {
-                let new_collider_handle = add_new_collider(
+    let placement = attachment.get_placement(world_transform, pixels_per_meter);
+
+    let new_collider_handle = match (collider, has_transform, collider_handle) {
+        (Some(ecs_collider), true, Some(collider_handle)) if !handle_has_changed => {
+            // the entity has a handle, which means it is already in the set, so we just need to update the collider
+            let properties = ColliderProperties::new(ecs_collider);
+            let Some(collider) = collider_set.get_mut(collider_handle.0) else {
+                // this means the handle is invalid, so we should insert the collider into the set
+                let added_active_hooks = apply_hook_flags(
+                    ecs_collider,
+                    active_hooks,
+                    previous_active_hooks.unwrap_or(ActiveHooks::empty()),
+                );
+                let (new_collider_handle, new_sync_state) = add_new_collider(
                     entity,
                     ecs_collider,
-                    rb_handle,
+                    &attachment,
+                    world_transform,
                     rigid_body_set,
                     collider_set,
                     out_collider_entity_map,
+                    pixels_per_meter,
                 );
 
                 // add a handle to the collider to the entity
                 world.add_component_to(entity, new_collider_handle);
-                world.remove_component_from::<RBHandleChanged>(entity);
-            } else {
-                // the entity has a handle, which means it is already in the set, so we just need to update the collider
-                // get the collider from the handle
-                let collider = collider_set.get_mut(collider_handle.0);
-
-                if let Some(collider) = collider {
-                    collider.copy_from(&ecs_collider.clone());
-                } else {
-                    // this means the handle is invalid, so we should insert the collider into the set
-                    let new_collider_handle = add_new_collider(
-                        entity,
-                        ecs_collider,
-                        rb_handle,
-                        rigid_body_set,
-                        collider_set,
-                        out_collider_entity_map,
-                    );
-
-                    // add a handle to the collider to the entity
-                    world.add_component_to(entity, new_collider_handle);
+                world.add_component_to(entity, new_sync_state);
+                world.add_component_to(entity, HookActiveHooks(added_active_hooks));
+                return;
+            };
+
+            let properties_changed = match &sync_state {
+                Some(sync_state) => sync_state.properties != properties,
+                None => true,
+            };
+            let placement_changed = match &sync_state {
+                Some(sync_state) => sync_state.placement != placement,
+                None => true,
+            };
+
+            // only touch the rapier collider if something changed, otherwise rapier has to redo work for it
+            if properties_changed {
+                collider.copy_from(ecs_collider);
+            }
+
+            // recomputed every frame, so removing the component a hook looks for turns the hook off
+            hook_active_hooks = Some(apply_hook_flags(
+                collider,
+                active_hooks,
+                previous_active_hooks.unwrap_or(ActiveHooks::empty()),
+            ));
+
+            let parent_has_changed =
+                collider.parent() != attachment.get_body_handle().map(|handle| handle.0);
+
+            if parent_has_changed {
+                collider_set.set_parent(
+                    collider_handle.0,
+                    attachment.get_body_handle().map(|handle| handle.0),
+                    rigid_body_set,
+                );
+            }
+
+            let collider = collider_set.get_mut(collider_handle.0).expect(
+                "failed to get collider from handle found in entity, please report this as a bug",
+            );
+
+            if properties_changed || placement_changed || parent_has_changed {
+                attachment.place_collider(collider, world_transform, pixels_per_meter);
+            }
+
+            let changed = properties_changed
+                || placement_changed
+                || parent_has_changed
+                || collider.active_hooks() != properties.active_hooks;
+
+            match sync_state {
+                Some(sync_state) => {
+                    if changed {
+                        // let the user see where the collider ended up
+                        *ecs_collider = collider.clone();
+                        sync_state.properties = ColliderProperties::new(ecs_collider);
+                    }
+                    sync_state.placement = placement;
+                }
+                None => {
+                    *ecs_collider = collider.clone();
+                    let sync_state = ColliderSyncState {
+                        properties: ColliderProperties::new(ecs_collider),
+                        placement,
+                    };
+                    world.add_component_to(entity, sync_state);
                 }
             }
+
+            None
         }
-        (Some(collider), Some(_), None) => {
-            // if the collider doesn't have a handle, insert it into the set, and add a handle to the entity
-            let new_collider_handle = add_new_collider(
+        (Some(ecs_collider), true, _) => {
+            // if the collider doesn't have a handle or the rigidbody it was attached to changed, insert it into the set, and add a handle to the entity
+            hook_active_hooks = Some(apply_hook_flags(
+                ecs_collider,
+                active_hooks,
+                previous_active_hooks.unwrap_or(ActiveHooks::empty()),
+            ));
+            Some(add_new_collider(
                 entity,
-                collider,
-                rb_handle,
+                ecs_collider,
+                &attachment,
+                world_transform,
                 rigid_body_set,
                 collider_set,
                 out_collider_entity_map,
-            );
-
-            // add a handle to the collider to the entity
-            world.add_component_to(entity, new_collider_handle);
+                pixels_per_meter,
+            ))
         }
         _ => {
             // log warning that collider is missing transform
@@ -897,45 +1752,99 @@ fn update_collider(
                 Level::WARN,
                 "collider is missing transform, the collider will not be simulated without one"
             );
+            None
+        }
+    };
+
+    if let Some((new_collider_handle, sync_state)) = new_collider_handle {
+        // add a handle to the collider to the entity
+        world.add_component_to(entity, new_collider_handle);
+        world.add_component_to(entity, sync_state);
+    }
+
+    if hook_active_hooks != previous_active_hooks {
+        if let Some(hook_active_hooks) = hook_active_hooks {
+            world.add_component_to(entity, HookActiveHooks(hook_active_hooks));
         }
     }
+
+    if handle_has_changed {
+        world.remove_component_from::<RBHandleChanged>(entity);
+    }
 }
 
 /// This function adds a new collider to the world and adds a handle to the entity
 fn add_new_collider(
     entity: Entity,
-    collider: &Collider,
-    rb_handle: Option<&RigidBodyHandle>,
+    collider: &mut Collider,
+    attachment: &ColliderAttachment,
+    world_transform: Transform,
     rigid_body_set: &mut RigidBodySet,
     collider_set: &mut ColliderSet,
     out_collider_entity_map: &mut std::collections::HashMap<ColliderHandle, Entity>,
-) -> ColliderHandle {
-    let collider = collider.clone();
+    pixels_per_meter: Real,
+) -> (ColliderHandle, ColliderSyncState) {
+    attachment.place_collider(collider, world_transform, pixels_per_meter);
 
-    let new_collider_handle = if let Some(rigidbody_handle) = rb_handle {
-        collider_set.insert_with_parent(collider, rigidbody_handle.0, rigid_body_set)
+    let new_collider_handle = if let Some(rigidbody_handle) = attachment.get_body_handle() {
+        collider_set.insert_with_parent(collider.clone(), rigidbody_handle.0, rigid_body_set)
     } else {
-        collider_set.insert(collider)
+        collider_set.insert(collider.clone())
     };
 
     out_collider_entity_map.insert(ColliderHandle(new_collider_handle), entity);
 
-    ColliderHandle(new_collider_handle)
+    let sync_state = ColliderSyncState {
+        properties: ColliderProperties::new(collider),
+        placement: attachment.get_placement(world_transform, pixels_per_meter),
+    };
+
+    (ColliderHandle(new_collider_handle), sync_state)
 }
 
 /// This function updates the transforms of all rigid bodies and colliders in the world
-fn set_all_rigid_bodies_and_colliders(
+pub(crate) fn set_all_rigid_bodies_and_colliders(
     physics_info: &RapierPhysicsInfo,
     world: &mut EntitiesAndComponents,
+) {
+    set_rigid_bodies_and_colliders(
+        physics_info,
+        world,
+        physics_info.rigid_body_handle_map.keys().copied(),
+    );
+
+    // colliders that aren't attached to a rigidbody never move on their own, but they still need to be up to date
+    for (collider_handle, entity) in physics_info.collider_handle_map.iter() {
+        if let Some(collider) = physics_info.collider_set.get(collider_handle.0) {
+            if collider.parent().is_none() {
+                set_collider(collider, *entity, world);
+            }
+        }
+    }
+}
+
+/// This function updates the transforms of the given rigid bodies and the colliders attached to them
+/// the physics info is only read from, so bodies that didn't move can be skipped
+fn set_rigid_bodies_and_colliders(
+    physics_info: &RapierPhysicsInfo,
+    world: &mut EntitiesAndComponents,
+    rb_handles: impl IntoIterator<Item = RigidBodyHandle>,
 ) {
     let rigid_body_set = &physics_info.rigid_body_set;
     let collider_set = &physics_info.collider_set;
 
-    for (rb_handle, entity) in physics_info.rigid_body_handle_map.iter() {
-        let transform_total = crate::get_transform(*entity, world);
+    for rb_handle in rb_handles {
+        let Some(entity) = physics_info.rigid_body_handle_map.get(&rb_handle).copied() else {
+            continue;
+        };
+        let Some(rigidbody) = rigid_body_set.get(rb_handle.0) else {
+            continue;
+        };
+
+        let transform_total = crate::get_transform(entity, world);
 
-        let (rigidbody, transform, rigidbody_handle) =
-            world.try_get_components_mut::<(RigidBody, Transform, RigidBodyHandle)>(*entity);
+        let (ecs_rigidbody, transform, rigidbody_handle) =
+            world.try_get_components_mut::<(RigidBody, Transform, RigidBodyHandle)>(entity);
 
         if transform.is_none() {
             // log warning that rigidbody is missing transform
@@ -949,45 +1858,127 @@ fn set_all_rigid_bodies_and_colliders(
         let transform = transform.unwrap();
         let transform_offset = &transform_total - &transform.clone();
 
-        match (rigidbody, rigidbody_handle) {
-            (Some(ecs_rigidbody), Some(rigidbody_handle)) => {
-                let rigidbody = rigid_body_set.get(rigidbody_handle.0).expect("failed to get rigidbody from handle found in entity, please report this as a bug on abc game engine github page");
+        match (ecs_rigidbody, rigidbody_handle) {
+            (Some(ecs_rigidbody), Some(rigidbody_handle)) if *rigidbody_handle == rb_handle => {
                 update_abc_transform_from_rapier_transform(
                     transform,
                     transform_offset,
                     *rigidbody.position(),
+                    physics_info.pixels_per_meter,
                 );
 
                 *ecs_rigidbody = rigidbody.clone();
             }
-            _ => {}
+            _ => continue,
+        }
+
+        // remember what was written so that it isn't pushed straight back into the physics world next frame
+        let sync_state = RigidBodySyncState {
+            properties: RigidBodyProperties::new(rigidbody),
+            transform: crate::get_transform(entity, world),
+        };
+        world.add_component_to(entity, sync_state);
+
+        for collider_handle in rigidbody.colliders() {
+            let (Some(collider), Some(collider_entity)) = (
+                collider_set.get(*collider_handle),
+                physics_info
+                    .collider_handle_map
+                    .get(&ColliderHandle(*collider_handle)),
+            ) else {
+                continue;
+            };
+
+            set_collider(collider, *collider_entity, world);
         }
     }
+}
 
-    for (collider_handle, entity) in physics_info.collider_handle_map.iter() {
-        let (collider, transform, collider_handle) =
-            world.try_get_components_mut::<(Collider, Transform, ColliderHandle)>(*entity);
-        if let (Some(ecs_collider), Some(transform), Some(collider_handle)) =
-            (collider, transform, collider_handle)
-        {
-            let collider = collider_set.get(collider_handle.0).expect(
-                "failed to get collider from handle found in entity, please report this as a bug",
-            );
+/// copies the collider from the physics world into the entity
+fn set_collider(collider: &Collider, entity: Entity, world: &mut EntitiesAndComponents) {
+    let (ecs_collider, transform, sync_state) =
+        world.try_get_components_mut::<(Collider, Transform, ColliderSyncState)>(entity);
 
-            *ecs_collider = collider.clone();
-        } else {
-            // log warning that collider is missing transform
-            event!(
-                Level::WARN,
-                "collider is missing transform, the collider will not be simulated without one"
-            );
+    if let (Some(ecs_collider), Some(_)) = (ecs_collider, transform) {
+        *ecs_collider = collider.clone();
+
+        if let Some(sync_state) = sync_state {
+            sync_state.properties = ColliderProperties::new(collider);
+        }
+    } else {
+        // log warning that collider is missing transform
+        event!(
+            Level::WARN,
+            "collider is missing transform, the collider will not be simulated without one"
+        );
+    }
+}
+
+/// removes the given component from every entity that has it
+pub(crate) fn remove_all_components_of_type<T: Component>(
+    entities_and_components: &mut EntitiesAndComponents,
+    world_id: PhysicsWorldId,
+) {
+    let mut entities = entities_and_components
+        .get_entities_with_component::<T>()
+        .copied()
+        .collect::<Vec<Entity>>();
+    entities.retain(|entity| PhysicsWorldId::of(*entity, entities_and_components) == world_id);
+
+    for entity in entities {
+        entities_and_components.remove_component_from::<T>(entity);
+    }
+}
+
+/// Takes everything the physics system added to the entities in the physics world back off of them,
+/// so their rigidbodies and colliders are added from scratch if they are ever simulated again
+pub(crate) fn remove_physics_components(
+    entities_and_components: &mut EntitiesAndComponents,
+    world_id: PhysicsWorldId,
+) {
+    let mut collider_entities = entities_and_components
+        .get_entities_with_component::<Collider>()
+        .copied()
+        .collect::<Vec<Entity>>();
+    collider_entities
+        .retain(|entity| PhysicsWorldId::of(*entity, entities_and_components) == world_id);
+
+    // give the colliders back the shape and hooks the user gave them, or they would be scaled twice
+    for entity in collider_entities {
+        let (collider, scaled_collider_shape, hook_active_hooks) = entities_and_components
+            .try_get_components_mut::<(Collider, ScaledColliderShape, HookActiveHooks)>(entity);
+        let Some(collider) = collider else {
+            continue;
+        };
+
+        if let Some(scaled_collider_shape) = scaled_collider_shape {
+            collider.set_shape(scaled_collider_shape.base_shape.clone());
+        }
+        if let Some(hook_active_hooks) = hook_active_hooks {
+            collider.set_active_hooks(collider.active_hooks() & !hook_active_hooks.0);
         }
     }
+
+    remove_all_components_of_type::<RigidBodyHandle>(entities_and_components, world_id);
+    remove_all_components_of_type::<ColliderHandle>(entities_and_components, world_id);
+    remove_all_components_of_type::<RBHandleChanged>(entities_and_components, world_id);
+    remove_all_components_of_type::<RigidBodySyncState>(entities_and_components, world_id);
+    remove_all_components_of_type::<ColliderSyncState>(entities_and_components, world_id);
+    remove_all_components_of_type::<ScaledColliderShape>(entities_and_components, world_id);
+    remove_all_components_of_type::<HookActiveHooks>(entities_and_components, world_id);
 }
 
-fn abc_transform_to_rapier_transform(transform: Transform) -> Isometry<Real> {
+// transforms are in pixels and rapier is in meters, see PhysicsSettings::set_pixels_per_meter
+fn abc_transform_to_rapier_transform(
+    transform: Transform,
+    pixels_per_meter: Real,
+) -> Isometry<Real> {
+    let pixels_per_meter = pixels_per_meter as f64;
     let new_transform = Isometry::new(
-        vector![transform.x as f32, transform.y as f32],
+        vector![
+            (transform.x / pixels_per_meter) as f32,
+            (transform.y / pixels_per_meter) as f32
+        ],
         transform.rotation as f32,
     );
     new_transform
@@ -997,10 +1988,273 @@ fn update_abc_transform_from_rapier_transform(
     transform: &mut Transform,
     offset: Transform, // offset is the parent transform
     rapier_transform: Isometry<Real>,
+    pixels_per_meter: Real,
 ) {
+    let pixels_per_meter = pixels_per_meter as f64;
+
     // we subtract the offset to get the local transform
-    transform.x = rapier_transform.translation.x as f64 - offset.x;
-    transform.y = rapier_transform.translation.y as f64 - offset.y;
+    transform.x = rapier_transform.translation.x as f64 * pixels_per_meter - offset.x;
+    transform.y = rapier_transform.translation.y as f64 * pixels_per_meter - offset.y;
 
     transform.rotation = rapier_transform.rotation.angle() as f64 - offset.rotation;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::physics_test_world;
+    use ABC_ECS::World;
+
+    #[test]
+    fn steps_follow_the_frame_time() {
+        let step_time = 0.25;
+        let mut accumulated_time = 0.0;
+
+        // a short frame doesn't step, but its time isn't lost
+        assert_eq!(
+            take_fixed_steps(&mut accumulated_time, 0.125, step_time, 4),
+            0
+        );
+        assert_eq!(
+            take_fixed_steps(&mut accumulated_time, 0.125, step_time, 4),
+            1
+        );
+        assert_eq!(accumulated_time, 0.0);
+
+        // a long frame catches up with more steps and keeps what is left over
+        assert_eq!(
+            take_fixed_steps(&mut accumulated_time, 0.875, step_time, 4),
+            3
+        );
+        assert_eq!(accumulated_time, 0.125);
+
+        assert_eq!(
+            take_fixed_steps(&mut accumulated_time, -1.0, step_time, 4),
+            0
+        );
+        assert_eq!(accumulated_time, 0.125);
+    }
+
+    #[test]
+    fn catching_up_is_limited() {
+        let step_time = 0.25;
+        let mut accumulated_time = 0.0;
+
+        // the time past the limit is dropped instead of being stepped on later frames
+        assert_eq!(
+            take_fixed_steps(&mut accumulated_time, 10.125, step_time, 4),
+            4
+        );
+        assert_eq!(accumulated_time, 0.125);
+        assert_eq!(
+            take_fixed_steps(&mut accumulated_time, 0.125, step_time, 4),
+            1
+        );
+
+        // at least one step is always allowed
+        assert_eq!(
+            take_fixed_steps(&mut accumulated_time, 1.0, step_time, 0),
+            1
+        );
+    }
+
+    /// syncs the components with the physics world, the delta time isn't updated so no steps are taken
+    fn sync(world: &mut World) {
+        RapierPhysicsSystem {}.run(&mut world.entities_and_components);
+    }
+
+    fn physics_info(world: &mut World) -> &mut RapierPhysicsInfo {
+        world
+            .entities_and_components
+            .get_resource_mut::<RapierPhysicsInfo>()
+            .unwrap()
+    }
+
+    #[test]
+    fn a_new_collider_shape_is_synced() {
+        let mut world = physics_test_world();
+        let entity = world
+            .entities_and_components
+            .add_entity_with((Transform::default(), ColliderBuilder::ball(1.0).build()));
+        sync(&mut world);
+
+        let (collider,) = world
+            .entities_and_components
+            .get_components_mut::<(Collider,)>(entity);
+        collider.set_shape(SharedShape::ball(2.0));
+        sync(&mut world);
+
+        let handle = *world
+            .entities_and_components
+            .get_components::<(ColliderHandle,)>(entity)
+            .0;
+        let collider = &physics_info(&mut world).collider_set[handle.0];
+        assert_eq!(collider.shape().as_ball().unwrap().radius, 2.0);
+    }
+
+    #[test]
+    fn unchanged_components_are_not_synced() {
+        let mut world = physics_test_world();
+        let entity = world.entities_and_components.add_entity_with((
+            Transform::default(),
+            RigidBodyBuilder::dynamic().build(),
+            ColliderBuilder::ball(1.0).friction(0.5).build(),
+        ));
+        sync(&mut world);
+
+        let (rb_handle, collider_handle) = world
+            .entities_and_components
+            .get_components::<(RigidBodyHandle, ColliderHandle)>(entity);
+        let (rb_handle, collider_handle) = (*rb_handle, *collider_handle);
+
+        // if the components were copied over these would be put back
+        let info = physics_info(&mut world);
+        info.rigid_body_set[rb_handle.0].set_linvel(vector![3.0, 0.0], true);
+        info.collider_set[collider_handle.0].set_friction(0.25);
+        sync(&mut world);
+
+        let info = physics_info(&mut world);
+        assert_eq!(
+            *info.rigid_body_set[rb_handle.0].linvel(),
+            vector![3.0, 0.0]
+        );
+        assert_eq!(info.collider_set[collider_handle.0].friction(), 0.25);
+
+        // but a change to a component still is
+        let (rigidbody,) = world
+            .entities_and_components
+            .get_components_mut::<(RigidBody,)>(entity);
+        rigidbody.set_linvel(vector![0.0, 1.0], true);
+        sync(&mut world);
+        assert_eq!(
+            *physics_info(&mut world).rigid_body_set[rb_handle.0].linvel(),
+            vector![0.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn bodies_moved_by_the_physics_world_are_pulled_back() {
+        let mut world = physics_test_world();
+        let entity = world.entities_and_components.add_entity_with((
+            Transform::default(),
+            RigidBodyBuilder::dynamic().build(),
+            ColliderBuilder::ball(1.0).build(),
+        ));
+
+        // make sure at least one step is taken
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        world.run();
+
+        let (transform, rigidbody, rb_handle) =
+            world
+                .entities_and_components
+                .get_components::<(Transform, RigidBody, RigidBodyHandle)>(entity);
+        let (transform, ecs_translation, rb_handle) =
+            (*transform, *rigidbody.translation(), *rb_handle);
+        let rapier_translation =
+            *physics_info(&mut world).rigid_body_set[rb_handle.0].translation();
+
+        // the body fell, and the components know about it
+        assert!(rapier_translation.y < 0.0);
+        assert_eq!(ecs_translation, rapier_translation);
+        assert_eq!(transform.y, rapier_translation.y as f64);
+
+        // and syncing doesn't push an old position back
+        sync(&mut world);
+        assert_eq!(
+            *physics_info(&mut world).rigid_body_set[rb_handle.0].translation(),
+            rapier_translation
+        );
+    }
+
+    #[test]
+    fn entities_are_sorted_in_the_order_they_were_first_seen() {
+        let mut world = World::new();
+        let mut physics_info = RapierPhysicsInfo::new(PhysicsWorldId::DEFAULT);
+        let first = (0..10)
+            .map(|_| world.entities_and_components.add_entity())
+            .collect::<Vec<Entity>>();
+        let second = (0..10)
+            .map(|_| world.entities_and_components.add_entity())
+            .collect::<Vec<Entity>>();
+
+        physics_info.order_new_entities(&second);
+        // seeing them again doesn't move them
+        physics_info.order_new_entities(&[first.clone(), second.clone()].concat());
+        physics_info.order_new_entities(&second);
+
+        let mut shuffled = [first.clone(), second.clone()].concat();
+        shuffled.reverse();
+        shuffled.swap(3, 11);
+        physics_info.sort_entities_deterministically(&mut shuffled);
+
+        let mut sorted_second = second.clone();
+        physics_info.sort_entities_deterministically(&mut sorted_second);
+        assert_eq!(shuffled[..10], sorted_second[..]);
+        assert!(shuffled[10..].iter().all(|entity| first.contains(entity)));
+
+        // entities that were numbered together are ordered the same way every time
+        let mut other_physics_info = RapierPhysicsInfo::new(PhysicsWorldId::DEFAULT);
+        let mut reversed_second = second.clone();
+        reversed_second.reverse();
+        other_physics_info.order_new_entities(&reversed_second);
+        other_physics_info.sort_entities_deterministically(&mut reversed_second);
+        assert_eq!(reversed_second, sorted_second);
+    }
+
+    fn deterministic_world_with_a_falling_ball() -> ABC_ECS::World {
+        let mut world = physics_test_world();
+        world
+            .entities_and_components
+            .get_resource_mut::<PhysicsSettings>()
+            .unwrap()
+            .set_deterministic(true);
+        world.entities_and_components.add_entity_with((
+            Transform::default(),
+            RigidBodyBuilder::dynamic().build(),
+            ColliderBuilder::ball(1.0).build(),
+        ));
+        world.entities_and_components.add_entity_with((
+            Transform {
+                y: -10.0,
+                ..Default::default()
+            },
+            ColliderBuilder::cuboid(10.0, 1.0).build(),
+        ));
+        world
+    }
+
+    fn run_ticks(world: &mut ABC_ECS::World, ticks: usize) -> u64 {
+        for _ in 0..ticks {
+            get_physics_info_mut(&mut world.entities_and_components, PhysicsWorldId::DEFAULT)
+                .unwrap()
+                .step_once();
+            world.run();
+        }
+
+        get_physics_info(&world.entities_and_components, PhysicsWorldId::DEFAULT)
+            .unwrap()
+            .state_hash()
+    }
+
+    #[test]
+    fn the_same_ticks_give_the_same_hash() {
+        let mut first = deterministic_world_with_a_falling_ball();
+        let mut second = deterministic_world_with_a_falling_ball();
+
+        // frames without a tick don't step, no matter how much time passed
+        first.run();
+        first.run();
+
+        for ticks in [1, 5, 30] {
+            assert_eq!(run_ticks(&mut first, ticks), run_ticks(&mut second, ticks));
+        }
+
+        let hash = run_ticks(&mut first, 0);
+        get_physics_info_mut(&mut second.entities_and_components, PhysicsWorldId::DEFAULT)
+            .unwrap()
+            .set_gravity(vector![0.0, -20.0]);
+        assert_ne!(run_ticks(&mut second, 1), run_ticks(&mut first, 1));
+        assert_ne!(run_ticks(&mut first, 0), hash);
+    }
+}
@@ -0,0 +1,408 @@
+use std::collections::BTreeMap;
+
+use fxhash::FxHashMap;
+use ABC_ECS::EntitiesAndComponents;
+use ABC_ECS::Entity;
+use ABC_ECS::Resource;
+
+use crate::physics::physics_settings::PhysicsSettings;
+use crate::physics::physics_system::{remove_physics_components, RapierPhysicsInfo};
+
+/// A component that decides which physics world an entity is simulated in.
+/// entities without one use the world of their closest ancestor that has one, or the default world.
+/// an entity should not be moved to another world after its rigidbody or collider has been added,
+/// remove the RigidBody and Collider components first and add them back after
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PhysicsWorldId(pub u32);
+
+impl PhysicsWorldId {
+    /// the world made by add_default_physics_systems, its physics info and settings are resources
+    pub const DEFAULT: PhysicsWorldId = PhysicsWorldId(0);
+
+    /// the physics world the given entity is simulated in
+    pub fn of(entity: Entity, entities_and_components: &EntitiesAndComponents) -> Self {
+        let mut current = Some(entity);
+        while let Some(current_entity) = current {
+            if let (Some(world_id),) =
+                entities_and_components.try_get_components::<(PhysicsWorldId,)>(current_entity)
+            {
+                return *world_id;
+            }
+
+            current = entities_and_components.get_parent(current_entity);
+        }
+
+        PhysicsWorldId::DEFAULT
+    }
+}
+
+/// Remembers the physics world of every entity it was asked about and of their ancestors,
+/// so a sync pass walks up the parents of each entity only once instead of for every lookup.
+/// it is only valid while no PhysicsWorldId components or parents are changed
+#[derive(Default)]
+pub(crate) struct PhysicsWorldIdCache {
+    world_ids: FxHashMap<Entity, PhysicsWorldId>,
+}
+
+impl PhysicsWorldIdCache {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// the same as PhysicsWorldId::of
+    pub(crate) fn of(
+        &mut self,
+        entity: Entity,
+        entities_and_components: &EntitiesAndComponents,
+    ) -> PhysicsWorldId {
+        let mut unresolved = vec![];
+        let mut current = Some(entity);
+        let world_id = loop {
+            let Some(current_entity) = current else {
+                break PhysicsWorldId::DEFAULT;
+            };
+            if let Some(world_id) = self.world_ids.get(&current_entity) {
+                break *world_id;
+            }
+            if let (Some(world_id),) =
+                entities_and_components.try_get_components::<(PhysicsWorldId,)>(current_entity)
+            {
+                break *world_id;
+            }
+
+            unresolved.push(current_entity);
+            current = entities_and_components.get_parent(current_entity);
+        };
+
+        // everything between the entity and where the id was found is in the same world
+        for unresolved_entity in unresolved {
+            self.world_ids.insert(unresolved_entity, world_id);
+        }
+
+        world_id
+    }
+}
+
+struct PhysicsWorld {
+    physics_info: RapierPhysicsInfo,
+    settings: PhysicsSettings,
+}
+
+/// The resource that holds every physics world except the default one, which is the RapierPhysicsInfo resource.
+/// each world is stepped by the physics system with its own settings, add one with add_physics_world
+pub struct PhysicsWorlds {
+    worlds: BTreeMap<PhysicsWorldId, PhysicsWorld>,
+}
+
+impl PhysicsWorlds {
+    pub(crate) fn new() -> Self {
+        Self {
+            worlds: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, world_id: PhysicsWorldId) -> Option<&RapierPhysicsInfo> {
+        self.worlds
+            .get(&world_id)
+            .map(|physics_world| &physics_world.physics_info)
+    }
+
+    pub fn get_mut(&mut self, world_id: PhysicsWorldId) -> Option<&mut RapierPhysicsInfo> {
+        self.worlds
+            .get_mut(&world_id)
+            .map(|physics_world| &mut physics_world.physics_info)
+    }
+
+    pub fn get_settings(&self, world_id: PhysicsWorldId) -> Option<&PhysicsSettings> {
+        self.worlds
+            .get(&world_id)
+            .map(|physics_world| &physics_world.settings)
+    }
+
+    pub fn get_settings_mut(&mut self, world_id: PhysicsWorldId) -> Option<&mut PhysicsSettings> {
+        self.worlds
+            .get_mut(&world_id)
+            .map(|physics_world| &mut physics_world.settings)
+    }
+
+    /// the ids of every world in here, in order, this doesn't include the default world
+    pub fn get_ids(&self) -> Vec<PhysicsWorldId> {
+        self.worlds.keys().copied().collect()
+    }
+}
+
+impl Resource for PhysicsWorlds {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+/// Adds a new physics world that is stepped alongside the default one.
+/// entities are put in it with the PhysicsWorldId component, add_default_physics_systems must be called first
+pub fn add_physics_world(
+    entities_and_components: &mut EntitiesAndComponents,
+    world_id: PhysicsWorldId,
+    settings: PhysicsSettings,
+) {
+    assert!(
+        world_id != PhysicsWorldId::DEFAULT,
+        "the default physics world is made by add_default_physics_systems"
+    );
+
+    let physics_worlds = entities_and_components
+        .get_resource_mut::<PhysicsWorlds>()
+        .expect("failed to get physics worlds, call add_default_physics_systems first");
+
+    assert!(
+        !physics_worlds.worlds.contains_key(&world_id),
+        "physics world {:?} already exists",
+        world_id
+    );
+
+    physics_worlds.worlds.insert(
+        world_id,
+        PhysicsWorld {
+            physics_info: RapierPhysicsInfo::new(world_id),
+            settings,
+        },
+    );
+}
+
+/// Removes a physics world, the entities in it stop being simulated.
+/// the handles the physics system gave them are removed too, so they can be moved to another world
+/// returns false if there was no world with the given id
+pub fn remove_physics_world(
+    entities_and_components: &mut EntitiesAndComponents,
+    world_id: PhysicsWorldId,
+) -> bool {
+    let removed = entities_and_components
+        .get_resource_mut::<PhysicsWorlds>()
+        .map_or(false, |physics_worlds| {
+            physics_worlds.worlds.remove(&world_id).is_some()
+        });
+
+    if removed {
+        remove_physics_components(entities_and_components, world_id);
+    }
+
+    removed
+}
+
+/// gets the physics info of any world, including the default one
+pub fn get_physics_info(
+    entities_and_components: &EntitiesAndComponents,
+    world_id: PhysicsWorldId,
+) -> Option<&RapierPhysicsInfo> {
+    if world_id == PhysicsWorldId::DEFAULT {
+        entities_and_components.get_resource::<RapierPhysicsInfo>()
+    } else {
+        entities_and_components
+            .get_resource::<PhysicsWorlds>()?
+            .get(world_id)
+    }
+}
+
+/// gets the physics info of any world, including the default one
+pub fn get_physics_info_mut(
+    entities_and_components: &mut EntitiesAndComponents,
+    world_id: PhysicsWorldId,
+) -> Option<&mut RapierPhysicsInfo> {
+    if world_id == PhysicsWorldId::DEFAULT {
+        entities_and_components.get_resource_mut::<RapierPhysicsInfo>()
+    } else {
+        entities_and_components
+            .get_resource_mut::<PhysicsWorlds>()?
+            .get_mut(world_id)
+    }
+}
+
+/// the settings of any world, the defaults are used if they are missing
+pub(crate) fn get_physics_settings(
+    entities_and_components: &EntitiesAndComponents,
+    world_id: PhysicsWorldId,
+) -> PhysicsSettings {
+    let settings = if world_id == PhysicsWorldId::DEFAULT {
+        entities_and_components.get_resource::<PhysicsSettings>()
+    } else {
+        entities_and_components
+            .get_resource::<PhysicsWorlds>()
+            .and_then(|physics_worlds| physics_worlds.get_settings(world_id))
+    };
+
+    settings.cloned().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::add_default_physics_systems;
+    use crate::physics::physics_system::RigidBodyHandle;
+    use crate::physics::physics_test_world;
+    use crate::Transform;
+    use rapier2d::prelude::*;
+
+    fn body_count(
+        entities_and_components: &EntitiesAndComponents,
+        world_id: PhysicsWorldId,
+    ) -> usize {
+        get_physics_info(entities_and_components, world_id)
+            .unwrap()
+            .rigid_body_set
+            .len()
+    }
+
+    #[test]
+    fn worlds_are_simulated_separately() {
+        let mut world = physics_test_world();
+        let entities_and_components = &mut world.entities_and_components;
+        let other_world = PhysicsWorldId(1);
+        add_physics_world(
+            entities_and_components,
+            other_world,
+            PhysicsSettings::default(),
+        );
+        get_physics_info_mut(entities_and_components, other_world)
+            .unwrap()
+            .set_gravity(vector![0.0, 0.0]);
+
+        let falling = entities_and_components.add_entity_with((
+            Transform::default(),
+            RigidBodyBuilder::dynamic().build(),
+            ColliderBuilder::ball(1.0).build(),
+        ));
+        // in the same place, but the other world has no gravity and the balls can't touch
+        let floating = entities_and_components.add_entity_with((
+            Transform::default(),
+            RigidBodyBuilder::dynamic().build(),
+            ColliderBuilder::ball(1.0).build(),
+            other_world,
+        ));
+
+        for world_id in [PhysicsWorldId::DEFAULT, other_world] {
+            let physics_info = get_physics_info_mut(entities_and_components, world_id).unwrap();
+            physics_info.pause();
+            physics_info.step_n(10);
+        }
+        world.run();
+
+        let entities_and_components = &mut world.entities_and_components;
+        assert_eq!(
+            body_count(entities_and_components, PhysicsWorldId::DEFAULT),
+            1
+        );
+        assert_eq!(body_count(entities_and_components, other_world), 1);
+        let (falling_transform,) = entities_and_components.get_components::<(Transform,)>(falling);
+        assert!(falling_transform.y < 0.0);
+        let (floating_transform,) =
+            entities_and_components.get_components::<(Transform,)>(floating);
+        assert_eq!((floating_transform.x, floating_transform.y), (0.0, 0.0));
+
+        // re-adding the physics systems keeps the default world
+        add_default_physics_systems(&mut world);
+        let entities_and_components = &mut world.entities_and_components;
+        assert_eq!(
+            body_count(entities_and_components, PhysicsWorldId::DEFAULT),
+            1
+        );
+
+        assert!(remove_physics_world(entities_and_components, other_world));
+        assert!(entities_and_components
+            .try_get_components::<(RigidBodyHandle,)>(floating)
+            .0
+            .is_none());
+        assert!(entities_and_components
+            .try_get_components::<(RigidBodyHandle,)>(falling)
+            .0
+            .is_some());
+        assert!(!remove_physics_world(entities_and_components, other_world));
+    }
+
+    #[test]
+    fn re_adding_the_physics_systems_keeps_the_worlds_and_their_settings() {
+        let mut world = physics_test_world();
+        let entities_and_components = &mut world.entities_and_components;
+        let other_world = PhysicsWorldId(1);
+        add_physics_world(
+            entities_and_components,
+            other_world,
+            PhysicsSettings::default(),
+        );
+        get_physics_info_mut(entities_and_components, PhysicsWorldId::DEFAULT)
+            .unwrap()
+            .set_gravity(vector![0.0, 0.0]);
+        entities_and_components
+            .get_resource_mut::<PhysicsSettings>()
+            .unwrap()
+            .set_pixels_per_meter(2.0);
+        let entity = entities_and_components.add_entity_with((
+            Transform::default(),
+            RigidBodyBuilder::dynamic().build(),
+            ColliderBuilder::ball(1.0).build(),
+        ));
+        world.run();
+        let (rb_handle,) = world
+            .entities_and_components
+            .get_components::<(RigidBodyHandle,)>(entity);
+        let rb_handle = *rb_handle;
+
+        add_default_physics_systems(&mut world);
+        world.run();
+
+        // the entity still has a handle into the default world, so it mustn't be replaced
+        let entities_and_components = &world.entities_and_components;
+        let physics_info =
+            get_physics_info(entities_and_components, PhysicsWorldId::DEFAULT).unwrap();
+        assert_eq!(physics_info.gravity, vector![0.0, 0.0]);
+        assert_eq!(physics_info.rigid_body_set.len(), 1);
+        assert!(physics_info.rigid_body_set.contains(rb_handle.0));
+        assert_eq!(
+            entities_and_components
+                .get_resource::<PhysicsSettings>()
+                .unwrap()
+                .get_pixels_per_meter(),
+            2.0
+        );
+        assert!(get_physics_info(entities_and_components, other_world).is_some());
+    }
+
+    #[test]
+    fn the_cache_finds_the_same_worlds_as_looking_them_up() {
+        let mut world = physics_test_world();
+        let entities_and_components = &mut world.entities_and_components;
+        let other_world = PhysicsWorldId(1);
+        let third_world = PhysicsWorldId(2);
+
+        let root = entities_and_components.add_entity_with((Transform::default(), other_world));
+        let child = entities_and_components.add_entity_with((Transform::default(),));
+        entities_and_components.set_parent(child, root);
+        let grandchild = entities_and_components.add_entity_with((Transform::default(),));
+        entities_and_components.set_parent(grandchild, child);
+        let moved_child =
+            entities_and_components.add_entity_with((Transform::default(), third_world));
+        entities_and_components.set_parent(moved_child, child);
+        let moved_grandchild = entities_and_components.add_entity_with((Transform::default(),));
+        entities_and_components.set_parent(moved_grandchild, moved_child);
+        let outside = entities_and_components.add_entity_with((Transform::default(),));
+
+        // the grandchild goes first, so the child is already cached when it is looked up
+        let mut cache = PhysicsWorldIdCache::new();
+        for (entity, world_id) in [
+            (grandchild, other_world),
+            (child, other_world),
+            (root, other_world),
+            (moved_grandchild, third_world),
+            (moved_child, third_world),
+            (outside, PhysicsWorldId::DEFAULT),
+        ] {
+            assert_eq!(cache.of(entity, entities_and_components), world_id);
+            assert_eq!(
+                PhysicsWorldId::of(entity, entities_and_components),
+                world_id
+            );
+        }
+    }
+}
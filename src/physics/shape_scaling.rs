@@ -0,0 +1,119 @@
+use rapier2d::parry::shape::{RoundShape, TypedShape};
+use rapier2d::prelude::*;
+use tracing::event;
+use tracing::Level;
+
+/// the smallest scale a shape is scaled by, rapier breaks on shapes with a size of 0
+const MIN_SCALE: Real = 1.0e-4;
+
+/// a mirrored transform has a negative scale, but the shape is the same size either way
+pub(crate) fn sanitize_scale(scale: Real) -> Real {
+    if scale.is_nan() {
+        return 1.0;
+    }
+    scale.abs().max(MIN_SCALE)
+}
+
+/// scales a shape uniformly, used to apply Transform::scale to colliders
+/// shapes that can't be scaled (half spaces and custom shapes) are returned as is
+pub(crate) fn scale_shape(shape: &SharedShape, scale: Real) -> SharedShape {
+    let scale = sanitize_scale(scale);
+    let scale_vector = vector![scale, scale];
+
+    match shape.as_typed_shape() {
+        TypedShape::Ball(ball) => SharedShape::ball(ball.radius * scale),
+        TypedShape::Cuboid(cuboid) => SharedShape::new(cuboid.scaled(&scale_vector)),
+        TypedShape::Capsule(capsule) => SharedShape::capsule(
+            capsule.segment.a * scale,
+            capsule.segment.b * scale,
+            capsule.radius * scale,
+        ),
+        TypedShape::Segment(segment) => SharedShape::new(segment.scaled(&scale_vector)),
+        TypedShape::Triangle(triangle) => SharedShape::new(triangle.scaled(&scale_vector)),
+        TypedShape::TriMesh(trimesh) => SharedShape::new(trimesh.clone().scaled(&scale_vector)),
+        TypedShape::Polyline(polyline) => SharedShape::new(polyline.clone().scaled(&scale_vector)),
+        TypedShape::HeightField(heightfield) => {
+            SharedShape::new(heightfield.clone().scaled(&scale_vector))
+        }
+        TypedShape::Compound(compound) => {
+            let shapes = compound
+                .shapes()
+                .iter()
+                .map(|(position, shape)| {
+                    let mut position = *position;
+                    position.translation.vector *= scale;
+                    (position, scale_shape(shape, scale))
+                })
+                .collect();
+
+            SharedShape::compound(shapes)
+        }
+        TypedShape::ConvexPolygon(polygon) => match polygon.clone().scaled(&scale_vector) {
+            Some(polygon) => SharedShape::new(polygon),
+            None => shape.clone(),
+        },
+        TypedShape::RoundCuboid(round_cuboid) => SharedShape::new(RoundShape {
+            inner_shape: round_cuboid.inner_shape.scaled(&scale_vector),
+            border_radius: round_cuboid.border_radius * scale,
+        }),
+        TypedShape::RoundTriangle(round_triangle) => SharedShape::new(RoundShape {
+            inner_shape: round_triangle.inner_shape.scaled(&scale_vector),
+            border_radius: round_triangle.border_radius * scale,
+        }),
+        TypedShape::RoundConvexPolygon(round_polygon) => {
+            match round_polygon.inner_shape.clone().scaled(&scale_vector) {
+                Some(polygon) => SharedShape::new(RoundShape {
+                    inner_shape: polygon,
+                    border_radius: round_polygon.border_radius * scale,
+                }),
+                None => shape.clone(),
+            }
+        }
+        TypedShape::HalfSpace(_) => shape.clone(),
+        TypedShape::Custom(_) => {
+            event!(
+                Level::WARN,
+                "custom collider shapes can not be scaled, the transform scale will be ignored"
+            );
+            shape.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_ball() {
+        let shape = scale_shape(&SharedShape::ball(2.0), 3.0);
+
+        assert_eq!(shape.as_ball().unwrap().radius, 6.0);
+    }
+
+    #[test]
+    fn scale_compound() {
+        let compound = SharedShape::compound(vec![(
+            Isometry::translation(1.0, 0.0),
+            SharedShape::cuboid(1.0, 2.0),
+        )]);
+
+        let shape = scale_shape(&compound, 2.0);
+        let (position, cuboid) = &shape.as_compound().unwrap().shapes()[0];
+
+        assert_eq!(position.translation.vector, vector![2.0, 0.0]);
+        assert_eq!(cuboid.as_cuboid().unwrap().half_extents, vector![2.0, 4.0]);
+    }
+
+    #[test]
+    fn negative_and_zero_scales_keep_shapes_valid() {
+        let mirrored = scale_shape(&SharedShape::cuboid(1.0, 2.0), -2.0);
+        assert_eq!(
+            mirrored.as_cuboid().unwrap().half_extents,
+            vector![2.0, 4.0]
+        );
+
+        let flattened = scale_shape(&SharedShape::ball(2.0), 0.0);
+        assert!(flattened.as_ball().unwrap().radius > 0.0);
+    }
+}
@@ -2,7 +2,22 @@ pub use crate::get_transform;
 pub use crate::input::*;
 pub use crate::physics;
 pub use crate::physics::add_default_physics_systems;
+pub use crate::physics::area_effectors::{AreaEffect, AreaEffector};
+pub use crate::physics::collider_generation::{
+    add_alpha_mask_colliders, add_tile_grid_colliders, AlphaMask, MaskColliderShape,
+    TileColliderShape, TileGrid,
+};
+pub use crate::physics::explosions::{explode, Explosion, ExplosionHit};
+pub use crate::physics::physics_debugging::PhysicsContact;
+pub use crate::physics::physics_hooks::{OneWayPlatform, PhysicsHook};
+pub use crate::physics::physics_queries::{EntityFilter, EntityQuery};
+pub use crate::physics::physics_settings::PhysicsSettings;
+pub use crate::physics::physics_snapshot::{restore_physics_snapshot, PhysicsSnapshot};
 pub use crate::physics::physics_system::RapierPhysicsInfo;
+pub use crate::physics::physics_worlds::{
+    add_physics_world, get_physics_info, get_physics_info_mut, remove_physics_world,
+    PhysicsWorldId, PhysicsWorlds,
+};
 pub use crate::physics::rapier2d::prelude::{
     Collider, ColliderBuilder, ColliderHandle, QueryFilter, RigidBody, RigidBodyBuilder,
     RigidBodyHandle,
@@ -27,16 +27,24 @@ pub(crate) fn add_default_resources(scene: &mut Scene) {
         .world
         .entities_and_components
         .add_resource(AudioHandle::new());
+
+    scene
+        .world
+        .entities_and_components
+        .add_resource(MusicPlayer::new());
 }
 
 pub fn add_all_systems(world: &mut World) {
     crate::physics::add_default_physics_systems(world);
     crate::ui::add_all_ui_systems(world);
+    audio_stream::add_audio_systems(world);
 }
 
+/// removes every system added by the user, the physics worlds and their settings are kept
 pub fn remove_all_non_internal_systems(scene: &mut World) {
     scene.remove_all_systems();
     crate::physics::add_default_physics_systems(scene);
     scene.add_system(crate::resources::InputUpdateSystem::new());
     crate::ui::add_all_ui_systems(scene);
+    audio_stream::add_audio_systems(scene);
 }
@@ -1,12 +1,46 @@
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use rodio::cpal::FromSample;
+use rodio::dynamic_mixer::DynamicMixerController;
 use rodio::Sample;
-use rodio::{source::Source, Decoder, OutputStream};
+use rodio::{source::Source, Decoder};
 use std::fs::File;
 use std::io::BufReader;
-use ABC_ECS::Resource;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::event;
+use tracing::Level;
+use ABC_ECS::{EntitiesAndComponents, Resource, System, World};
+
+use super::delta_time::DeltaTime;
+
+mod audio_backend;
+pub use audio_backend::*;
+mod audio_clip;
+pub use audio_clip::*;
+mod sound_handle;
+pub use sound_handle::SoundHandle;
+mod audio_effects;
+pub use audio_effects::*;
+mod audio_bus;
+pub use audio_bus::AudioBus;
+use audio_bus::{BusControls, BusNode};
+mod ducking;
+pub use ducking::DuckingRule;
+mod fades;
+use fades::{Fade, FadeCurve, FadeTarget, Fades};
+mod music_player;
+pub use music_player::{MusicPlayer, RepeatMode};
+mod voice_limits;
+use voice_limits::VoiceLimiter;
+pub use voice_limits::{VoiceLimit, VoiceStealing};
+mod sound_synth;
+pub use sound_synth::{SynthPreset, SynthSound, SynthSource, Waveform};
+mod positional_audio;
+pub use positional_audio::{AudioEmitter, AudioListener, AudioRolloff};
 
 /// A struct that holds an audio file
+/// the file is decoded while it plays and can only be played once, use AudioClip for sounds that are played often
 pub struct AudioFile {
     pub(crate) file: Decoder<BufReader<File>>,
 }
@@ -21,98 +55,10 @@ impl AudioFile {
     }
 }
 
-#[derive(Clone)]
-pub struct AudioBus {
-    parent: Option<String>,
-    name: String,
-    volume: f32,
-    speed: f32,
-}
-
-impl AudioBus {
-    pub fn new(name: &str) -> Self {
-        Self {
-            parent: None,
-            name: name.to_string(),
-            volume: 1.0,
-            speed: 1.0,
-        }
-    }
-
-    pub fn set_volume(&mut self, volume: f32) {
-        self.volume = volume;
-    }
-
-    pub fn get_volume(&self, audio_handle: &AudioHandle) -> f32 {
-        match self.parent {
-            Some(ref parent) => {
-                let parent = audio_handle
-                    .buses
-                    .get(parent)
-                    .expect(format!("Parent: {} not found for {}", parent, self.name).as_str());
-
-                self.volume * parent.get_volume(audio_handle)
-            }
-            None => self.volume,
-        }
-    }
-
-    pub fn get_volume_without_parent(&self) -> f32 {
-        self.volume
-    }
-
-    /// 1.0 is normal speed
-    pub fn set_speed(&mut self, speed: f32) {
-        self.speed = speed;
-    }
-
-    /// 1.0 is normal speed
-    pub fn get_speed(&self, audio_handle: &AudioHandle) -> f32 {
-        match self.parent {
-            Some(ref parent) => {
-                let parent = audio_handle
-                    .buses
-                    .get(parent)
-                    .expect(format!("Parent: {} not found for {}", parent, self.name).as_str());
-
-                self.speed * parent.get_speed(audio_handle)
-            }
-            None => self.speed,
-        }
-    }
-
-    pub fn get_name(&self) -> &str {
-        &self.name
-    }
-
-    pub fn set_name(&mut self, name: &str) {
-        self.name = name.to_string();
-    }
-
-    pub fn set_parent(&mut self, parent: &str) {
-        self.parent = Some(parent.to_string());
-    }
-
-    pub fn get_parent(&self) -> Option<&String> {
-        self.parent.as_ref()
-    }
-
-    pub fn get_full_name(&self, handle: &AudioHandle) -> String {
-        let mut full_name = self.name.clone();
-        if let Some(parent) = &self.parent {
-            let parent = handle.buses.get(parent).expect("Parent not found");
-            *&mut full_name += &parent.get_full_name(handle);
-        }
-
-        full_name.to_string()
-    }
-}
-
 /// A struct that can play multiple audio files at the same time
 struct ParellelSink {
     sinks: Vec<rodio::Sink>,
     volume: f32,
-    speed: f32,
 }
 
 impl ParellelSink {
@@ -120,24 +66,23 @@ impl ParellelSink {
         Self {
             sinks: Vec::new(),
             volume: 1.0,
-            speed: 1.0,
         }
     }
 
-    fn append<S>(&mut self, source: S, handle: &rodio::OutputStreamHandle)
+    fn append<S>(&mut self, source: S, mixer: &DynamicMixerController<f32>)
     where
         S: Source + Send + 'static,
         f32: FromSample<S::Item>,
         S::Item: Sample + Send,
     {
         // either find an empty sink or create a new one
-        self.append_without_cleanup(source, handle);
+        self.append_without_cleanup(source, mixer);
 
         // check if any of the sinks are empty and if they are remove them
         self.clean_up();
     }
 
-    fn append_without_cleanup<S>(&mut self, source: S, handle: &rodio::OutputStreamHandle)
+    fn append_without_cleanup<S>(&mut self, source: S, mixer: &DynamicMixerController<f32>)
     where
         S: Source + Send + 'static,
         f32: FromSample<S::Item>,
@@ -151,11 +96,11 @@ impl ParellelSink {
             }
         }
 
-        // add a new sink if all are being used
+        // add a new sink if all are being used, the voice limits of the AudioHandle keep the number of sinks down
 
-        let sink = rodio::Sink::try_new(handle).expect("Failed to create audio sink");
+        let (sink, sink_output) = rodio::Sink::new_idle();
+        mixer.add(sink_output);
         sink.set_volume(self.volume);
-        sink.set_speed(self.speed);
         sink.append(source);
         self.sinks.push(sink);
     }
@@ -164,172 +109,520 @@ impl ParellelSink {
         self.sinks.retain(|sink| !sink.empty());
     }
 
+    /// only used to silence the sounds when another bus is soloed, the volume of the bus is applied after mixing
     fn set_volume(&mut self, volume: f32) {
         for sink in &mut self.sinks {
             sink.set_volume(volume);
         }
         self.volume = volume;
     }
+}
 
-    /// 1.0 is normal speed
-    fn set_speed(&mut self, speed: f32) {
-        for sink in &mut self.sinks {
-            sink.set_speed(speed);
-        }
-        self.speed = speed;
-    }
-
-    fn pause(&mut self) {
-        for sink in &mut self.sinks {
-            sink.pause();
-        }
-    }
+/// plays the sounds one after the other as a single source
+fn sequence_of<P: Playable>(sounds: Vec<P>) -> PlayableSource {
+    let sources = sounds
+        .into_iter()
+        .map(Playable::into_source)
+        .collect::<Vec<PlayableSource>>();
 
-    fn play(&mut self) {
-        for sink in &mut self.sinks {
-            sink.play();
-        }
-    }
+    Box::new(UnseekableSource(rodio::source::from_iter(sources)))
 }
 
 /// The resource that is used to play audio files
 pub struct AudioHandle {
-    pub(crate) handle: rodio::OutputStreamHandle,
-    // this is needed to keep the stream alive
-    _stream: OutputStream,
-    master_volume: f32,
-    master_speed: f32,
-    sink: ParellelSink,
-    sinks: FxHashMap<String, ParellelSink>, // this way we can have multiple sinks with different volumes
+    backend: Box<dyn AudioBackend>,
+    // every bus without a parent is mixed into this, sounds played without a bus are played on it directly
+    master: BusNode,
     buses: FxHashMap<String, AudioBus>,
+    // the live part of each bus, made the first time something is played on the bus or a bus inside it
+    bus_nodes: FxHashMap<String, BusNode>,
+    soloed_buses: FxHashSet<String>,
+    fades: Fades,
+    voices: VoiceLimiter,
 }
 
 // every master volume is multiplied by 0.1 because the volume is WAY too loud
 impl AudioHandle {
     /// creates a new audio handle, which is used to play audio files
+    /// if there is no output device the audio is thrown away instead
     pub(crate) fn new() -> Self {
-        let (_stream, handle) = rodio::OutputStream::try_default().expect("Failed to open stream");
+        match DeviceAudioBackend::new() {
+            Ok(backend) => Self::new_with_backend(backend),
+            Err(error) => {
+                event!(
+                    Level::WARN,
+                    "failed to open an audio output device, audio will not be heard: {}",
+                    error
+                );
+                Self::new_with_backend(NullAudioBackend::new())
+            }
+        }
+    }
+
+    /// creates a new audio handle that sends its output to the given backend
+    /// replace the AudioHandle resource with this to render audio offline in tests
+    pub fn new_with_backend(mut backend: impl AudioBackend + 'static) -> Self {
+        let (mixer, mixer_output) =
+            rodio::dynamic_mixer::mixer::<f32>(MIXER_CHANNELS, MIXER_SAMPLE_RATE);
+        // the mixer ends when it has nothing to play, this keeps it going forever
+        mixer.add(rodio::source::Zero::<f32>::new(
+            MIXER_CHANNELS,
+            MIXER_SAMPLE_RATE,
+        ));
+        backend.start(Box::new(mixer_output));
+
+        let master_controls = BusControls::new();
+        master_controls.volume.set(0.1);
+        let master = BusNode::new(Arc::new(master_controls), None, &mixer);
+
         Self {
-            handle: handle.clone(),
-            _stream,
-            master_volume: 0.1,
-            master_speed: 1.0,
-            sink: ParellelSink::new(),
-            sinks: FxHashMap::default(),
+            backend: Box::new(backend),
+            master,
             buses: FxHashMap::default(),
+            bus_nodes: FxHashMap::default(),
+            soloed_buses: FxHashSet::default(),
+            fades: Fades::default(),
+            voices: VoiceLimiter::default(),
         }
     }
 
-    /// plays the audio file once
-    pub fn play_one_shot(&mut self, audio_file: AudioFile) {
-        self.sink.set_volume(self.master_volume);
-        self.sink.set_speed(self.master_speed);
-        self.sink
-            .append(audio_file.file.convert_samples::<f32>(), &self.handle);
+    /// plays the sound once
+    pub fn play_one_shot(&mut self, sound: impl Playable) -> SoundHandle {
+        let clip = sound.get_clip();
+        self.play_limited(sound.into_source(), clip, None)
     }
 
-    /// plays the audio files in sequence, waiting for each one to finish before playing the next
-    /// sleeps until the end of the last audio file
-    pub fn play_sounds_in_sequence(&mut self, audio_files: Vec<AudioFile>) {
-        for audio_file in audio_files {
-            self.sink
-                .append(audio_file.file.convert_samples::<f32>(), &self.handle);
-        }
+    /// plays the sounds in sequence, waiting for each one to finish before playing the next
+    /// the returned handle controls the whole sequence
+    pub fn play_sounds_in_sequence<P: Playable>(&mut self, sounds: Vec<P>) -> SoundHandle {
+        self.play_limited(sequence_of(sounds), None, None)
     }
 
-    /// plays the audio file over and over again until the program is terminated
-    pub fn play_infinitely(&mut self, audio_file: AudioFile) {
-        self.sink.append(
-            audio_file.file.convert_samples::<f32>().repeat_infinite(),
-            &self.handle,
-        );
+    /// plays the sound over and over again until it is stopped
+    pub fn play_infinitely(&mut self, sound: impl Playable) -> SoundHandle {
+        let clip = sound.get_clip();
+        self.play_limited(sound.into_looping_source(), clip, None)
     }
 
     pub fn set_master_volume(&mut self, volume: f32) {
-        self.master_volume = volume * 0.1;
-        self.sink.set_volume(volume);
-
-        for sink in self.sinks.values_mut() {
-            sink.set_volume(volume);
-        }
+        self.master.controls.volume.set(volume * 0.1);
     }
 
     pub fn get_master_volume(&self) -> f32 {
-        self.master_volume / 0.1
+        self.master.controls.volume.get() / 0.1
+    }
+
+    /// the effects on everything that is played
+    pub fn get_master_effects(&self) -> &EffectChain {
+        &self.master.controls.effects
     }
 
     /// 1.0 is normal speed
     pub fn set_master_speed(&mut self, speed: f32) {
-        self.master_speed = speed;
-        self.sink.set_speed(speed);
-
-        for sink in self.sinks.values_mut() {
-            sink.set_speed(speed);
-        }
+        self.master.controls.speed.set(speed);
     }
 
     /// 1.0 is normal speed
     pub fn get_master_speed(&self) -> f32 {
-        self.master_speed
+        self.master.controls.speed.get()
+    }
+
+    /// Fades the volume of the sound to the given volume over the duration.
+    /// fades are moved forward by the scaled DeltaTime, so they stop while the game is paused with a time scale of 0
+    pub fn fade_sound(&mut self, sound: &SoundHandle, volume: f32, duration: Duration) {
+        self.fades.add(Fade::new(
+            FadeTarget::Sound(sound.clone()),
+            volume,
+            duration,
+            FadeCurve::Linear,
+        ));
+    }
+
+    /// fades the sound to silence and then stops it
+    pub fn fade_out_sound(&mut self, sound: &SoundHandle, duration: Duration) {
+        self.fades.add(
+            Fade::new(
+                FadeTarget::Sound(sound.clone()),
+                0.0,
+                duration,
+                FadeCurve::Linear,
+            )
+            .with_stop_at_end(),
+        );
     }
 
-    pub fn play_sound_on_bus(&mut self, audio_file: AudioFile, name: &str) {
-        let (volume, speed) = self.get_volume_and_speed_of(name);
+    /// fades the volume of the bus, every sound on it and the buses inside it follow
+    pub fn fade_bus(&mut self, name: &str, volume: f32, duration: Duration) {
+        let controls = self
+            .buses
+            .get(name)
+            .expect("Bus not found, call add_bus first")
+            .controls
+            .clone();
+
+        self.fades.add(Fade::new(
+            FadeTarget::Bus(controls),
+            volume,
+            duration,
+            FadeCurve::Linear,
+        ));
+    }
+
+    pub fn fade_master_volume(&mut self, volume: f32, duration: Duration) {
+        self.fades.add(Fade::new(
+            FadeTarget::Bus(self.master.controls.clone()),
+            volume * 0.1,
+            duration,
+            FadeCurve::Linear,
+        ));
+    }
+
+    /// Fades out the first sound and stops it, while the second sound fades in from silence to its current volume.
+    /// the loudness stays about the same during the crossfade
+    pub fn crossfade(&mut self, from: &SoundHandle, to: &SoundHandle, duration: Duration) {
+        let volume = to.get_volume();
+        to.set_volume(0.0);
+        self.crossfade_to_volume(from, to, volume, duration);
+    }
+
+    pub(crate) fn crossfade_to_volume(
+        &mut self,
+        from: &SoundHandle,
+        to: &SoundHandle,
+        volume: f32,
+        duration: Duration,
+    ) {
+        self.fades.add(
+            Fade::new(
+                FadeTarget::Sound(from.clone()),
+                0.0,
+                duration,
+                FadeCurve::EqualPower,
+            )
+            .with_stop_at_end(),
+        );
+        self.fades.add(Fade::new(
+            FadeTarget::Sound(to.clone()),
+            volume,
+            duration,
+            FadeCurve::EqualPower,
+        ));
+    }
+
+    /// moves every fade forward by the given time in seconds, this is done by the audio system every frame
+    pub fn update_fades(&mut self, delta_time: f64) {
+        self.fades.update(delta_time);
+    }
+
+    pub fn play_sound_on_bus(&mut self, sound: impl Playable, name: &str) -> SoundHandle {
+        let clip = sound.get_clip();
+        self.play_limited(sound.into_source(), clip, Some(name))
+    }
+
+    pub fn play_sounds_in_sequence_on_bus<P: Playable>(
+        &mut self,
+        sounds: Vec<P>,
+        name: &str,
+    ) -> SoundHandle {
+        self.play_limited(sequence_of(sounds), None, Some(name))
+    }
+
+    pub fn play_infinitely_on_bus(&mut self, sound: impl Playable, name: &str) -> SoundHandle {
+        let clip = sound.get_clip();
+        self.play_limited(sound.into_looping_source(), clip, Some(name))
+    }
+
+    /// plays the source if the voice limits allow it, otherwise the returned handle is already stopped
+    fn play_limited(
+        &mut self,
+        source: PlayableSource,
+        clip: Option<AudioClip>,
+        bus: Option<&str>,
+    ) -> SoundHandle {
+        let bus_limit = bus.and_then(|name| {
+            self.buses
+                .get(name)
+                .expect("Bus not found, call add_bus first")
+                .voice_limit
+        });
+        if !self.voices.make_room(clip.as_ref(), bus, bus_limit) {
+            return SoundHandle::new_stopped();
+        }
 
-        // this should be a function but rust doesn't like it :(
-        // because, it would not allow the handle to be borrowed
-        let sink = self
-            .sinks
-            .entry(name.to_string())
-            .or_insert_with(|| ParellelSink::new());
+        let sound = self.play_source(source, bus, 1.0);
+        self.voices.add(sound.clone(), clip.as_ref(), bus);
+        sound
+    }
 
-        sink.set_volume(volume * self.master_volume);
-        sink.set_speed(speed * self.master_speed);
-        sink.append(audio_file.file.convert_samples::<f32>(), &self.handle);
+    /// How many sounds can play at once in total, None for no limit.
+    /// the music of the MusicPlayer doesn't count towards it
+    pub fn set_voice_limit(&mut self, limit: Option<VoiceLimit>) {
+        self.voices.set_limit(limit);
     }
 
-    pub fn play_sounds_in_sequence_on_bus(&mut self, audio_files: Vec<AudioFile>, name: &str) {
-        let (volume, speed) = self.get_volume_and_speed_of(name);
+    pub fn get_voice_limit(&self) -> Option<VoiceLimit> {
+        self.voices.get_limit()
+    }
 
-        let sink = self
-            .sinks
-            .entry(name.to_string())
-            .or_insert_with(|| ParellelSink::new());
+    /// how many times the clip can play at once, None for no limit
+    pub fn set_clip_voice_limit(&mut self, clip: &AudioClip, limit: Option<VoiceLimit>) {
+        self.voices.set_clip_limit(clip, limit);
+    }
+
+    /// sounds with a higher priority are kept over sounds with a lower priority when a limit steals by priority,
+    /// clips have a priority of 0 by default
+    pub fn set_clip_priority(&mut self, clip: &AudioClip, priority: i32) {
+        self.voices.set_clip_priority(clip, priority);
+    }
+
+    /// the clip isn't played again until this much time has passed since it last played
+    pub fn set_clip_cooldown(&mut self, clip: &AudioClip, cooldown: Duration) {
+        self.voices.set_clip_cooldown(clip, cooldown);
+    }
 
-        sink.set_volume(volume * self.master_volume);
-        sink.set_speed(speed * self.master_speed);
-        for audio_file in audio_files {
-            sink.append(audio_file.file.convert_samples::<f32>(), &self.handle);
+    /// how many of the sounds played by the play methods are still playing
+    pub fn get_voice_count(&self) -> usize {
+        self.voices.get_voice_count()
+    }
+
+    /// moves the clock of the cooldowns forward by the given time in seconds, this is done by the audio system every frame
+    pub fn update_voices(&mut self, delta_time: f64) {
+        self.voices.update(delta_time);
+    }
+
+    /// plays the source on the bus, or without a bus if it is None
+    pub(crate) fn play_source(
+        &mut self,
+        source: PlayableSource,
+        bus: Option<&str>,
+        volume: f32,
+    ) -> SoundHandle {
+        match bus {
+            Some(name) => self.get_bus_node(name).append(source, volume),
+            None => self.master.append(source, volume),
         }
     }
 
-    fn get_volume_and_speed_of(&self, name: &str) -> (f32, f32) {
-        let bus = self
-            .buses
-            .get(name)
-            .expect("Bus not found, call add_bus first");
+    /// the live part of the bus, with the graph brought up to date with the buses first
+    fn get_bus_node(&mut self, name: &str) -> &mut BusNode {
+        assert!(
+            self.buses.contains_key(name),
+            "Bus not found, call add_bus first"
+        );
+        self.make_bus_node(name);
+        self.update_bus_graph();
 
-        (bus.get_volume(self), bus.get_speed(self))
+        self.bus_nodes
+            .get_mut(name)
+            .expect("the bus node was just made")
     }
 
-    pub fn play_infinitely_on_bus(&mut self, audio_file: AudioFile, name: &str) {
-        let (volume, speed) = self.get_volume_and_speed_of(name);
+    /// makes the live part of the bus and of all of its parents if they don't exist yet
+    fn make_bus_node(&mut self, name: &str) {
+        if self.bus_nodes.contains_key(name) {
+            return;
+        }
 
-        let sink = self
-            .sinks
-            .entry(name.to_string())
-            .or_insert_with(|| ParellelSink::new());
+        let bus = self.buses.get(name).expect("Bus not found");
+        let controls = bus.controls.clone();
+        let parent = bus.parent.clone();
+
+        let parent_mixer = match &parent {
+            Some(parent_name) => {
+                assert!(
+                    self.buses.contains_key(parent_name),
+                    "Parent: {} not found for {}",
+                    parent_name,
+                    name
+                );
+                self.make_bus_node(parent_name);
+                self.bus_nodes[parent_name].mixer.clone()
+            }
+            None => self.master.mixer.clone(),
+        };
 
-        sink.set_volume(volume * self.master_volume);
-        sink.set_speed(speed * self.master_speed);
-        sink.append(
-            audio_file.file.convert_samples::<f32>().repeat_infinite(),
-            &self.handle,
+        self.bus_nodes.insert(
+            name.to_string(),
+            BusNode::new(controls, parent, &parent_mixer),
         );
     }
 
+    /// moves bus nodes whose bus was given a new parent or replaced, and updates which buses are silenced by solo
+    fn update_bus_graph(&mut self) {
+        let outdated = self
+            .bus_nodes
+            .iter()
+            .filter(|(name, node)| match self.buses.get(*name) {
+                Some(bus) => {
+                    bus.parent != node.parent || !Arc::ptr_eq(&bus.controls, &node.controls)
+                }
+                None => true,
+            })
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<String>>();
+
+        for name in outdated {
+            let Some(bus) = self.buses.get(&name) else {
+                self.bus_nodes.remove(&name);
+                continue;
+            };
+            let controls = bus.controls.clone();
+            let parent = bus.parent.clone();
+
+            let parent_mixer = match &parent {
+                Some(parent_name) => {
+                    self.make_bus_node(parent_name);
+                    self.bus_nodes[parent_name].mixer.clone()
+                }
+                None => self.master.mixer.clone(),
+            };
+
+            let node = self.bus_nodes.get_mut(&name).expect("outdated nodes exist");
+            node.parent = parent;
+            node.controls = controls;
+            node.connect_to(&parent_mixer);
+        }
+
+        self.update_solo();
+    }
+
+    /// true if the bus or one of its parents is soloed
+    fn is_inside_soloed_bus(&self, name: &str) -> bool {
+        let mut current = Some(name);
+        while let Some(current_name) = current {
+            if self.soloed_buses.contains(current_name) {
+                return true;
+            }
+            current = self
+                .buses
+                .get(current_name)
+                .and_then(|bus| bus.parent.as_deref());
+        }
+
+        false
+    }
+
+    /// true if the bus is a parent of a soloed bus
+    fn contains_soloed_bus(&self, name: &str) -> bool {
+        self.soloed_buses.iter().any(|soloed| {
+            let mut current = self.buses.get(soloed).and_then(|bus| bus.parent.as_deref());
+            while let Some(current_name) = current {
+                if current_name == name {
+                    return true;
+                }
+                current = self
+                    .buses
+                    .get(current_name)
+                    .and_then(|bus| bus.parent.as_deref());
+            }
+
+            false
+        })
+    }
+
+    fn update_solo(&mut self) {
+        let solo_active = !self.soloed_buses.is_empty();
+
+        for (name, bus) in &self.buses {
+            let inside_soloed_bus = self.is_inside_soloed_bus(name);
+            // a bus with a soloed bus inside it has to let it through, but its own sounds are silenced
+            let audible = !solo_active || inside_soloed_bus || self.contains_soloed_bus(name);
+            bus.controls
+                .solo_silenced
+                .store(!audible, Ordering::Relaxed);
+
+            if let Some(node) = self.bus_nodes.get_mut(name) {
+                let own_sounds_audible = !solo_active || inside_soloed_bus;
+                node.sink
+                    .set_volume(if own_sounds_audible { 1.0 } else { 0.0 });
+            }
+        }
+
+        let master_sounds_audible = !solo_active;
+        self.master
+            .sink
+            .set_volume(if master_sounds_audible { 1.0 } else { 0.0 });
+    }
+
+    /// Only soloed buses, the buses inside them and the buses they are inside are heard while any bus is soloed.
+    /// sounds that are not on a bus are silenced too
+    pub fn set_bus_solo(&mut self, name: &str, solo: bool) {
+        assert!(
+            self.buses.contains_key(name),
+            "Bus not found, call add_bus first"
+        );
+
+        if solo {
+            self.soloed_buses.insert(name.to_string());
+        } else {
+            self.soloed_buses.remove(name);
+        }
+        self.update_bus_graph();
+    }
+
+    pub fn is_bus_soloed(&self, name: &str) -> bool {
+        self.soloed_buses.contains(name)
+    }
+
+    /// unsolos every bus
+    pub fn clear_solo(&mut self) {
+        self.soloed_buses.clear();
+        self.update_bus_graph();
+    }
+
+    /// Turns the target bus down while anything is playing on the source bus, like turning the music down while someone talks.
+    /// replaces the rule between the two buses if there already is one
+    pub fn add_ducking(&mut self, source_bus: &str, target_bus: &str, rule: DuckingRule) {
+        let source = self
+            .buses
+            .get(source_bus)
+            .expect("Source bus not found, call add_bus first");
+        let target = self
+            .buses
+            .get(target_bus)
+            .expect("Target bus not found, call add_bus first");
+
+        target.controls.ducking.add(&source.controls, rule);
+    }
+
+    /// the target bus goes back to its full volume, returns false if there was no rule between them
+    pub fn remove_ducking(&mut self, source_bus: &str, target_bus: &str) -> bool {
+        match (self.buses.get(source_bus), self.buses.get(target_bus)) {
+            (Some(source), Some(target)) => target.controls.ducking.remove(&source.controls),
+            _ => false,
+        }
+    }
+
+    /// moves the bus into another bus, the sounds playing on it keep playing
+    pub fn set_bus_parent(&mut self, name: &str, parent: &str) {
+        assert!(
+            self.buses.contains_key(parent),
+            "Parent: {} not found for {}",
+            parent,
+            name
+        );
+        self.buses
+            .get_mut(name)
+            .expect("Bus not found, call add_bus first")
+            .set_parent(parent);
+        self.update_bus_graph();
+    }
+
+    /// the backend the audio is sent to, None if it isn't a T
+    pub fn get_backend<T: AudioBackend + 'static>(&self) -> Option<&T> {
+        self.backend.as_any().downcast_ref::<T>()
+    }
+
+    /// the backend the audio is sent to, None if it isn't a T
+    pub fn get_backend_mut<T: AudioBackend + 'static>(&mut self) -> Option<&mut T> {
+        self.backend.as_any_mut().downcast_mut::<T>()
+    }
+
+    /// replaces the bus if there already is one with the same name
     pub fn add_bus(&mut self, bus: AudioBus) {
         self.buses.insert(bus.name.clone(), bus);
+        // the new bus has to be silenced if another bus is soloed
+        self.update_bus_graph();
     }
 
     pub fn get_bus(&self, bus: &str) -> Option<&AudioBus> {
@@ -342,39 +635,73 @@ impl AudioHandle {
 
     /// gets the bus with the given name or creates a new one if it doesn't exist
     pub fn get_or_make_bus(&mut self, bus: &str) -> &AudioBus {
-        self.buses
-            .entry(bus.to_string())
-            .or_insert_with(|| AudioBus::new(bus))
+        self.get_or_make_bus_mut(bus)
     }
 
     pub fn get_or_make_bus_mut(&mut self, bus: &str) -> &mut AudioBus {
-        self.buses
-            .entry(bus.to_string())
-            .or_insert_with(|| AudioBus::new(bus))
+        if !self.buses.contains_key(bus) {
+            self.add_bus(AudioBus::new(bus));
+        }
+
+        self.buses.get_mut(bus).expect("the bus was just added")
     }
 
     pub fn add_bus_to_bus(&mut self, mut new_bus: AudioBus, parent_bus: &str) {
         new_bus.parent = Some(parent_bus.to_string());
-        self.buses.insert(new_bus.name.clone(), new_bus.clone());
+        self.add_bus(new_bus);
+    }
+
+    /// Removes the bus and every bus inside it, all of the sounds playing on them are stopped.
+    /// returns false if there was no bus with the name
+    pub fn remove_bus(&mut self, name: &str) -> bool {
+        if !self.buses.contains_key(name) {
+            return false;
+        }
+
+        let removed = self
+            .buses
+            .keys()
+            .filter(|bus| {
+                let mut current = Some(bus.as_str());
+                while let Some(current_name) = current {
+                    if current_name == name {
+                        return true;
+                    }
+                    current = self
+                        .buses
+                        .get(current_name)
+                        .and_then(|bus| bus.parent.as_deref());
+                }
+                false
+            })
+            .cloned()
+            .collect::<Vec<String>>();
+
+        for bus in removed {
+            self.buses.remove(&bus);
+            // dropping the node stops everything on it
+            self.bus_nodes.remove(&bus);
+            self.soloed_buses.remove(&bus);
+        }
+        self.update_bus_graph();
+
+        true
     }
 
     pub fn drop_all_sounds(&mut self) {
-        self.sink.sinks.clear();
-        self.sinks.clear();
+        self.master.sink.sinks.clear();
+        for node in self.bus_nodes.values_mut() {
+            node.sink.sinks.clear();
+        }
     }
 
+    /// pauses every sound, they continue from the same place when play is called
     pub fn pause(&mut self) {
-        self.sink.pause();
-        for sink in self.sinks.values_mut() {
-            sink.pause();
-        }
+        self.master.controls.paused.store(true, Ordering::Relaxed);
     }
 
     pub fn play(&mut self) {
-        self.sink.play();
-        for sink in self.sinks.values_mut() {
-            sink.play();
-        }
+        self.master.controls.paused.store(false, Ordering::Relaxed);
     }
 }
 
@@ -386,3 +713,62 @@ impl Resource for AudioHandle {
         self
     }
 }
+
+/// Moves the fades of the AudioHandle forward, keeps the MusicPlayer going and updates the sounds on AudioEmitters.
+/// the time comes from DeltaTime, so everything stops while the time scale is 0
+pub(crate) struct AudioUpdateSystem;
+
+impl AudioUpdateSystem {
+    pub(crate) fn new() -> Self {
+        Self
+    }
+}
+
+impl System for AudioUpdateSystem {
+    fn run(&mut self, entities_and_components: &mut EntitiesAndComponents) {
+        let delta_time = entities_and_components
+            .get_resource::<DeltaTime>()
+            .expect("DeltaTime not found");
+        let (delta_time, unscaled_delta_time) = (
+            delta_time.get_delta_time(),
+            delta_time.get_unscaled_delta_time(),
+        );
+
+        positional_audio::update_positional_audio(entities_and_components);
+
+        if entities_and_components
+            .get_resource::<AudioHandle>()
+            .is_none()
+        {
+            return;
+        }
+
+        // the music player needs the audio handle and both are resources,
+        // so the player is taken out while it updates and put back after
+        let mut music_player = entities_and_components
+            .get_resource_mut::<MusicPlayer>()
+            .map(std::mem::take);
+
+        let audio_handle = entities_and_components
+            .get_resource_mut::<AudioHandle>()
+            .expect("the audio handle was just found");
+
+        if let Some(music_player) = &mut music_player {
+            music_player.update(audio_handle);
+        }
+        audio_handle.update_fades(delta_time);
+        audio_handle.update_voices(unscaled_delta_time);
+
+        if let Some(music_player) = music_player {
+            if let Some(resource) = entities_and_components.get_resource_mut::<MusicPlayer>() {
+                *resource = music_player;
+            }
+        }
+    }
+}
+
+pub(crate) fn add_audio_systems(world: &mut World) {
+    // remove the audio system to prevent duplicates
+    world.remove_all_systems_of_type::<AudioUpdateSystem>();
+    world.add_system(AudioUpdateSystem::new());
+}
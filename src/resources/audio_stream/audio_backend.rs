@@ -0,0 +1,245 @@
+use rodio::source::Source;
+use rodio::{OutputStream, OutputStreamHandle};
+use std::any::Any;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::event;
+use tracing::Level;
+
+/// the sample rate everything is mixed at before it is given to the backend
+pub const MIXER_SAMPLE_RATE: u32 = 44100;
+/// everything is mixed in stereo
+pub const MIXER_CHANNELS: u16 = 2;
+
+/// every sound played by an AudioHandle mixed together, it never ends and is silent when nothing is playing
+pub type MixedOutput = Box<dyn Source<Item = f32> + Send>;
+
+/// Where the mixed output of an AudioHandle goes, see AudioHandle::new_with_backend.
+/// the output is in MIXER_CHANNELS channels at MIXER_SAMPLE_RATE
+pub trait AudioBackend {
+    /// called once when the AudioHandle is made
+    fn start(&mut self, output: MixedOutput);
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// Plays the audio on the default output device, this is what AudioHandle uses if it can find one
+pub struct DeviceAudioBackend {
+    // this is needed to keep the stream alive
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+}
+
+impl DeviceAudioBackend {
+    /// fails if there is no output device, like on most CI machines
+    pub fn new() -> Result<Self, rodio::StreamError> {
+        let (_stream, handle) = OutputStream::try_default()?;
+        Ok(Self { _stream, handle })
+    }
+}
+
+impl AudioBackend for DeviceAudioBackend {
+    fn start(&mut self, output: MixedOutput) {
+        if let Err(error) = self.handle.play_raw(output) {
+            event!(
+                Level::ERROR,
+                "failed to play audio on the output device: {}",
+                error
+            );
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Throws the audio away at the speed it would have been played at, so sounds still end on time.
+/// used when there is no output device
+pub struct NullAudioBackend {
+    stopped: Arc<AtomicBool>,
+}
+
+impl NullAudioBackend {
+    pub fn new() -> Self {
+        Self {
+            stopped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+impl AudioBackend for NullAudioBackend {
+    fn start(&mut self, mut output: MixedOutput) {
+        let stopped = self.stopped.clone();
+        const CHUNK_TIME: Duration = Duration::from_millis(10);
+
+        std::thread::spawn(move || {
+            // paced against when it started instead of how long it slept, so time spent mixing
+            // and oversleeping is caught up on the next chunk instead of adding up
+            let start = Instant::now();
+            let mut rendered_frames = 0;
+            while !stopped.load(Ordering::Relaxed) {
+                let due_frames = (start.elapsed().as_secs_f64() * MIXER_SAMPLE_RATE as f64) as u64;
+                for _ in rendered_frames..due_frames {
+                    for _ in 0..MIXER_CHANNELS {
+                        output.next();
+                    }
+                }
+                rendered_frames = rendered_frames.max(due_frames);
+
+                let next_deadline = start
+                    + Duration::from_secs_f64(rendered_frames as f64 / MIXER_SAMPLE_RATE as f64)
+                    + CHUNK_TIME;
+                std::thread::sleep(next_deadline.saturating_duration_since(Instant::now()));
+            }
+        });
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Drop for NullAudioBackend {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Only moves forward when it is asked to render, so tests can check exactly what was played and when.
+/// get it back from the AudioHandle with get_backend_mut::<OfflineAudioBackend>()
+pub struct OfflineAudioBackend {
+    output: Option<MixedOutput>,
+    rendered_samples: usize,
+}
+
+impl OfflineAudioBackend {
+    pub fn new() -> Self {
+        Self {
+            output: None,
+            rendered_samples: 0,
+        }
+    }
+
+    /// mixes the given amount of time and returns the interleaved stereo samples
+    pub fn render(&mut self, duration: Duration) -> Vec<f32> {
+        let output = self
+            .output
+            .as_mut()
+            .expect("the offline audio backend has to be given to an AudioHandle before rendering");
+
+        let frames = (duration.as_secs_f64() * MIXER_SAMPLE_RATE as f64).round() as usize;
+        let samples = output
+            .by_ref()
+            .take(frames * MIXER_CHANNELS as usize)
+            .collect::<Vec<f32>>();
+
+        self.rendered_samples += samples.len();
+        samples
+    }
+
+    /// mixes the given amount of time and writes it to a 16 bit wav file
+    pub fn render_to_wav(
+        &mut self,
+        duration: Duration,
+        path: impl AsRef<Path>,
+    ) -> std::io::Result<()> {
+        let samples = self.render(duration);
+        let file = std::fs::File::create(path)?;
+        write_wav(std::io::BufWriter::new(file), &samples)
+    }
+
+    /// how much time has been rendered so far
+    pub fn get_rendered_time(&self) -> Duration {
+        Duration::from_secs_f64(
+            self.rendered_samples as f64 / MIXER_CHANNELS as f64 / MIXER_SAMPLE_RATE as f64,
+        )
+    }
+}
+
+impl AudioBackend for OfflineAudioBackend {
+    fn start(&mut self, output: MixedOutput) {
+        self.output = Some(output);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// writes interleaved stereo samples at the mixer sample rate as a 16 bit wav
+pub fn write_wav(mut writer: impl Write, samples: &[f32]) -> std::io::Result<()> {
+    const BYTES_PER_SAMPLE: u16 = 2;
+    let data_size = (samples.len() * BYTES_PER_SAMPLE as usize) as u32;
+    let block_align = MIXER_CHANNELS * BYTES_PER_SAMPLE;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_size).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    // 1 is uncompressed pcm
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&MIXER_CHANNELS.to_le_bytes())?;
+    writer.write_all(&MIXER_SAMPLE_RATE.to_le_bytes())?;
+    writer.write_all(&(MIXER_SAMPLE_RATE * block_align as u32).to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&(BYTES_PER_SAMPLE * 8).to_le_bytes())?;
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())?;
+    for sample in samples {
+        let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        writer.write_all(&sample.to_le_bytes())?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AudioHandle;
+
+    #[test]
+    fn offline_backend_renders_silence() {
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        let backend = audio_handle
+            .get_backend_mut::<OfflineAudioBackend>()
+            .unwrap();
+
+        let samples = backend.render(Duration::from_millis(100));
+        assert_eq!(samples.len(), 4410 * MIXER_CHANNELS as usize);
+        assert!(samples.iter().all(|sample| *sample == 0.0));
+        assert_eq!(backend.get_rendered_time(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn wav_has_correct_sizes() {
+        let mut bytes = vec![];
+        write_wav(&mut bytes, &[0.0, 0.5, -0.5, 1.0]).unwrap();
+
+        assert_eq!(bytes.len(), 44 + 4 * 2);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(bytes[4..8].try_into().unwrap()), 36 + 8);
+        assert_eq!(u32::from_le_bytes(bytes[40..44].try_into().unwrap()), 8);
+    }
+}
@@ -0,0 +1,462 @@
+use rodio::dynamic_mixer::{DynamicMixer, DynamicMixerController};
+use rodio::source::Source;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::ducking::DuckingState;
+use super::sound_handle::{AtomicF32, Voice};
+use super::{AudioHandle, ParellelSink, PlayableSource, SoundHandle};
+use super::{EffectChain, VoiceLimit};
+use super::{MIXER_CHANNELS, MIXER_SAMPLE_RATE};
+
+/// how many samples per channel a bus mixes at once, changes to the bus are heard from the next frame
+const BUS_FRAME_LEN: usize = 512;
+
+/// shared between an AudioBus and the output of its mixer on the audio thread
+pub(crate) struct BusControls {
+    pub(crate) volume: AtomicF32,
+    pub(crate) speed: AtomicF32,
+    pub(crate) muted: AtomicBool,
+    /// set by the AudioHandle when another bus is soloed
+    pub(crate) solo_silenced: AtomicBool,
+    pub(crate) paused: AtomicBool,
+    pub(crate) effects: EffectChain,
+    /// the loudest sample of the last frame the bus mixed, after its volume
+    pub(crate) level: AtomicF32,
+    /// the rules from other buses that turn this bus down while they are playing
+    pub(crate) ducking: DuckingState,
+}
+
+impl BusControls {
+    pub(crate) fn new() -> Self {
+        Self {
+            volume: AtomicF32::new(1.0),
+            speed: AtomicF32::new(1.0),
+            muted: AtomicBool::new(false),
+            solo_silenced: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            effects: EffectChain::default(),
+            level: AtomicF32::new(0.0),
+            ducking: DuckingState::new(),
+        }
+    }
+
+    fn get_gain(&self) -> f32 {
+        if self.muted.load(Ordering::Relaxed) || self.solo_silenced.load(Ordering::Relaxed) {
+            0.0
+        } else {
+            self.volume.get() * self.ducking.get_gain()
+        }
+    }
+}
+
+/// A group of sounds that share a volume and speed, buses can be put inside other buses.
+/// changes to a bus are heard right away by every sound playing on it and on the buses inside it,
+/// clones of a bus control the same bus
+#[derive(Clone)]
+pub struct AudioBus {
+    pub(crate) parent: Option<String>,
+    pub(crate) name: String,
+    pub(crate) controls: Arc<BusControls>,
+    pub(crate) voice_limit: Option<VoiceLimit>,
+}
+
+impl AudioBus {
+    pub fn new(name: &str) -> Self {
+        Self {
+            parent: None,
+            name: name.to_string(),
+            controls: Arc::new(BusControls::new()),
+            voice_limit: None,
+        }
+    }
+
+    /// how many sounds can play on the bus at once, sounds on the buses inside it have their own limits
+    pub fn with_voice_limit(mut self, limit: VoiceLimit) -> Self {
+        self.set_voice_limit(Some(limit));
+        self
+    }
+
+    pub fn set_voice_limit(&mut self, limit: Option<VoiceLimit>) {
+        self.voice_limit = limit;
+    }
+
+    pub fn get_voice_limit(&self) -> Option<VoiceLimit> {
+        self.voice_limit
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.controls.volume.set(volume);
+    }
+
+    /// the volume of the bus multiplied by the volume of all of its parents
+    pub fn get_volume(&self, audio_handle: &AudioHandle) -> f32 {
+        match self.parent {
+            Some(ref parent) => {
+                let parent = audio_handle
+                    .buses
+                    .get(parent)
+                    .expect(format!("Parent: {} not found for {}", parent, self.name).as_str());
+
+                self.get_volume_without_parent() * parent.get_volume(audio_handle)
+            }
+            None => self.get_volume_without_parent(),
+        }
+    }
+
+    pub fn get_volume_without_parent(&self) -> f32 {
+        self.controls.volume.get()
+    }
+
+    /// 1.0 is normal speed
+    pub fn set_speed(&mut self, speed: f32) {
+        self.controls.speed.set(speed);
+    }
+
+    /// 1.0 is normal speed, this is the speed of the bus multiplied by the speed of all of its parents
+    pub fn get_speed(&self, audio_handle: &AudioHandle) -> f32 {
+        match self.parent {
+            Some(ref parent) => {
+                let parent = audio_handle
+                    .buses
+                    .get(parent)
+                    .expect(format!("Parent: {} not found for {}", parent, self.name).as_str());
+
+                self.get_speed_without_parent() * parent.get_speed(audio_handle)
+            }
+            None => self.get_speed_without_parent(),
+        }
+    }
+
+    /// 1.0 is normal speed
+    pub fn get_speed_without_parent(&self) -> f32 {
+        self.controls.speed.get()
+    }
+
+    /// a muted bus and every bus inside it is silent, but the sounds keep playing
+    pub fn set_muted(&mut self, muted: bool) {
+        self.controls.muted.store(muted, Ordering::Relaxed);
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.controls.muted.load(Ordering::Relaxed)
+    }
+
+    /// the effects on the bus, they change every sound on it and on the buses inside it
+    pub fn get_effects(&self) -> &EffectChain {
+        &self.controls.effects
+    }
+
+    /// how loud the bus is right now, the loudest sample it played in the last few milliseconds
+    pub fn get_level(&self) -> f32 {
+        self.controls.level.get()
+    }
+
+    /// how much the bus is turned down by ducking right now, 1.0 when it isn't ducked
+    pub fn get_ducking_gain(&self) -> f32 {
+        self.controls.ducking.get_gain()
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    /// if the bus was already added to an AudioHandle this is applied the next time
+    /// something is played, use AudioHandle::set_bus_parent to apply it right away
+    pub fn set_parent(&mut self, parent: &str) {
+        self.parent = Some(parent.to_string());
+    }
+
+    pub fn get_parent(&self) -> Option<&String> {
+        self.parent.as_ref()
+    }
+
+    pub fn get_full_name(&self, handle: &AudioHandle) -> String {
+        let mut full_name = self.name.clone();
+        if let Some(parent) = &self.parent {
+            let parent = handle.buses.get(parent).expect("Parent not found");
+            *&mut full_name += &parent.get_full_name(handle);
+        }
+
+        full_name.to_string()
+    }
+}
+
+/// The live part of a bus, everything played on the bus and the outputs of the buses inside it are mixed here.
+/// the output of the mixer goes through the controls of the bus into the mixer of the parent
+pub(crate) struct BusNode {
+    pub(crate) parent: Option<String>,
+    pub(crate) controls: Arc<BusControls>,
+    pub(crate) mixer: Arc<DynamicMixerController<f32>>,
+    input: Arc<Mutex<DynamicMixer<f32>>>,
+    // set to end the output that is currently in the parent mixer
+    detached: Arc<AtomicBool>,
+    /// the sounds played directly on this bus
+    pub(crate) sink: ParellelSink,
+}
+
+impl BusNode {
+    pub(crate) fn new(
+        controls: Arc<BusControls>,
+        parent: Option<String>,
+        parent_mixer: &DynamicMixerController<f32>,
+    ) -> Self {
+        let (mixer, input) = rodio::dynamic_mixer::mixer::<f32>(MIXER_CHANNELS, MIXER_SAMPLE_RATE);
+        // the mixer ends when it has nothing to play, this keeps it going for as long as the bus exists
+        mixer.add(rodio::source::Zero::<f32>::new(
+            MIXER_CHANNELS,
+            MIXER_SAMPLE_RATE,
+        ));
+
+        let mut node = Self {
+            parent,
+            controls,
+            mixer,
+            input: Arc::new(Mutex::new(input)),
+            detached: Arc::new(AtomicBool::new(false)),
+            sink: ParellelSink::new(),
+        };
+        node.connect_to(parent_mixer);
+        node
+    }
+
+    /// moves the output of the bus into the given mixer, the sounds on the bus keep playing
+    pub(crate) fn connect_to(&mut self, parent_mixer: &DynamicMixerController<f32>) {
+        self.detached.store(true, Ordering::Relaxed);
+        self.detached = Arc::new(AtomicBool::new(false));
+
+        parent_mixer.add(BusOutput {
+            input: self.input.clone(),
+            controls: self.controls.clone(),
+            detached: self.detached.clone(),
+            buffer: vec![],
+            position: 0,
+            gain: self.controls.get_gain(),
+            speed: 1.0,
+        });
+    }
+
+    /// plays the source on the bus, starting at the given volume
+    pub(crate) fn append(&mut self, source: PlayableSource, volume: f32) -> SoundHandle {
+        let (voice, sound_handle) = Voice::new(source);
+        sound_handle.set_volume(volume);
+        self.sink.append(voice, &self.mixer);
+        sound_handle
+    }
+}
+
+impl Drop for BusNode {
+    fn drop(&mut self) {
+        // the output owns the mixer, so this stops every sound on the bus
+        self.detached.store(true, Ordering::Relaxed);
+    }
+}
+
+/// applies the controls of a bus to the mix of everything on it, a frame at a time
+struct BusOutput {
+    input: Arc<Mutex<DynamicMixer<f32>>>,
+    controls: Arc<BusControls>,
+    detached: Arc<AtomicBool>,
+    buffer: Vec<f32>,
+    position: usize,
+    // the gain at the end of the last frame, the next frame fades from it so volume changes don't click
+    gain: f32,
+    speed: f32,
+}
+
+impl BusOutput {
+    fn frame_len() -> usize {
+        BUS_FRAME_LEN * MIXER_CHANNELS as usize
+    }
+
+    fn mix_frame(&mut self) {
+        let frame_len = Self::frame_len();
+        self.buffer.clear();
+        self.position = 0;
+        self.speed = self.controls.speed.get().max(0.01);
+        self.controls.ducking.update(Duration::from_secs_f64(
+            BUS_FRAME_LEN as f64 / (MIXER_SAMPLE_RATE as f64 * self.speed as f64),
+        ));
+
+        // a paused bus doesn't pull from its mixer, so everything on it stops where it is
+        if !self.controls.paused.load(Ordering::Relaxed) {
+            let mut input = match self.input.lock() {
+                Ok(input) => input,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            self.buffer.extend(input.by_ref().take(frame_len));
+            drop(input);
+
+            self.buffer.resize(frame_len, 0.0);
+            self.controls
+                .effects
+                .process(&mut self.buffer, MIXER_CHANNELS, MIXER_SAMPLE_RATE);
+        }
+        self.buffer.resize(frame_len, 0.0);
+
+        let start_gain = self.gain;
+        let end_gain = self.controls.get_gain();
+        let mut level: f32 = 0.0;
+        for (index, sample) in self.buffer.iter_mut().enumerate() {
+            let progress = index as f32 / frame_len as f32;
+            *sample *= start_gain + (end_gain - start_gain) * progress;
+            level = level.max(sample.abs());
+        }
+        self.gain = end_gain;
+        self.controls.level.set(level);
+    }
+}
+
+impl Iterator for BusOutput {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.detached.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        if self.position >= self.buffer.len() {
+            self.mix_frame();
+        }
+
+        let sample = self.buffer[self.position];
+        self.position += 1;
+        Some(sample)
+    }
+}
+
+impl Source for BusOutput {
+    fn current_frame_len(&self) -> Option<usize> {
+        // rodio treats a frame length of 0 as the end of the source
+        let remaining = self.buffer.len().saturating_sub(self.position);
+        if remaining == 0 {
+            Some(Self::frame_len())
+        } else {
+            Some(remaining)
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        MIXER_CHANNELS
+    }
+
+    fn sample_rate(&self) -> u32 {
+        // the parent mixer resamples the output, so a higher sample rate plays everything on the bus faster
+        ((MIXER_SAMPLE_RATE as f32 * self.speed) as u32).max(1)
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AudioClip, OfflineAudioBackend};
+
+    fn render(audio_handle: &mut AudioHandle, milliseconds: u64) -> Vec<f32> {
+        audio_handle
+            .get_backend_mut::<OfflineAudioBackend>()
+            .unwrap()
+            .render(Duration::from_millis(milliseconds))
+    }
+
+    /// the last sample rendered, once every change has faded in
+    fn level_after(audio_handle: &mut AudioHandle, milliseconds: u64) -> f32 {
+        *render(audio_handle, milliseconds).last().unwrap()
+    }
+
+    fn constant_clip(value: f32) -> AudioClip {
+        AudioClip::from_samples(vec![value; 4410], 1, 44100)
+    }
+
+    #[test]
+    fn bus_changes_reach_sounds_that_are_playing() {
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        audio_handle.set_master_volume(10.0);
+        audio_handle.add_bus(AudioBus::new("music"));
+        audio_handle.add_bus_to_bus(AudioBus::new("combat"), "music");
+        audio_handle.play_infinitely_on_bus(&constant_clip(0.5), "combat");
+
+        assert!((level_after(&mut audio_handle, 50) - 0.5).abs() < 0.001);
+
+        audio_handle.get_bus_mut("combat").unwrap().set_volume(0.5);
+        assert!((level_after(&mut audio_handle, 50) - 0.25).abs() < 0.001);
+
+        audio_handle.get_bus_mut("music").unwrap().set_volume(0.5);
+        assert!((level_after(&mut audio_handle, 50) - 0.125).abs() < 0.001);
+
+        audio_handle.get_bus_mut("music").unwrap().set_muted(true);
+        assert_eq!(level_after(&mut audio_handle, 50), 0.0);
+    }
+
+    #[test]
+    fn solo_silences_the_other_buses() {
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        audio_handle.set_master_volume(10.0);
+        audio_handle.add_bus(AudioBus::new("music"));
+        audio_handle.add_bus_to_bus(AudioBus::new("voice"), "music");
+        audio_handle.add_bus(AudioBus::new("sfx"));
+        audio_handle.play_infinitely_on_bus(&constant_clip(0.5), "music");
+        audio_handle.play_infinitely_on_bus(&constant_clip(0.25), "voice");
+        audio_handle.play_infinitely_on_bus(&constant_clip(0.125), "sfx");
+
+        assert!((level_after(&mut audio_handle, 50) - 0.875).abs() < 0.001);
+
+        audio_handle.set_bus_solo("voice", true);
+        assert!((level_after(&mut audio_handle, 50) - 0.25).abs() < 0.001);
+
+        audio_handle.clear_solo();
+        assert!((level_after(&mut audio_handle, 50) - 0.875).abs() < 0.001);
+    }
+
+    #[test]
+    fn buses_added_while_another_is_soloed_are_silenced() {
+        let is_silenced = |audio_handle: &AudioHandle, name: &str| {
+            audio_handle
+                .get_bus(name)
+                .unwrap()
+                .controls
+                .solo_silenced
+                .load(Ordering::Relaxed)
+        };
+
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        audio_handle.add_bus(AudioBus::new("voice"));
+        audio_handle.set_bus_solo("voice", true);
+
+        audio_handle.add_bus(AudioBus::new("sfx"));
+        audio_handle.add_bus_to_bus(AudioBus::new("dialogue"), "voice");
+        audio_handle.get_or_make_bus("music");
+        assert!(is_silenced(&audio_handle, "sfx"));
+        assert!(is_silenced(&audio_handle, "music"));
+        assert!(!is_silenced(&audio_handle, "dialogue"));
+
+        // replacing the soloed bus keeps it soloed
+        audio_handle.add_bus(AudioBus::new("voice"));
+        assert!(!is_silenced(&audio_handle, "voice"));
+        assert!(is_silenced(&audio_handle, "sfx"));
+    }
+
+    #[test]
+    fn removing_a_bus_stops_its_sounds_and_the_buses_inside_it() {
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        audio_handle.add_bus(AudioBus::new("music"));
+        audio_handle.add_bus_to_bus(AudioBus::new("combat"), "music");
+        let sound = audio_handle.play_infinitely_on_bus(&constant_clip(0.5), "combat");
+        render(&mut audio_handle, 50);
+
+        assert!(audio_handle.remove_bus("music"));
+        render(&mut audio_handle, 50);
+
+        assert!(!sound.is_playing());
+        assert!(audio_handle.get_bus("combat").is_none());
+        assert!(!audio_handle.remove_bus("music"));
+    }
+}
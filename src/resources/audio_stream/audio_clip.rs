@@ -0,0 +1,321 @@
+use rodio::cpal::FromSample;
+use rodio::source::Source;
+use rodio::{Decoder, Sample};
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::AudioFile;
+
+/// A source that can be played by AudioHandle, seeking is optional
+pub trait SoundSource: Source<Item = f32> + Send {
+    /// jumps to the given time from the start, returns false if the source can't seek
+    fn seek(&mut self, _position: Duration) -> bool {
+        false
+    }
+}
+
+/// a source that can be given to any of the play methods on AudioHandle
+pub type PlayableSource = Box<dyn SoundSource>;
+
+impl Source for PlayableSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.as_ref().current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.as_ref().channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.as_ref().sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.as_ref().total_duration()
+    }
+}
+
+impl SoundSource for PlayableSource {
+    fn seek(&mut self, position: Duration) -> bool {
+        self.as_mut().seek(position)
+    }
+}
+
+/// wraps any rodio source so it can be played, it can't seek
+pub(crate) struct UnseekableSource<S>(pub(crate) S);
+
+impl<S: Source<Item = f32>> Iterator for UnseekableSource<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.0.next()
+    }
+}
+
+impl<S: Source<Item = f32>> Source for UnseekableSource<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.0.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.0.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.0.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.0.total_duration()
+    }
+}
+
+impl<S: Source<Item = f32> + Send> SoundSource for UnseekableSource<S> {}
+
+/// Anything that can be played by AudioHandle, like an AudioFile or an AudioClip
+pub trait Playable {
+    fn into_source(self) -> PlayableSource;
+
+    /// the source played by the play_infinitely methods, by default the source is remembered the first time it plays
+    fn into_looping_source(self) -> PlayableSource
+    where
+        Self: Sized,
+    {
+        Box::new(UnseekableSource(self.into_source().repeat_infinite()))
+    }
+
+    /// the clip that is played, so the voice limits, priority and cooldown set for the clip apply to it
+    fn get_clip(&self) -> Option<AudioClip> {
+        None
+    }
+}
+
+impl Playable for AudioFile {
+    fn into_source(self) -> PlayableSource {
+        Box::new(UnseekableSource(self.file.convert_samples::<f32>()))
+    }
+}
+
+/// Audio that is decoded into memory once and can then be played any number of times, even at the same time.
+/// cloning a clip is cheap, every clone shares the same samples
+#[derive(Clone)]
+pub struct AudioClip {
+    samples: Arc<[f32]>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl AudioClip {
+    /// loads and decodes the audio file at the given path
+    pub fn new(path: &str) -> Self {
+        let bytes = std::fs::read(path).expect("Failed to open file");
+        Self::from_bytes(bytes)
+    }
+
+    /// decodes audio from the bytes of a file, for example from include_bytes!
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Self {
+        let source = Decoder::new(Cursor::new(bytes.into())).expect("Failed to decode file");
+        Self::from_source(source)
+    }
+
+    /// plays the source to the end and keeps the result, the source must not be infinite
+    pub fn from_source<S>(source: S) -> Self
+    where
+        S: Source,
+        S::Item: Sample,
+        f32: FromSample<S::Item>,
+    {
+        let channels = source.channels();
+        let sample_rate = source.sample_rate();
+        let samples = source.convert_samples::<f32>().collect::<Vec<f32>>();
+
+        Self::from_samples(samples, channels, sample_rate)
+    }
+
+    /// a clip from interleaved samples
+    pub fn from_samples(samples: Vec<f32>, channels: u16, sample_rate: u32) -> Self {
+        assert!(channels > 0, "an audio clip must have at least one channel");
+        assert!(
+            sample_rate > 0,
+            "an audio clip must have a sample rate above 0"
+        );
+
+        Self {
+            samples: samples.into(),
+            channels,
+            sample_rate,
+        }
+    }
+
+    pub fn get_channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// the interleaved samples of the clip
+    pub fn get_samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    pub fn get_duration(&self) -> Duration {
+        Duration::from_secs_f64(
+            self.samples.len() as f64 / self.channels as f64 / self.sample_rate as f64,
+        )
+    }
+
+    /// true if both clips share the same samples, which is the case for clones
+    pub fn is_same_clip(&self, other: &AudioClip) -> bool {
+        Arc::ptr_eq(&self.samples, &other.samples)
+    }
+
+    /// the same for every clone of the clip, as long as one of them is alive
+    pub(crate) fn get_id(&self) -> usize {
+        Arc::as_ptr(&self.samples) as *const f32 as usize
+    }
+
+    /// a source that plays the clip once
+    pub fn to_source(&self) -> AudioClipSource {
+        AudioClipSource {
+            clip: self.clone(),
+            position: 0,
+            looping: false,
+        }
+    }
+}
+
+impl Playable for AudioClip {
+    fn into_source(self) -> PlayableSource {
+        Box::new(self.to_source())
+    }
+
+    fn into_looping_source(self) -> PlayableSource {
+        let mut source = self.to_source();
+        source.looping = true;
+        Box::new(source)
+    }
+
+    fn get_clip(&self) -> Option<AudioClip> {
+        Some(self.clone())
+    }
+}
+
+impl Playable for &AudioClip {
+    fn into_source(self) -> PlayableSource {
+        self.clone().into_source()
+    }
+
+    fn into_looping_source(self) -> PlayableSource {
+        self.clone().into_looping_source()
+    }
+
+    fn get_clip(&self) -> Option<AudioClip> {
+        Some((*self).clone())
+    }
+}
+
+impl SoundSource for AudioClipSource {
+    fn seek(&mut self, position: Duration) -> bool {
+        let frame = (position.as_secs_f64() * self.clip.sample_rate as f64) as usize;
+        let mut sample = frame * self.clip.channels as usize;
+        if self.looping && !self.clip.samples.is_empty() {
+            sample %= self.clip.samples.len();
+        }
+
+        self.position = sample.min(self.clip.samples.len());
+        true
+    }
+}
+
+/// plays the samples of an AudioClip without copying them
+pub struct AudioClipSource {
+    clip: AudioClip,
+    position: usize,
+    looping: bool,
+}
+
+impl Iterator for AudioClipSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.position >= self.clip.samples.len() {
+            if !self.looping || self.clip.samples.is_empty() {
+                return None;
+            }
+            self.position = 0;
+        }
+
+        let sample = self.clip.samples[self.position];
+        self.position += 1;
+        Some(sample)
+    }
+}
+
+impl Source for AudioClipSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        // the channels and sample rate never change, so the whole clip is one frame
+        if self.looping {
+            None
+        } else {
+            Some(self.clip.samples.len().saturating_sub(self.position))
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.clip.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.clip.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        if self.looping {
+            None
+        } else {
+            Some(self.clip.get_duration())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{write_wav, AudioHandle, OfflineAudioBackend};
+
+    #[test]
+    fn clip_from_wav_bytes_keeps_samples() {
+        let samples = vec![0.0, 0.25, -0.25, 0.5];
+        let mut bytes = vec![];
+        write_wav(&mut bytes, &samples).unwrap();
+
+        let clip = AudioClip::from_bytes(bytes);
+        assert_eq!(clip.get_channels(), 2);
+        assert_eq!(clip.get_sample_rate(), 44100);
+        for (decoded, original) in clip.get_samples().iter().zip(&samples) {
+            assert!((decoded - original).abs() < 0.001);
+        }
+    }
+
+    #[test]
+    fn clip_can_play_more_than_once() {
+        let clip = AudioClip::from_samples(vec![0.5; 4410], 1, 44100);
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        audio_handle.play_one_shot(&clip);
+        audio_handle.play_one_shot(&clip);
+
+        let backend = audio_handle
+            .get_backend_mut::<OfflineAudioBackend>()
+            .unwrap();
+        let playing = backend.render(Duration::from_millis(50));
+        let finished = backend.render(Duration::from_millis(200));
+
+        assert!(playing.iter().any(|sample| *sample != 0.0));
+        assert!(finished[finished.len() / 2..]
+            .iter()
+            .all(|sample| *sample == 0.0));
+    }
+}
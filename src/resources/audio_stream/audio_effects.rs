@@ -0,0 +1,735 @@
+use std::any::Any;
+use std::f32::consts::PI;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Changes the sound of everything on a bus, see AudioBus::get_effects.
+/// effects run on the audio thread, so process has to be fast and must not block
+pub trait AudioEffect: Send {
+    /// changes the interleaved samples in place
+    fn process(&mut self, samples: &mut [f32], channels: u16, sample_rate: u32);
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// The effects on a bus, they run in the order they were added before the volume of the bus is applied.
+/// changes are heard from the next frame the bus mixes
+#[derive(Default)]
+pub struct EffectChain {
+    effects: Mutex<Vec<Box<dyn AudioEffect>>>,
+}
+
+impl EffectChain {
+    fn effects(&self) -> std::sync::MutexGuard<'_, Vec<Box<dyn AudioEffect>>> {
+        match self.effects.lock() {
+            Ok(effects) => effects,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// adds the effect to the end of the chain
+    pub fn add(&self, effect: impl AudioEffect + 'static) {
+        self.effects().push(Box::new(effect));
+    }
+
+    /// removes the first effect of the given type, returns false if there wasn't one
+    pub fn remove<T: AudioEffect + 'static>(&self) -> bool {
+        let mut effects = self.effects();
+        match effects.iter().position(|effect| effect.as_any().is::<T>()) {
+            Some(index) => {
+                effects.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn has<T: AudioEffect + 'static>(&self) -> bool {
+        self.effects()
+            .iter()
+            .any(|effect| effect.as_any().is::<T>())
+    }
+
+    pub fn clear(&self) {
+        self.effects().clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.effects().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects().is_empty()
+    }
+
+    /// Changes the first effect of the given type while it is playing, returns None if there isn't one.
+    /// the audio thread waits for this, so keep it short
+    pub fn with_effect_mut<T: AudioEffect + 'static, R>(
+        &self,
+        change: impl FnOnce(&mut T) -> R,
+    ) -> Option<R> {
+        self.effects()
+            .iter_mut()
+            .find_map(|effect| effect.as_any_mut().downcast_mut::<T>())
+            .map(change)
+    }
+
+    pub(crate) fn process(&self, samples: &mut [f32], channels: u16, sample_rate: u32) {
+        for effect in self.effects().iter_mut() {
+            effect.process(samples, channels, sample_rate);
+        }
+    }
+}
+
+/// the state of one channel of a biquad filter
+#[derive(Clone, Copy, Default)]
+struct BiquadState {
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FilterKind {
+    LowPass,
+    HighPass,
+}
+
+/// the filters from the audio eq cookbook
+fn process_biquad(
+    kind: FilterKind,
+    cutoff: f32,
+    resonance: f32,
+    states: &mut Vec<BiquadState>,
+    samples: &mut [f32],
+    channels: u16,
+    sample_rate: u32,
+) {
+    let channels = channels.max(1) as usize;
+    states.resize(channels, BiquadState::default());
+
+    let cutoff = cutoff.clamp(10.0, sample_rate as f32 * 0.49);
+    let w0 = 2.0 * PI * cutoff / sample_rate as f32;
+    let alpha = w0.sin() / (2.0 * resonance.max(0.01));
+    let cos_w0 = w0.cos();
+
+    let (b0, b1, b2) = match kind {
+        FilterKind::LowPass => ((1.0 - cos_w0) / 2.0, 1.0 - cos_w0, (1.0 - cos_w0) / 2.0),
+        FilterKind::HighPass => ((1.0 + cos_w0) / 2.0, -(1.0 + cos_w0), (1.0 + cos_w0) / 2.0),
+    };
+    let a0 = 1.0 + alpha;
+    let a1 = -2.0 * cos_w0;
+    let a2 = 1.0 - alpha;
+
+    for (index, sample) in samples.iter_mut().enumerate() {
+        let state = &mut states[index % channels];
+        let x = *sample;
+        let y = (b0 * x + b1 * state.x1 + b2 * state.x2 - a1 * state.y1 - a2 * state.y2) / a0;
+
+        state.x2 = state.x1;
+        state.x1 = x;
+        state.y2 = state.y1;
+        state.y1 = y;
+        *sample = y;
+    }
+}
+
+/// Lets the low frequencies through and muffles the high ones, like hearing through a wall
+pub struct LowPassFilter {
+    cutoff: f32,
+    resonance: f32,
+    states: Vec<BiquadState>,
+}
+
+impl LowPassFilter {
+    /// the cutoff is in hz
+    pub fn new(cutoff: f32) -> Self {
+        Self {
+            cutoff,
+            resonance: std::f32::consts::FRAC_1_SQRT_2,
+            states: vec![],
+        }
+    }
+
+    /// how much the frequencies around the cutoff are boosted, about 0.7 is flat
+    pub fn with_resonance(mut self, resonance: f32) -> Self {
+        self.set_resonance(resonance);
+        self
+    }
+
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        self.cutoff = cutoff;
+    }
+
+    pub fn get_cutoff(&self) -> f32 {
+        self.cutoff
+    }
+
+    pub fn set_resonance(&mut self, resonance: f32) {
+        self.resonance = resonance;
+    }
+
+    pub fn get_resonance(&self) -> f32 {
+        self.resonance
+    }
+}
+
+impl AudioEffect for LowPassFilter {
+    fn process(&mut self, samples: &mut [f32], channels: u16, sample_rate: u32) {
+        process_biquad(
+            FilterKind::LowPass,
+            self.cutoff,
+            self.resonance,
+            &mut self.states,
+            samples,
+            channels,
+            sample_rate,
+        );
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Lets the high frequencies through and takes away the low ones, like a small radio
+pub struct HighPassFilter {
+    cutoff: f32,
+    resonance: f32,
+    states: Vec<BiquadState>,
+}
+
+impl HighPassFilter {
+    /// the cutoff is in hz
+    pub fn new(cutoff: f32) -> Self {
+        Self {
+            cutoff,
+            resonance: std::f32::consts::FRAC_1_SQRT_2,
+            states: vec![],
+        }
+    }
+
+    /// how much the frequencies around the cutoff are boosted, about 0.7 is flat
+    pub fn with_resonance(mut self, resonance: f32) -> Self {
+        self.set_resonance(resonance);
+        self
+    }
+
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        self.cutoff = cutoff;
+    }
+
+    pub fn get_cutoff(&self) -> f32 {
+        self.cutoff
+    }
+
+    pub fn set_resonance(&mut self, resonance: f32) {
+        self.resonance = resonance;
+    }
+
+    pub fn get_resonance(&self) -> f32 {
+        self.resonance
+    }
+}
+
+impl AudioEffect for HighPassFilter {
+    fn process(&mut self, samples: &mut [f32], channels: u16, sample_rate: u32) {
+        process_biquad(
+            FilterKind::HighPass,
+            self.cutoff,
+            self.resonance,
+            &mut self.states,
+            samples,
+            channels,
+            sample_rate,
+        );
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// a delay line that feeds back into itself through a low pass, part of the reverb
+struct CombFilter {
+    buffer: Vec<f32>,
+    position: usize,
+    filter_store: f32,
+}
+
+impl CombFilter {
+    fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0.0; length.max(1)],
+            position: 0,
+            filter_store: 0.0,
+        }
+    }
+
+    fn process(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let output = self.buffer[self.position];
+        self.filter_store = output * (1.0 - damping) + self.filter_store * damping;
+        self.buffer[self.position] = input + self.filter_store * feedback;
+        self.position = (self.position + 1) % self.buffer.len();
+        output
+    }
+}
+
+/// spreads the echoes of the comb filters out, part of the reverb
+struct AllPassFilter {
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl AllPassFilter {
+    fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0.0; length.max(1)],
+            position: 0,
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let buffered = self.buffer[self.position];
+        self.buffer[self.position] = input + buffered * 0.5;
+        self.position = (self.position + 1) % self.buffer.len();
+        buffered - input
+    }
+}
+
+struct ReverbChannel {
+    combs: Vec<CombFilter>,
+    all_passes: Vec<AllPassFilter>,
+}
+
+/// Makes everything sound like it is in a room, based on freeverb
+pub struct Reverb {
+    room_size: f32,
+    damping: f32,
+    mix: f32,
+    channels: Vec<ReverbChannel>,
+    sample_rate: u32,
+}
+
+impl Reverb {
+    // the lengths from freeverb at 44100 hz, they are scaled to the sample rate
+    const COMB_LENGTHS: [usize; 4] = [1116, 1188, 1277, 1356];
+    const ALL_PASS_LENGTHS: [usize; 2] = [556, 441];
+    // the right channel is a little longer so it doesn't sound the same as the left
+    const STEREO_SPREAD: usize = 23;
+
+    /// room size and damping go from 0.0 to 1.0
+    pub fn new(room_size: f32, damping: f32) -> Self {
+        Self {
+            room_size,
+            damping,
+            mix: 0.3,
+            channels: vec![],
+            sample_rate: 0,
+        }
+    }
+
+    /// how much of the reverb is heard, 0.0 is only the original sound and 1.0 is only the reverb
+    pub fn with_mix(mut self, mix: f32) -> Self {
+        self.set_mix(mix);
+        self
+    }
+
+    pub fn set_room_size(&mut self, room_size: f32) {
+        self.room_size = room_size;
+    }
+
+    pub fn get_room_size(&self) -> f32 {
+        self.room_size
+    }
+
+    pub fn set_damping(&mut self, damping: f32) {
+        self.damping = damping;
+    }
+
+    pub fn get_damping(&self) -> f32 {
+        self.damping
+    }
+
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix;
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.mix
+    }
+
+    fn make_channels(&mut self, channels: usize, sample_rate: u32) {
+        let scale = sample_rate as f32 / 44100.0;
+        let scaled = |length: usize| (length as f32 * scale) as usize;
+
+        self.channels = (0..channels)
+            .map(|channel| {
+                let spread = Self::STEREO_SPREAD * channel;
+                ReverbChannel {
+                    combs: Self::COMB_LENGTHS
+                        .iter()
+                        .map(|length| CombFilter::new(scaled(length + spread)))
+                        .collect(),
+                    all_passes: Self::ALL_PASS_LENGTHS
+                        .iter()
+                        .map(|length| AllPassFilter::new(scaled(length + spread)))
+                        .collect(),
+                }
+            })
+            .collect();
+        self.sample_rate = sample_rate;
+    }
+}
+
+impl AudioEffect for Reverb {
+    fn process(&mut self, samples: &mut [f32], channels: u16, sample_rate: u32) {
+        let channels = channels.max(1) as usize;
+        if self.channels.len() != channels || self.sample_rate != sample_rate {
+            self.make_channels(channels, sample_rate);
+        }
+
+        const INPUT_GAIN: f32 = 0.015;
+        let feedback = self.room_size.clamp(0.0, 1.0) * 0.28 + 0.7;
+        let damping = self.damping.clamp(0.0, 1.0) * 0.4;
+        let mix = self.mix.clamp(0.0, 1.0);
+
+        for (index, sample) in samples.iter_mut().enumerate() {
+            let channel = &mut self.channels[index % channels];
+            let input = *sample * INPUT_GAIN;
+
+            let mut wet = channel
+                .combs
+                .iter_mut()
+                .map(|comb| comb.process(input, feedback, damping))
+                .sum::<f32>();
+            for all_pass in &mut channel.all_passes {
+                wet = all_pass.process(wet);
+            }
+
+            *sample = *sample * (1.0 - mix) + wet * mix;
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Repeats everything after a delay, each echo quieter than the last
+pub struct Delay {
+    time: Duration,
+    feedback: f32,
+    mix: f32,
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl Delay {
+    /// the longest delay that can be set
+    pub const MAX_TIME: Duration = Duration::from_secs(2);
+
+    /// feedback is how loud each echo is compared to the one before it, from 0.0 to below 1.0
+    pub fn new(time: Duration, feedback: f32) -> Self {
+        Self {
+            time,
+            feedback,
+            mix: 0.5,
+            buffer: vec![],
+            position: 0,
+        }
+    }
+
+    /// how loud the echoes are compared to the original sound
+    pub fn with_mix(mut self, mix: f32) -> Self {
+        self.set_mix(mix);
+        self
+    }
+
+    /// at most MAX_TIME
+    pub fn set_time(&mut self, time: Duration) {
+        self.time = time;
+    }
+
+    pub fn get_time(&self) -> Duration {
+        self.time
+    }
+
+    pub fn set_feedback(&mut self, feedback: f32) {
+        self.feedback = feedback;
+    }
+
+    pub fn get_feedback(&self) -> f32 {
+        self.feedback
+    }
+
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix;
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.mix
+    }
+}
+
+impl AudioEffect for Delay {
+    fn process(&mut self, samples: &mut [f32], channels: u16, sample_rate: u32) {
+        let channels = channels.max(1) as usize;
+        let capacity = (Self::MAX_TIME.as_secs_f64() * sample_rate as f64) as usize * channels;
+        if self.buffer.len() != capacity {
+            // the buffer holds interleaved frames, so every channel is delayed the same
+            self.buffer = vec![0.0; capacity.max(channels)];
+            self.position = 0;
+        }
+
+        let delay_frames =
+            (self.time.min(Self::MAX_TIME).as_secs_f64() * sample_rate as f64) as usize;
+        let delay = (delay_frames * channels).clamp(channels, self.buffer.len());
+        let feedback = self.feedback.clamp(0.0, 0.99);
+
+        for sample in samples.iter_mut() {
+            let read = (self.position + self.buffer.len() - delay) % self.buffer.len();
+            let echo = self.buffer[read];
+            self.buffer[self.position] = *sample + echo * feedback;
+            self.position = (self.position + 1) % self.buffer.len();
+
+            *sample += echo * self.mix;
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Overdrives the sound and softly clips it, for crunchy radios and explosions
+pub struct Distortion {
+    drive: f32,
+    mix: f32,
+}
+
+impl Distortion {
+    /// 1.0 barely changes the sound, higher is more distorted
+    pub fn new(drive: f32) -> Self {
+        Self { drive, mix: 1.0 }
+    }
+
+    /// how much of the distorted sound is heard, 0.0 is only the original sound
+    pub fn with_mix(mut self, mix: f32) -> Self {
+        self.set_mix(mix);
+        self
+    }
+
+    pub fn set_drive(&mut self, drive: f32) {
+        self.drive = drive;
+    }
+
+    pub fn get_drive(&self) -> f32 {
+        self.drive
+    }
+
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix;
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.mix
+    }
+}
+
+impl AudioEffect for Distortion {
+    fn process(&mut self, samples: &mut [f32], _channels: u16, _sample_rate: u32) {
+        let drive = self.drive.max(0.01);
+        // keeps full scale at full scale no matter the drive
+        let normalize = drive.tanh();
+        let mix = self.mix.clamp(0.0, 1.0);
+
+        for sample in samples.iter_mut() {
+            let distorted = (*sample * drive).tanh() / normalize;
+            *sample = *sample * (1.0 - mix) + distorted * mix;
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Turns the sound down right away when it gets louder than the threshold, so it never clips
+pub struct Limiter {
+    threshold: f32,
+    release: Duration,
+    envelope: f32,
+}
+
+impl Limiter {
+    /// the threshold is the loudest a sample can be, 1.0 is full scale
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            release: Duration::from_millis(100),
+            envelope: 0.0,
+        }
+    }
+
+    /// how long it takes to turn the sound back up after it gets quieter
+    pub fn with_release(mut self, release: Duration) -> Self {
+        self.set_release(release);
+        self
+    }
+
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+    }
+
+    pub fn get_threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    pub fn set_release(&mut self, release: Duration) {
+        self.release = release;
+    }
+
+    pub fn get_release(&self) -> Duration {
+        self.release
+    }
+}
+
+impl AudioEffect for Limiter {
+    fn process(&mut self, samples: &mut [f32], channels: u16, sample_rate: u32) {
+        let channels = channels.max(1) as usize;
+        let threshold = self.threshold.max(0.0001);
+        let release_samples = self.release.as_secs_f32() * sample_rate as f32;
+        let release = if release_samples > 0.0 {
+            (-1.0 / release_samples).exp()
+        } else {
+            0.0
+        };
+
+        // every channel of a frame gets the same gain so the sound doesn't move to one side
+        for frame in samples.chunks_mut(channels) {
+            let peak = frame
+                .iter()
+                .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+            self.envelope = if peak > self.envelope {
+                peak
+            } else {
+                peak + (self.envelope - peak) * release
+            };
+
+            if self.envelope > threshold {
+                let gain = threshold / self.envelope;
+                for sample in frame.iter_mut() {
+                    *sample *= gain;
+                }
+            }
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AudioBus, AudioClip, AudioHandle, OfflineAudioBackend};
+
+    fn sine(frequency: f32, amplitude: f32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|frame| (frame as f32 / 44100.0 * frequency * 2.0 * PI).sin() * amplitude)
+            .collect()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples
+            .iter()
+            .fold(0.0, |peak, sample| peak.max(sample.abs()))
+    }
+
+    #[test]
+    fn filters_keep_their_side_of_the_cutoff() {
+        let mut low = sine(10000.0, 1.0, 4410);
+        LowPassFilter::new(500.0).process(&mut low, 1, 44100);
+        assert!(peak(&low[2205..]) < 0.05);
+
+        let mut high = sine(50.0, 1.0, 4410);
+        HighPassFilter::new(2000.0).process(&mut high, 1, 44100);
+        assert!(peak(&high[2205..]) < 0.05);
+
+        let mut kept = sine(100.0, 1.0, 4410);
+        LowPassFilter::new(5000.0).process(&mut kept, 1, 44100);
+        assert!(peak(&kept[2205..]) > 0.9);
+    }
+
+    #[test]
+    fn limiter_keeps_peaks_under_the_threshold() {
+        let mut samples = sine(440.0, 2.0, 4410);
+        Limiter::new(0.5).process(&mut samples, 1, 44100);
+        assert!(peak(&samples) <= 0.5 + f32::EPSILON);
+    }
+
+    #[test]
+    fn effects_on_a_bus_change_sounds_that_are_playing() {
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        audio_handle.set_master_volume(10.0);
+        audio_handle.add_bus(AudioBus::new("music"));
+        let clip = AudioClip::from_samples(sine(10000.0, 0.5, 44100), 1, 44100);
+        audio_handle.play_infinitely_on_bus(&clip, "music");
+
+        let render = |audio_handle: &mut AudioHandle| {
+            audio_handle
+                .get_backend_mut::<OfflineAudioBackend>()
+                .unwrap()
+                .render(Duration::from_millis(100))
+        };
+        let effects = |audio_handle: &AudioHandle| {
+            // the effects are shared with the bus, so they can be changed through any clone of it
+            audio_handle.get_bus("music").unwrap().clone()
+        };
+        assert!(peak(&render(&mut audio_handle)[4410..]) > 0.4);
+
+        // muffles the music, like when a pause menu opens
+        effects(&audio_handle)
+            .get_effects()
+            .add(LowPassFilter::new(500.0));
+        assert!(peak(&render(&mut audio_handle)[4410..]) < 0.05);
+
+        effects(&audio_handle)
+            .get_effects()
+            .with_effect_mut::<LowPassFilter, _>(|filter| filter.set_cutoff(18000.0));
+        assert!(peak(&render(&mut audio_handle)[4410..]) > 0.3);
+
+        let music = effects(&audio_handle);
+        assert!(music.get_effects().remove::<LowPassFilter>());
+        assert!(music.get_effects().is_empty());
+    }
+}
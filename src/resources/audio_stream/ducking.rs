@@ -0,0 +1,194 @@
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use super::audio_bus::BusControls;
+use super::sound_handle::AtomicF32;
+
+/// How much and how fast one bus turns another down while it is playing, see AudioHandle::add_ducking
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DuckingRule {
+    amount_db: f32,
+    attack: Duration,
+    release: Duration,
+    threshold: f32,
+}
+
+impl DuckingRule {
+    /// the amount is how many decibels the bus is turned down by, -12.0 is about a quarter of the volume
+    pub fn new(amount_db: f32) -> Self {
+        Self {
+            amount_db: -amount_db.abs(),
+            attack: Duration::from_millis(200),
+            release: Duration::from_millis(500),
+            threshold: 0.01,
+        }
+    }
+
+    /// how long it takes to turn the bus all the way down once the other bus starts playing
+    pub fn with_attack(mut self, attack: Duration) -> Self {
+        self.attack = attack;
+        self
+    }
+
+    /// how long it takes to turn the bus back up once the other bus is quiet
+    pub fn with_release(mut self, release: Duration) -> Self {
+        self.release = release;
+        self
+    }
+
+    /// the other bus counts as playing while its loudest sample is above this, 1.0 is full scale
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn get_amount_db(&self) -> f32 {
+        self.amount_db
+    }
+
+    pub fn get_attack(&self) -> Duration {
+        self.attack
+    }
+
+    pub fn get_release(&self) -> Duration {
+        self.release
+    }
+
+    pub fn get_threshold(&self) -> f32 {
+        self.threshold
+    }
+}
+
+/// a rule from one source bus, weak so buses that duck each other don't keep each other alive
+struct Ducking {
+    source: Weak<BusControls>,
+    rule: DuckingRule,
+    // how far the bus is turned down by this rule right now
+    current_db: f32,
+}
+
+/// every rule that turns a bus down, the rules are run by the output of the bus every frame
+pub(crate) struct DuckingState {
+    rules: Mutex<Vec<Ducking>>,
+    gain: AtomicF32,
+}
+
+impl DuckingState {
+    pub(crate) fn new() -> Self {
+        Self {
+            rules: Mutex::new(vec![]),
+            gain: AtomicF32::new(1.0),
+        }
+    }
+
+    fn rules(&self) -> std::sync::MutexGuard<'_, Vec<Ducking>> {
+        match self.rules.lock() {
+            Ok(rules) => rules,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// replaces the rule from the source if there already is one
+    pub(crate) fn add(&self, source: &Arc<BusControls>, rule: DuckingRule) {
+        let source = Arc::downgrade(source);
+        let mut rules = self.rules();
+        rules.retain(|ducking| !ducking.source.ptr_eq(&source));
+        rules.push(Ducking {
+            source,
+            rule,
+            current_db: 0.0,
+        });
+    }
+
+    pub(crate) fn remove(&self, source: &Arc<BusControls>) -> bool {
+        let source = Arc::downgrade(source);
+        let mut rules = self.rules();
+        let rule_count = rules.len();
+        rules.retain(|ducking| !ducking.source.ptr_eq(&source));
+        rules.len() != rule_count
+    }
+
+    /// the gain from every rule together
+    pub(crate) fn get_gain(&self) -> f32 {
+        self.gain.get()
+    }
+
+    /// moves every rule towards being ducked or not, going by the levels the sources measured last frame
+    pub(crate) fn update(&self, frame_time: Duration) {
+        let mut rules = self.rules();
+        // removed buses can't duck anything
+        rules.retain(|ducking| ducking.source.strong_count() > 0);
+
+        let mut total_db = 0.0;
+        for ducking in rules.iter_mut() {
+            let active = ducking
+                .source
+                .upgrade()
+                .is_some_and(|source| source.level.get() > ducking.rule.threshold);
+
+            // moves in a straight line in decibels, so the attack and release are exactly as long as asked
+            let (target_db, time) = if active {
+                (ducking.rule.amount_db, ducking.rule.attack)
+            } else {
+                (0.0, ducking.rule.release)
+            };
+            let step = if time.is_zero() {
+                f32::INFINITY
+            } else {
+                ducking.rule.amount_db.abs() * frame_time.as_secs_f32() / time.as_secs_f32()
+            };
+
+            let difference = target_db - ducking.current_db;
+            ducking.current_db += difference.clamp(-step, step);
+            total_db += ducking.current_db;
+        }
+
+        self.gain.set(10f32.powf(total_db / 20.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DuckingRule;
+    use crate::{AudioBus, AudioClip, AudioHandle, OfflineAudioBackend};
+    use std::time::Duration;
+
+    fn level_after(audio_handle: &mut AudioHandle, milliseconds: u64) -> f32 {
+        *audio_handle
+            .get_backend_mut::<OfflineAudioBackend>()
+            .unwrap()
+            .render(Duration::from_millis(milliseconds))
+            .last()
+            .unwrap()
+    }
+
+    #[test]
+    fn voice_ducks_the_music_while_it_plays() {
+        let clip = AudioClip::from_samples(vec![0.5; 4410], 1, 44100);
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        audio_handle.set_master_volume(10.0);
+        audio_handle.add_bus(AudioBus::new("music"));
+        audio_handle.add_bus(AudioBus::new("voice"));
+        audio_handle.add_ducking("voice", "music", DuckingRule::new(-12.0));
+        audio_handle.play_infinitely_on_bus(&clip, "music");
+
+        assert!((level_after(&mut audio_handle, 50) - 0.5).abs() < 0.001);
+
+        let voice = audio_handle.play_infinitely_on_bus(&clip, "voice");
+        level_after(&mut audio_handle, 300);
+        let music_gain = audio_handle.get_bus("music").unwrap().get_ducking_gain();
+        assert!((music_gain - 0.251).abs() < 0.001);
+        assert!((level_after(&mut audio_handle, 20) - (0.5 + 0.5 * music_gain)).abs() < 0.001);
+
+        voice.stop();
+        level_after(&mut audio_handle, 600);
+        assert_eq!(
+            audio_handle.get_bus("music").unwrap().get_ducking_gain(),
+            1.0
+        );
+        assert!((level_after(&mut audio_handle, 20) - 0.5).abs() < 0.001);
+
+        assert!(audio_handle.remove_ducking("voice", "music"));
+        assert!(!audio_handle.remove_ducking("voice", "music"));
+    }
+}
@@ -0,0 +1,172 @@
+use std::f32::consts::FRAC_PI_2;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::audio_bus::BusControls;
+use super::SoundHandle;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum FadeCurve {
+    Linear,
+    /// keeps the loudness steady when one sound fades out while another fades in
+    EqualPower,
+}
+
+pub(crate) enum FadeTarget {
+    Sound(SoundHandle),
+    Bus(Arc<BusControls>),
+}
+
+impl FadeTarget {
+    fn get_volume(&self) -> f32 {
+        match self {
+            FadeTarget::Sound(sound) => sound.get_volume(),
+            FadeTarget::Bus(controls) => controls.volume.get(),
+        }
+    }
+
+    fn set_volume(&self, volume: f32) {
+        match self {
+            FadeTarget::Sound(sound) => sound.set_volume(volume),
+            FadeTarget::Bus(controls) => controls.volume.set(volume),
+        }
+    }
+
+    fn is_same_target(&self, other: &FadeTarget) -> bool {
+        match (self, other) {
+            (FadeTarget::Sound(a), FadeTarget::Sound(b)) => a.is_same_sound(b),
+            (FadeTarget::Bus(a), FadeTarget::Bus(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+/// moves the volume of a sound or bus over time, the time comes from DeltaTime
+pub(crate) struct Fade {
+    target: FadeTarget,
+    from: f32,
+    to: f32,
+    duration: f64,
+    elapsed: f64,
+    curve: FadeCurve,
+    stop_at_end: bool,
+}
+
+impl Fade {
+    /// fades from the current volume of the target
+    pub(crate) fn new(target: FadeTarget, to: f32, duration: Duration, curve: FadeCurve) -> Self {
+        Self {
+            from: target.get_volume(),
+            target,
+            to,
+            duration: duration.as_secs_f64(),
+            elapsed: 0.0,
+            curve,
+            stop_at_end: false,
+        }
+    }
+
+    /// stops the sound when the fade is done
+    pub(crate) fn with_stop_at_end(mut self) -> Self {
+        self.stop_at_end = true;
+        self
+    }
+
+    /// moves the fade forward and returns true when it is done
+    fn advance(&mut self, delta_time: f64) -> bool {
+        if let FadeTarget::Sound(sound) = &self.target {
+            if !sound.is_playing() {
+                return true;
+            }
+        }
+
+        self.elapsed += delta_time.max(0.0);
+        let progress = if self.duration <= 0.0 {
+            1.0
+        } else {
+            (self.elapsed / self.duration).min(1.0) as f32
+        };
+
+        let shaped_progress = match self.curve {
+            FadeCurve::Linear => progress,
+            FadeCurve::EqualPower if self.to >= self.from => (progress * FRAC_PI_2).sin(),
+            FadeCurve::EqualPower => 1.0 - (progress * FRAC_PI_2).cos(),
+        };
+        self.target
+            .set_volume(self.from + (self.to - self.from) * shaped_progress);
+
+        if progress < 1.0 {
+            return false;
+        }
+
+        if self.stop_at_end {
+            if let FadeTarget::Sound(sound) = &self.target {
+                sound.stop();
+            }
+        }
+        true
+    }
+}
+
+/// every fade that is running, a new fade on a sound or bus replaces the old one
+#[derive(Default)]
+pub(crate) struct Fades {
+    fades: Vec<Fade>,
+}
+
+impl Fades {
+    pub(crate) fn add(&mut self, fade: Fade) {
+        self.fades
+            .retain(|existing| !existing.target.is_same_target(&fade.target));
+        self.fades.push(fade);
+    }
+
+    pub(crate) fn update(&mut self, delta_time: f64) {
+        self.fades.retain_mut(|fade| !fade.advance(delta_time));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{AudioClip, AudioHandle, OfflineAudioBackend};
+    use std::time::Duration;
+
+    #[test]
+    fn fades_follow_delta_time() {
+        let clip = AudioClip::from_samples(vec![0.5; 4410], 1, 44100);
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        let sound = audio_handle.play_infinitely(&clip);
+
+        audio_handle.fade_sound(&sound, 0.0, Duration::from_secs(1));
+        audio_handle.update_fades(0.5);
+        assert!((sound.get_volume() - 0.5).abs() < 0.001);
+
+        // a time scale of 0 holds the fade where it is
+        audio_handle.update_fades(0.0);
+        assert!((sound.get_volume() - 0.5).abs() < 0.001);
+
+        audio_handle.fade_out_sound(&sound, Duration::from_secs(1));
+        audio_handle.update_fades(1.0);
+        assert_eq!(sound.get_volume(), 0.0);
+        assert!(!sound.is_playing());
+    }
+
+    #[test]
+    fn crossfades_keep_the_loudness() {
+        let clip = AudioClip::from_samples(vec![0.5; 4410], 1, 44100);
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        let old = audio_handle.play_infinitely(&clip);
+        let new = audio_handle.play_infinitely(&clip);
+
+        audio_handle.crossfade(&old, &new, Duration::from_secs(2));
+        assert_eq!(new.get_volume(), 0.0);
+
+        audio_handle.update_fades(1.0);
+        let power = old.get_volume().powi(2) + new.get_volume().powi(2);
+        assert!((power - 1.0).abs() < 0.001);
+
+        audio_handle.update_fades(1.0);
+        assert!(!old.is_playing());
+        assert!((new.get_volume() - 1.0).abs() < 0.001);
+    }
+}
@@ -0,0 +1,587 @@
+use rand::seq::SliceRandom;
+use rodio::source::Source;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use ABC_ECS::Resource;
+
+use super::{AudioClip, AudioClipSource, AudioHandle, SoundHandle, SoundSource};
+use super::{MIXER_CHANNELS, MIXER_SAMPLE_RATE};
+
+/// what the MusicPlayer does when a track ends
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RepeatMode {
+    /// stops after the last track
+    Off,
+    /// starts the playlist again after the last track, shuffled again if shuffle is on
+    All,
+    /// plays the same track over and over
+    One,
+}
+
+// the current track when nothing is playing
+const NO_TRACK: usize = usize::MAX;
+
+struct QueuedTrack {
+    index: usize,
+    clip: AudioClip,
+}
+
+/// Plays the tracks queued by the MusicPlayer one after the other with no gap between them.
+/// the player keeps the next track queued, the source moves on to it on the audio thread
+struct PlaylistSource {
+    current: Option<AudioClipSource>,
+    queue: Arc<Mutex<VecDeque<QueuedTrack>>>,
+    current_track: Arc<AtomicUsize>,
+}
+
+impl PlaylistSource {
+    fn new(queue: Arc<Mutex<VecDeque<QueuedTrack>>>, current_track: Arc<AtomicUsize>) -> Self {
+        let mut source = Self {
+            current: None,
+            queue,
+            current_track,
+        };
+        source.next_track();
+        source
+    }
+
+    fn next_track(&mut self) {
+        let next = match self.queue.lock() {
+            Ok(mut queue) => queue.pop_front(),
+            Err(_) => None,
+        };
+
+        match next {
+            Some(track) => {
+                self.current_track.store(track.index, Ordering::Relaxed);
+                self.current = Some(track.clip.to_source());
+            }
+            None => {
+                self.current_track.store(NO_TRACK, Ordering::Relaxed);
+                self.current = None;
+            }
+        }
+    }
+}
+
+impl Iterator for PlaylistSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.current.as_mut()?.next();
+
+        // move on as soon as the track ends, rodio treats a frame length of 0 as the end of the source
+        let track_ended = self
+            .current
+            .as_ref()
+            .map_or(true, |current| current.current_frame_len() == Some(0));
+        if track_ended || sample.is_none() {
+            self.next_track();
+        }
+
+        match sample {
+            Some(sample) => Some(sample),
+            None => self.current.as_mut()?.next(),
+        }
+    }
+}
+
+impl Source for PlaylistSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        // every track is one frame, so the channels and sample rate can change between tracks
+        match &self.current {
+            Some(current) => current.current_frame_len(),
+            None => Some(0),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.current
+            .as_ref()
+            .map_or(MIXER_CHANNELS, |current| current.channels())
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.current
+            .as_ref()
+            .map_or(MIXER_SAMPLE_RATE, |current| current.sample_rate())
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl SoundSource for PlaylistSource {
+    /// seeks in the current track
+    fn seek(&mut self, position: Duration) -> bool {
+        match &mut self.current {
+            Some(current) => current.seek(position),
+            None => false,
+        }
+    }
+}
+
+struct PlayingPlaylist {
+    sound: SoundHandle,
+    queue: Arc<Mutex<VecDeque<QueuedTrack>>>,
+    current_track: Arc<AtomicUsize>,
+    // fading out, nothing more is queued
+    stopping: bool,
+}
+
+enum MusicCommand {
+    Play,
+    Skip,
+    Stop(Duration),
+}
+
+/// A resource that plays a playlist of AudioClips, with shuffle, repeat and crossfades when the music changes.
+/// The tracks follow each other with no gap. Changes are applied the next time the audio system runs,
+/// which needs the AudioHandle, so they do nothing if the audio systems were not added.
+pub struct MusicPlayer {
+    playlist: Vec<AudioClip>,
+    // the order the tracks are played in, shuffled if shuffle is on
+    order: Vec<usize>,
+    next_in_order: usize,
+    last_track: Option<usize>,
+    bus: Option<String>,
+    shuffle: bool,
+    repeat: RepeatMode,
+    volume: f32,
+    crossfade: Duration,
+    scene_change_fade_out: Duration,
+    playing: Option<PlayingPlaylist>,
+    commands: Vec<MusicCommand>,
+}
+
+impl Default for MusicPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MusicPlayer {
+    pub fn new() -> Self {
+        Self {
+            playlist: vec![],
+            order: vec![],
+            next_in_order: 0,
+            last_track: None,
+            bus: None,
+            shuffle: false,
+            repeat: RepeatMode::All,
+            volume: 1.0,
+            crossfade: Duration::from_secs(2),
+            scene_change_fade_out: Duration::from_secs(1),
+            playing: None,
+            commands: vec![],
+        }
+    }
+
+    /// the music is played on this bus, the bus has to be added to the AudioHandle
+    pub fn with_bus(mut self, bus: &str) -> Self {
+        self.set_bus(bus);
+        self
+    }
+
+    pub fn with_playlist(mut self, playlist: Vec<AudioClip>) -> Self {
+        self.set_playlist(playlist);
+        self
+    }
+
+    pub fn with_shuffle(mut self, shuffle: bool) -> Self {
+        self.set_shuffle(shuffle);
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: RepeatMode) -> Self {
+        self.set_repeat(repeat);
+        self
+    }
+
+    /// how long the old music fades out while the new music fades in when play or skip is called
+    pub fn with_crossfade(mut self, crossfade: Duration) -> Self {
+        self.set_crossfade(crossfade);
+        self
+    }
+
+    /// how long fade_out_for_scene_change takes
+    pub fn with_scene_change_fade_out(mut self, fade_out: Duration) -> Self {
+        self.set_scene_change_fade_out(fade_out);
+        self
+    }
+
+    /// takes effect from the next time play is called
+    pub fn set_bus(&mut self, bus: &str) {
+        self.bus = Some(bus.to_string());
+    }
+
+    pub fn get_bus(&self) -> Option<&String> {
+        self.bus.as_ref()
+    }
+
+    /// the music that is playing keeps going, call play to start the new playlist
+    pub fn set_playlist(&mut self, playlist: Vec<AudioClip>) {
+        self.playlist = playlist;
+        self.order.clear();
+        self.next_in_order = 0;
+        self.last_track = None;
+    }
+
+    pub fn get_playlist(&self) -> &[AudioClip] {
+        &self.playlist
+    }
+
+    /// adds a track to the end of the playlist
+    pub fn add_track(&mut self, track: AudioClip) {
+        self.playlist.push(track);
+        // an empty order is made from the whole playlist when it is needed
+        if !self.shuffle && !self.order.is_empty() {
+            self.order.push(self.playlist.len() - 1);
+        }
+    }
+
+    /// takes effect when the playlist starts again
+    pub fn set_shuffle(&mut self, shuffle: bool) {
+        self.shuffle = shuffle;
+    }
+
+    pub fn get_shuffle(&self) -> bool {
+        self.shuffle
+    }
+
+    pub fn set_repeat(&mut self, repeat: RepeatMode) {
+        self.repeat = repeat;
+    }
+
+    pub fn get_repeat(&self) -> RepeatMode {
+        self.repeat
+    }
+
+    pub fn set_crossfade(&mut self, crossfade: Duration) {
+        self.crossfade = crossfade;
+    }
+
+    pub fn get_crossfade(&self) -> Duration {
+        self.crossfade
+    }
+
+    pub fn set_scene_change_fade_out(&mut self, fade_out: Duration) {
+        self.scene_change_fade_out = fade_out;
+    }
+
+    pub fn get_scene_change_fade_out(&self) -> Duration {
+        self.scene_change_fade_out
+    }
+
+    /// the volume of the music on its bus, this is applied right away
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+        if let Some(playing) = &self.playing {
+            playing.sound.set_volume(volume);
+        }
+    }
+
+    pub fn get_volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// starts the playlist from the start, crossfading from the music that was playing
+    pub fn play(&mut self) {
+        self.commands.push(MusicCommand::Play);
+    }
+
+    /// crossfades to the next track
+    pub fn skip(&mut self) {
+        self.commands.push(MusicCommand::Skip);
+    }
+
+    /// fades the music out over the given time and stops it
+    pub fn stop(&mut self, fade_out: Duration) {
+        self.commands.push(MusicCommand::Stop(fade_out));
+    }
+
+    /// Fades out the music over the scene change fade out time.
+    /// call this before changing scenes and keep running the old scene until is_playing is false,
+    /// the music stops when the AudioHandle of the old scene is dropped
+    pub fn fade_out_for_scene_change(&mut self) {
+        self.stop(self.scene_change_fade_out);
+    }
+
+    /// pauses the music right away, it continues from the same place when resume is called
+    pub fn pause(&mut self) {
+        if let Some(playing) = &self.playing {
+            playing.sound.pause();
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if let Some(playing) = &self.playing {
+            playing.sound.resume();
+        }
+    }
+
+    /// true while the music is playing or fading out, a paused player is still playing
+    pub fn is_playing(&self) -> bool {
+        self.playing
+            .as_ref()
+            .is_some_and(|playing| playing.sound.is_playing())
+    }
+
+    /// the index in the playlist of the track that is playing
+    pub fn get_current_track(&self) -> Option<usize> {
+        let playing = self.playing.as_ref()?;
+        match playing.current_track.load(Ordering::Relaxed) {
+            NO_TRACK => None,
+            track => Some(track),
+        }
+    }
+
+    /// the handle of the music that is playing, for example to seek in the current track
+    pub fn get_sound_handle(&self) -> Option<&SoundHandle> {
+        self.playing.as_ref().map(|playing| &playing.sound)
+    }
+
+    fn make_order(&mut self) {
+        self.order = (0..self.playlist.len()).collect();
+        if self.shuffle {
+            self.order.shuffle(&mut rand::thread_rng());
+
+            // don't play the same track twice in a row when the playlist starts again
+            if self.order.len() > 1 && self.order.first() == self.last_track.as_ref() {
+                let last = self.order.len() - 1;
+                self.order.swap(0, last);
+            }
+        }
+        self.next_in_order = 0;
+    }
+
+    /// picks the track that is played after the last one, None if the playlist is over
+    fn next_track(&mut self) -> Option<usize> {
+        if self.playlist.is_empty() {
+            return None;
+        }
+
+        if self.repeat == RepeatMode::One {
+            if let Some(track) = self.last_track.filter(|track| *track < self.playlist.len()) {
+                return Some(track);
+            }
+        }
+
+        if self.next_in_order >= self.order.len() {
+            if self.repeat == RepeatMode::Off && !self.order.is_empty() {
+                return None;
+            }
+            self.make_order();
+        }
+
+        let track = self.order[self.next_in_order];
+        self.next_in_order += 1;
+        self.last_track = Some(track);
+        Some(track)
+    }
+
+    /// queues the next track if nothing is queued, so the playlist source never runs out between tracks
+    fn queue_next_track(&mut self, queue: &Mutex<VecDeque<QueuedTrack>>) {
+        let Ok(mut queue) = queue.lock() else {
+            return;
+        };
+
+        if queue.is_empty() {
+            if let Some(index) = self.next_track() {
+                queue.push_back(QueuedTrack {
+                    index,
+                    clip: self.playlist[index].clone(),
+                });
+            }
+        }
+    }
+
+    /// starts the tracks from the queue, or the next track if it is empty, crossfading from the old music
+    fn start(&mut self, audio_handle: &mut AudioHandle, queue: VecDeque<QueuedTrack>) {
+        let queue = Arc::new(Mutex::new(queue));
+        self.queue_next_track(&queue);
+        let current_track = Arc::new(AtomicUsize::new(NO_TRACK));
+        let source = PlaylistSource::new(queue.clone(), current_track.clone());
+        if source.current.is_none() {
+            return;
+        }
+
+        let old = self.playing.take();
+        let crossfade = old.is_some() && !self.crossfade.is_zero();
+        let start_volume = if crossfade { 0.0 } else { self.volume };
+        let sound = audio_handle.play_source(Box::new(source), self.bus.as_deref(), start_volume);
+
+        if let Some(old) = old {
+            if crossfade {
+                audio_handle.crossfade_to_volume(&old.sound, &sound, self.volume, self.crossfade);
+            } else {
+                old.sound.stop();
+            }
+        }
+
+        self.queue_next_track(&queue);
+        self.playing = Some(PlayingPlaylist {
+            sound,
+            queue,
+            current_track,
+            stopping: false,
+        });
+    }
+
+    /// runs the commands and keeps the next track queued, this is done by the audio system every frame
+    pub fn update(&mut self, audio_handle: &mut AudioHandle) {
+        for command in std::mem::take(&mut self.commands) {
+            match command {
+                MusicCommand::Play => {
+                    self.make_order();
+                    self.last_track = None;
+                    self.start(audio_handle, VecDeque::new());
+                }
+                MusicCommand::Skip => {
+                    // the queued track is the one that comes next
+                    let queued = self
+                        .playing
+                        .as_ref()
+                        .and_then(|playing| {
+                            playing
+                                .queue
+                                .lock()
+                                .ok()
+                                .map(|mut queue| queue.drain(..).collect())
+                        })
+                        .unwrap_or_default();
+                    self.start(audio_handle, queued);
+                }
+                MusicCommand::Stop(fade_out) => {
+                    if let Some(playing) = &mut self.playing {
+                        if let Ok(mut queue) = playing.queue.lock() {
+                            queue.clear();
+                        }
+                        playing.stopping = true;
+                        audio_handle.fade_out_sound(&playing.sound, fade_out);
+                    }
+                }
+            }
+        }
+
+        let Some(playing) = &self.playing else {
+            return;
+        };
+        if !playing.sound.is_playing() {
+            self.playing = None;
+            return;
+        }
+
+        if !playing.stopping {
+            let queue = playing.queue.clone();
+            self.queue_next_track(&queue);
+        }
+    }
+}
+
+impl Resource for MusicPlayer {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::audio_stream::AudioUpdateSystem;
+    use crate::DeltaTime;
+    use crate::OfflineAudioBackend;
+    use ABC_ECS::System;
+    use ABC_ECS::World;
+
+    fn constant_clip(value: f32, samples: usize) -> AudioClip {
+        AudioClip::from_samples(vec![value; samples], 1, 44100)
+    }
+
+    fn track_order(player: &mut MusicPlayer, tracks: usize) -> Vec<Option<usize>> {
+        (0..tracks).map(|_| player.next_track()).collect()
+    }
+
+    #[test]
+    fn repeat_modes_pick_the_right_tracks() {
+        let playlist = vec![constant_clip(0.5, 10), constant_clip(0.25, 10)];
+
+        let mut player = MusicPlayer::new()
+            .with_playlist(playlist.clone())
+            .with_repeat(RepeatMode::Off);
+        assert_eq!(track_order(&mut player, 3), vec![Some(0), Some(1), None]);
+
+        let mut player = MusicPlayer::new()
+            .with_playlist(playlist.clone())
+            .with_repeat(RepeatMode::All);
+        assert_eq!(
+            track_order(&mut player, 4),
+            vec![Some(0), Some(1), Some(0), Some(1)]
+        );
+
+        let mut player = MusicPlayer::new()
+            .with_playlist(playlist)
+            .with_repeat(RepeatMode::One);
+        assert_eq!(track_order(&mut player, 3), vec![Some(0), Some(0), Some(0)]);
+    }
+
+    #[test]
+    fn tracks_play_without_a_gap() {
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        audio_handle.set_master_volume(10.0);
+        // lets the master volume settle before the music starts
+        audio_handle
+            .get_backend_mut::<OfflineAudioBackend>()
+            .unwrap()
+            .render(Duration::from_millis(20));
+
+        let mut player = MusicPlayer::new()
+            .with_playlist(vec![constant_clip(0.5, 441), constant_clip(0.25, 441)])
+            .with_repeat(RepeatMode::Off);
+        player.play();
+        player.update(&mut audio_handle);
+
+        let samples = audio_handle
+            .get_backend_mut::<OfflineAudioBackend>()
+            .unwrap()
+            .render(Duration::from_millis(100));
+        let start = samples
+            .iter()
+            .position(|sample| *sample != 0.0)
+            .expect("the music should play");
+
+        // 441 mono samples are 441 stereo frames
+        let first_track = &samples[start..start + 441 * 2];
+        let second_track = &samples[start + 441 * 2..start + 441 * 4];
+        assert!(first_track
+            .iter()
+            .all(|sample| (sample - 0.5).abs() < 0.001));
+        assert!(second_track
+            .iter()
+            .all(|sample| (sample - 0.25).abs() < 0.001));
+        assert_eq!(player.get_current_track(), None);
+    }
+
+    #[test]
+    fn the_music_player_is_kept_without_an_audio_handle() {
+        let mut world = World::new();
+        let entities_and_components = &mut world.entities_and_components;
+        entities_and_components.add_resource(DeltaTime::new());
+        entities_and_components
+            .add_resource(MusicPlayer::new().with_playlist(vec![constant_clip(0.5, 10)]));
+
+        AudioUpdateSystem::new().run(entities_and_components);
+
+        let player = entities_and_components
+            .get_resource::<MusicPlayer>()
+            .unwrap();
+        assert_eq!(player.get_playlist().len(), 1);
+    }
+}
@@ -0,0 +1,227 @@
+use ABC_ECS::{EntitiesAndComponents, Entity};
+
+use super::SoundHandle;
+use crate::get_transform;
+
+/// how the volume of an AudioEmitter falls off between its min and max distance
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioRolloff {
+    /// goes in a straight line from full volume at the min distance to silent at the max distance
+    Linear,
+    /// the volume is min_distance / (min_distance + factor * (distance - min_distance)), 1.0 is close to real sound
+    Inverse(f32),
+    /// the volume is (distance / min_distance) ^ -factor
+    Exponential(f32),
+}
+
+/// Sounds on AudioEmitters are heard from the first entity with this component, usually the camera or the player.
+/// without a listener positional sounds are played at full volume in the center
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AudioListener {
+    pan_distance: f32,
+}
+
+impl AudioListener {
+    pub fn new() -> Self {
+        Self {
+            pan_distance: 500.0,
+        }
+    }
+
+    /// how far to the side in pixels a sound has to be to only be heard on one side
+    pub fn with_pan_distance(mut self, pan_distance: f32) -> Self {
+        self.set_pan_distance(pan_distance);
+        self
+    }
+
+    pub fn set_pan_distance(&mut self, pan_distance: f32) {
+        self.pan_distance = pan_distance;
+    }
+
+    pub fn get_pan_distance(&self) -> f32 {
+        self.pan_distance
+    }
+}
+
+/// Makes the sounds added to it louder and panned towards where the entity is, compared to the AudioListener.
+/// The volume and pan are updated every frame from the world transforms, so moving emitters keep sounding right.
+/// finished sounds are removed from the emitter
+#[derive(Clone)]
+pub struct AudioEmitter {
+    min_distance: f32,
+    max_distance: f32,
+    rolloff: AudioRolloff,
+    sounds: Vec<SoundHandle>,
+}
+
+impl AudioEmitter {
+    pub fn new() -> Self {
+        Self {
+            min_distance: 100.0,
+            max_distance: 2000.0,
+            rolloff: AudioRolloff::Inverse(1.0),
+            sounds: vec![],
+        }
+    }
+
+    /// sounds closer than this in pixels are at full volume
+    pub fn with_min_distance(mut self, min_distance: f32) -> Self {
+        self.set_min_distance(min_distance);
+        self
+    }
+
+    /// sounds further than this in pixels are silent
+    pub fn with_max_distance(mut self, max_distance: f32) -> Self {
+        self.set_max_distance(max_distance);
+        self
+    }
+
+    pub fn with_rolloff(mut self, rolloff: AudioRolloff) -> Self {
+        self.set_rolloff(rolloff);
+        self
+    }
+
+    pub fn set_min_distance(&mut self, min_distance: f32) {
+        self.min_distance = min_distance;
+    }
+
+    pub fn get_min_distance(&self) -> f32 {
+        self.min_distance
+    }
+
+    pub fn set_max_distance(&mut self, max_distance: f32) {
+        self.max_distance = max_distance;
+    }
+
+    pub fn get_max_distance(&self) -> f32 {
+        self.max_distance
+    }
+
+    pub fn set_rolloff(&mut self, rolloff: AudioRolloff) {
+        self.rolloff = rolloff;
+    }
+
+    pub fn get_rolloff(&self) -> AudioRolloff {
+        self.rolloff
+    }
+
+    /// Plays a sound from this emitter, the sound is silent until the positional audio is updated at the end of the frame.
+    /// play the sound with the AudioHandle first and add the handle it returns
+    pub fn add_sound(&mut self, sound: SoundHandle) {
+        sound.set_spatial_gain(0.0);
+        self.sounds.push(sound);
+    }
+
+    /// the sounds that are playing from this emitter
+    pub fn get_sounds(&self) -> &[SoundHandle] {
+        &self.sounds
+    }
+
+    /// stops every sound on this emitter
+    pub fn stop_all(&mut self) {
+        for sound in self.sounds.drain(..) {
+            sound.stop();
+        }
+    }
+
+    /// the volume of a sound at the given distance in pixels
+    pub fn get_gain_at(&self, distance: f32) -> f32 {
+        let min_distance = self.min_distance.max(f32::EPSILON);
+        if distance <= min_distance {
+            return 1.0;
+        }
+        if distance >= self.max_distance {
+            return 0.0;
+        }
+
+        let gain = match self.rolloff {
+            AudioRolloff::Linear => {
+                1.0 - (distance - min_distance) / (self.max_distance - min_distance)
+            }
+            AudioRolloff::Inverse(factor) => {
+                min_distance / (min_distance + factor * (distance - min_distance))
+            }
+            AudioRolloff::Exponential(factor) => (distance / min_distance).powf(-factor),
+        };
+        gain.clamp(0.0, 1.0)
+    }
+}
+
+/// sets the volume and pan of every sound on an AudioEmitter from where it is compared to the AudioListener
+pub(crate) fn update_positional_audio(entities_and_components: &mut EntitiesAndComponents) {
+    let listener = entities_and_components
+        .get_entities_with_component::<AudioListener>()
+        .next()
+        .copied()
+        .map(|entity| {
+            let listener = *entities_and_components
+                .get_components::<(AudioListener,)>(entity)
+                .0;
+            (listener, get_transform(entity, entities_and_components))
+        });
+
+    let emitters = entities_and_components
+        .get_entities_with_component::<AudioEmitter>()
+        .cloned()
+        .collect::<Vec<Entity>>();
+
+    for entity in emitters {
+        let emitter_transform = get_transform(entity, entities_and_components);
+
+        let emitter = entities_and_components
+            .get_components_mut::<(AudioEmitter,)>(entity)
+            .0;
+        emitter.sounds.retain(|sound| sound.is_playing());
+
+        let (gain, pan) = match &listener {
+            Some((listener, listener_transform)) => {
+                let offset_x = emitter_transform.x - listener_transform.x;
+                let offset_y = emitter_transform.y - listener_transform.y;
+                let distance = (offset_x.powi(2) + offset_y.powi(2)).sqrt() as f32;
+
+                // the pan is from the side of the listener, so a rotated camera still hears the right side
+                let rotation = -listener_transform.rotation;
+                let local_x = (offset_x * rotation.cos() - offset_y * rotation.sin()) as f32;
+                let pan = (local_x / listener.pan_distance.max(f32::EPSILON)).clamp(-1.0, 1.0);
+
+                (emitter.get_gain_at(distance), pan)
+            }
+            None => (1.0, 0.0),
+        };
+
+        for sound in &emitter.sounds {
+            sound.set_spatial_gain(gain);
+            sound.set_pan(pan);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rolloff_goes_from_full_to_silent() {
+        for rolloff in [
+            AudioRolloff::Linear,
+            AudioRolloff::Inverse(1.0),
+            AudioRolloff::Exponential(2.0),
+        ] {
+            let emitter = AudioEmitter::new()
+                .with_min_distance(100.0)
+                .with_max_distance(1000.0)
+                .with_rolloff(rolloff);
+
+            assert_eq!(emitter.get_gain_at(50.0), 1.0);
+            assert_eq!(emitter.get_gain_at(1000.0), 0.0);
+            assert!(emitter.get_gain_at(300.0) < 1.0);
+            assert!(emitter.get_gain_at(300.0) > emitter.get_gain_at(600.0));
+        }
+
+        let emitter = AudioEmitter::new()
+            .with_min_distance(100.0)
+            .with_max_distance(1000.0)
+            .with_rolloff(AudioRolloff::Inverse(1.0));
+        assert!((emitter.get_gain_at(200.0) - 0.5).abs() < 0.001);
+    }
+}
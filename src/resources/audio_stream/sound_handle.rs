@@ -0,0 +1,381 @@
+use rodio::source::Source;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::PlayableSource;
+
+/// an f32 that can be shared between the game and the audio thread
+pub(crate) struct AtomicF32(AtomicU32);
+
+impl AtomicF32 {
+    pub(crate) fn new(value: f32) -> Self {
+        Self(AtomicU32::new(value.to_bits()))
+    }
+
+    pub(crate) fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn set(&self, value: f32) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// how many samples per channel a voice plays before it checks its controls again
+const VOICE_FRAME_LEN: usize = 512;
+/// how much of the way to the new gain and pan a voice moves every sample, so changes don't click
+const VOICE_SMOOTHING: f32 = 0.005;
+
+/// shared between a Voice on the audio thread and every SoundHandle to it
+pub(crate) struct VoiceControls {
+    volume: AtomicF32,
+    speed: AtomicF32,
+    /// -1.0 is fully left, 1.0 is fully right
+    pan: AtomicF32,
+    /// the volume from the distance to the AudioListener, kept apart so it doesn't change the volume of the handle
+    spatial_gain: AtomicF32,
+    paused: AtomicBool,
+    stopped: AtomicBool,
+    finished: AtomicBool,
+    seek_to: Mutex<Option<Duration>>,
+}
+
+impl VoiceControls {
+    fn new() -> Self {
+        Self {
+            volume: AtomicF32::new(1.0),
+            speed: AtomicF32::new(1.0),
+            pan: AtomicF32::new(0.0),
+            spatial_gain: AtomicF32::new(1.0),
+            paused: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+            seek_to: Mutex::new(None),
+        }
+    }
+}
+
+/// A single sound that is playing, it applies the controls of its SoundHandle to the source.
+/// mono sounds are played in stereo so they can be panned
+pub(crate) struct Voice {
+    source: PlayableSource,
+    controls: Arc<VoiceControls>,
+    // samples left until the controls are checked again, speed changes have to wait for a new frame
+    frame_remaining: usize,
+    speed: f32,
+    // the channel of the next sample from the source
+    channel: u16,
+    // the right channel of a mono sample, it is played after the left channel
+    pending_right: Option<f32>,
+    // the gain and pan being heard, they move towards the controls every sample, None until the first sample
+    gain: Option<f32>,
+    pan: f32,
+}
+
+impl Voice {
+    pub(crate) fn new(source: PlayableSource) -> (Self, SoundHandle) {
+        let controls = Arc::new(VoiceControls::new());
+        let mut voice = Self {
+            source,
+            controls: controls.clone(),
+            frame_remaining: 0,
+            speed: 1.0,
+            channel: 0,
+            pending_right: None,
+            gain: None,
+            pan: 0.0,
+        };
+        voice.start_frame();
+
+        (voice, SoundHandle { controls })
+    }
+
+    /// reads the controls that can only change between frames
+    fn start_frame(&mut self) {
+        let seek_to = self
+            .controls
+            .seek_to
+            .try_lock()
+            .ok()
+            .and_then(|mut seek_to| seek_to.take());
+        if let Some(position) = seek_to {
+            // sources that can't seek just keep playing
+            self.source.seek(position);
+        }
+
+        self.speed = self.controls.speed.get().max(0.01);
+        self.channel = 0;
+
+        let channels = self.source.channels().max(1) as usize;
+        self.frame_remaining = self
+            .source
+            .current_frame_len()
+            .unwrap_or(usize::MAX)
+            .min(VOICE_FRAME_LEN * channels);
+    }
+
+    fn is_upmixed(&self) -> bool {
+        self.source.channels() == 1
+    }
+
+    /// moves the gain and pan towards the controls and returns the gain of the left and right channel
+    fn next_channel_gains(&mut self) -> (f32, f32) {
+        let target_gain = self.controls.volume.get() * self.controls.spatial_gain.get();
+        let target_pan = self.controls.pan.get().clamp(-1.0, 1.0);
+
+        let gain = match self.gain {
+            Some(gain) => gain + (target_gain - gain) * VOICE_SMOOTHING,
+            None => {
+                self.pan = target_pan;
+                target_gain
+            }
+        };
+        self.gain = Some(gain);
+        self.pan += (target_pan - self.pan) * VOICE_SMOOTHING;
+
+        // the center is as loud as before, panning only takes volume away from the other side
+        let left = (1.0 - self.pan).min(1.0);
+        let right = (1.0 + self.pan).min(1.0);
+        (gain * left, gain * right)
+    }
+}
+
+impl Iterator for Voice {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.controls.stopped.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        if let Some(right) = self.pending_right.take() {
+            return Some(right);
+        }
+
+        if self.frame_remaining == 0 {
+            self.start_frame();
+        }
+
+        let sample = if self.controls.paused.load(Ordering::Relaxed) {
+            // keep the frame going so the output stays in sync, but don't move through the sound
+            0.0
+        } else {
+            self.source.next()?
+        };
+        self.frame_remaining = self.frame_remaining.saturating_sub(1);
+
+        let (left_gain, right_gain) = self.next_channel_gains();
+        let channels = self.source.channels().max(1);
+        let channel = self.channel;
+        self.channel = (self.channel + 1) % channels;
+
+        if self.is_upmixed() {
+            self.pending_right = Some(sample * right_gain);
+            return Some(sample * left_gain);
+        }
+
+        // only stereo sounds are panned, other layouts just get the volume
+        let gain = match (channels, channel) {
+            (2, 0) => left_gain,
+            (2, _) => right_gain,
+            _ => self.gain.unwrap_or(1.0),
+        };
+        Some(sample * gain)
+    }
+}
+
+impl Source for Voice {
+    fn current_frame_len(&self) -> Option<usize> {
+        if self.is_upmixed() {
+            Some(self.frame_remaining * 2 + self.pending_right.is_some() as usize)
+        } else {
+            Some(self.frame_remaining)
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        if self.is_upmixed() {
+            2
+        } else {
+            self.source.channels()
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        // playing faster is the same as pretending the sample rate is higher, like rodio's speed does
+        ((self.source.sample_rate() as f32 * self.speed) as u32).max(1)
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Drop for Voice {
+    fn drop(&mut self) {
+        // the voice is dropped when it ends, is stopped or everything is dropped
+        self.controls.finished.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Controls a single sound after it started playing, returned by every play method on AudioHandle.
+/// clones control the same sound, and dropping every handle does not stop the sound
+#[derive(Clone)]
+pub struct SoundHandle {
+    controls: Arc<VoiceControls>,
+}
+
+impl SoundHandle {
+    /// a handle to a sound that was never played, for sounds that were held back by a voice limit
+    pub(crate) fn new_stopped() -> Self {
+        let controls = VoiceControls::new();
+        controls.stopped.store(true, Ordering::Relaxed);
+        controls.finished.store(true, Ordering::Relaxed);
+        Self {
+            controls: Arc::new(controls),
+        }
+    }
+
+    /// stops the sound for good
+    pub fn stop(&self) {
+        self.controls.stopped.store(true, Ordering::Relaxed);
+    }
+
+    pub fn pause(&self) {
+        self.controls.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.controls.paused.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.controls.paused.load(Ordering::Relaxed)
+    }
+
+    /// true until the sound ends or is stopped, a paused sound is still playing
+    pub fn is_playing(&self) -> bool {
+        !self.controls.finished.load(Ordering::Relaxed)
+            && !self.controls.stopped.load(Ordering::Relaxed)
+    }
+
+    /// jumps to the given time from the start of the sound
+    /// only AudioClips can seek, other sounds ignore this
+    pub fn seek(&self, position: Duration) {
+        if let Ok(mut seek_to) = self.controls.seek_to.lock() {
+            *seek_to = Some(position);
+        }
+    }
+
+    /// the volume of this sound alone, it is multiplied by the volume of its bus and the master volume
+    pub fn set_volume(&self, volume: f32) {
+        self.controls.volume.set(volume);
+    }
+
+    pub fn get_volume(&self) -> f32 {
+        self.controls.volume.get()
+    }
+
+    /// 1.0 is normal speed, this changes the pitch too
+    pub fn set_speed(&self, speed: f32) {
+        self.controls.speed.set(speed);
+    }
+
+    /// 1.0 is normal speed
+    pub fn get_speed(&self) -> f32 {
+        self.controls.speed.get()
+    }
+
+    /// -1.0 is fully left, 1.0 is fully right and 0.0 is the center
+    /// this is set every frame for sounds on an AudioEmitter
+    pub fn set_pan(&self, pan: f32) {
+        self.controls.pan.set(pan);
+    }
+
+    pub fn get_pan(&self) -> f32 {
+        self.controls.pan.get()
+    }
+
+    pub(crate) fn set_spatial_gain(&self, gain: f32) {
+        self.controls.spatial_gain.set(gain);
+    }
+
+    /// how loud the sound is before its bus, the volume times the volume from its AudioEmitter
+    pub(crate) fn get_loudness(&self) -> f32 {
+        self.controls.volume.get() * self.controls.spatial_gain.get()
+    }
+
+    /// true if both handles control the same sound, which is the case for clones
+    pub fn is_same_sound(&self, other: &SoundHandle) -> bool {
+        Arc::ptr_eq(&self.controls, &other.controls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AudioClip, AudioHandle, OfflineAudioBackend};
+
+    fn render(audio_handle: &mut AudioHandle, milliseconds: u64) -> Vec<f32> {
+        audio_handle
+            .get_backend_mut::<OfflineAudioBackend>()
+            .unwrap()
+            .render(Duration::from_millis(milliseconds))
+    }
+
+    #[test]
+    fn stopping_one_sound_leaves_the_others() {
+        let clip = AudioClip::from_samples(vec![0.5; 4410], 1, 44100);
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        let looping = audio_handle.play_infinitely(&clip);
+        let other = audio_handle.play_infinitely(&clip);
+
+        render(&mut audio_handle, 50);
+        looping.stop();
+        let after_stop = render(&mut audio_handle, 50);
+
+        assert!(!looping.is_playing());
+        assert!(other.is_playing());
+        assert!(after_stop.iter().any(|sample| *sample != 0.0));
+
+        other.stop();
+        let silent = render(&mut audio_handle, 50);
+        assert!(silent[silent.len() / 2..]
+            .iter()
+            .all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn paused_sounds_are_silent_but_still_playing() {
+        let clip = AudioClip::from_samples(vec![0.5; 44100], 1, 44100);
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        let sound = audio_handle.play_one_shot(&clip);
+
+        render(&mut audio_handle, 50);
+        sound.pause();
+        let paused = render(&mut audio_handle, 100);
+
+        assert!(sound.is_playing());
+        assert!(paused[paused.len() / 2..]
+            .iter()
+            .all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn panned_mono_sounds_play_on_one_side() {
+        let clip = AudioClip::from_samples(vec![0.5; 44100], 1, 44100);
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        let sound = audio_handle.play_one_shot(&clip);
+        sound.set_pan(1.0);
+
+        let samples = render(&mut audio_handle, 50);
+        let (left, right): (Vec<(usize, &f32)>, Vec<(usize, &f32)>) = samples
+            .iter()
+            .enumerate()
+            .partition(|(index, _)| index % 2 == 0);
+
+        assert!(left.iter().all(|(_, sample)| **sample == 0.0));
+        assert!(right.iter().any(|(_, sample)| **sample != 0.0));
+    }
+}
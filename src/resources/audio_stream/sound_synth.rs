@@ -0,0 +1,482 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rodio::Source;
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use super::{AudioClip, Playable, PlayableSource, SoundSource};
+
+const SYNTH_SAMPLE_RATE: u32 = 44100;
+
+/// the shape of the wave a SynthSound is made from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    /// the classic retro sound, the duty cycle changes how hollow it sounds
+    Square,
+    Sawtooth,
+    Sine,
+    /// a new random value every period, so the frequency still changes how it sounds
+    Noise,
+}
+
+/// the kinds of sounds SynthSound::from_preset can make
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SynthPreset {
+    Coin,
+    Jump,
+    Explosion,
+    Laser,
+    Blip,
+}
+
+/// A retro sound effect made from a few settings instead of a file, like sfxr.
+/// it can be played like any other sound, or turned into an AudioClip with to_clip so it is only made once
+#[derive(Clone, Debug, PartialEq)]
+pub struct SynthSound {
+    waveform: Waveform,
+    frequency: f32,
+    min_frequency: f32,
+    frequency_slide: f32,
+    frequency_slide_change: f32,
+    vibrato_depth: f32,
+    vibrato_speed: f32,
+    arpeggio: Option<(f32, Duration)>,
+    duty_cycle: f32,
+    duty_sweep: f32,
+    attack: Duration,
+    sustain: Duration,
+    punch: f32,
+    decay: Duration,
+    low_pass_cutoff: Option<f32>,
+    volume: f32,
+    seed: u64,
+}
+
+impl SynthSound {
+    /// a short square wave beep
+    pub fn new() -> Self {
+        Self {
+            waveform: Waveform::Square,
+            frequency: 440.0,
+            min_frequency: 20.0,
+            frequency_slide: 0.0,
+            frequency_slide_change: 0.0,
+            vibrato_depth: 0.0,
+            vibrato_speed: 0.0,
+            arpeggio: None,
+            duty_cycle: 0.5,
+            duty_sweep: 0.0,
+            attack: Duration::ZERO,
+            sustain: Duration::from_millis(100),
+            punch: 0.0,
+            decay: Duration::from_millis(100),
+            low_pass_cutoff: None,
+            volume: 0.5,
+            seed: 0,
+        }
+    }
+
+    /// A random sound of the given kind, the same seed always makes the same sound.
+    /// the builder methods can be used after this to change the sound
+    pub fn from_preset(preset: SynthPreset, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut sound = Self::new().with_seed(seed);
+
+        match preset {
+            SynthPreset::Coin => {
+                sound.waveform = if rng.gen_bool(0.5) {
+                    Waveform::Square
+                } else {
+                    Waveform::Sawtooth
+                };
+                sound.frequency = rng.gen_range(600.0..1600.0);
+                sound.sustain = Duration::from_secs_f32(rng.gen_range(0.02..0.1));
+                sound.decay = Duration::from_secs_f32(rng.gen_range(0.1..0.4));
+                sound.punch = rng.gen_range(0.3..0.6);
+                if rng.gen_bool(0.5) {
+                    sound.arpeggio = Some((
+                        rng.gen_range(1.3..1.6),
+                        Duration::from_secs_f32(rng.gen_range(0.04..0.1)),
+                    ));
+                }
+            }
+            SynthPreset::Jump => {
+                sound.waveform = Waveform::Square;
+                sound.duty_cycle = rng.gen_range(0.2..0.6);
+                sound.frequency = rng.gen_range(250.0..600.0);
+                sound.frequency_slide = rng.gen_range(2.0..5.0);
+                sound.sustain = Duration::from_secs_f32(rng.gen_range(0.05..0.15));
+                sound.decay = Duration::from_secs_f32(rng.gen_range(0.1..0.25));
+                if rng.gen_bool(0.5) {
+                    sound.low_pass_cutoff = Some(rng.gen_range(2000.0..6000.0));
+                }
+            }
+            SynthPreset::Explosion => {
+                sound.waveform = Waveform::Noise;
+                sound.frequency = rng.gen_range(200.0..2000.0);
+                sound.frequency_slide = rng.gen_range(-3.0..-0.5);
+                sound.sustain = Duration::from_secs_f32(rng.gen_range(0.1..0.3));
+                sound.decay = Duration::from_secs_f32(rng.gen_range(0.3..0.8));
+                sound.punch = rng.gen_range(0.2..0.7);
+                if rng.gen_bool(0.5) {
+                    sound.vibrato_depth = rng.gen_range(0.1..0.4);
+                    sound.vibrato_speed = rng.gen_range(5.0..20.0);
+                }
+            }
+            SynthPreset::Laser => {
+                sound.waveform = match rng.gen_range(0..3) {
+                    0 => Waveform::Square,
+                    1 => Waveform::Sawtooth,
+                    _ => Waveform::Sine,
+                };
+                sound.duty_cycle = rng.gen_range(0.2..0.5);
+                sound.duty_sweep = rng.gen_range(0.0..1.0);
+                sound.frequency = rng.gen_range(800.0..2500.0);
+                sound.min_frequency = rng.gen_range(80.0..300.0);
+                sound.frequency_slide = rng.gen_range(-8.0..-3.0);
+                sound.frequency_slide_change = rng.gen_range(-4.0..4.0);
+                sound.sustain = Duration::from_secs_f32(rng.gen_range(0.05..0.15));
+                sound.decay = Duration::from_secs_f32(rng.gen_range(0.05..0.25));
+                if rng.gen_bool(0.5) {
+                    sound.punch = rng.gen_range(0.0..0.3);
+                }
+            }
+            SynthPreset::Blip => {
+                sound.waveform = if rng.gen_bool(0.5) {
+                    Waveform::Square
+                } else {
+                    Waveform::Sawtooth
+                };
+                sound.duty_cycle = rng.gen_range(0.2..0.6);
+                sound.frequency = rng.gen_range(500.0..1500.0);
+                sound.sustain = Duration::from_secs_f32(rng.gen_range(0.03..0.08));
+                sound.decay = Duration::from_secs_f32(rng.gen_range(0.01..0.05));
+                if rng.gen_bool(0.3) {
+                    sound.low_pass_cutoff = Some(rng.gen_range(3000.0..8000.0));
+                }
+            }
+        }
+
+        sound
+    }
+
+    pub fn with_waveform(mut self, waveform: Waveform) -> Self {
+        self.waveform = waveform;
+        self
+    }
+
+    /// the frequency in hertz at the start of the sound
+    pub fn with_frequency(mut self, frequency: f32) -> Self {
+        self.frequency = frequency;
+        self
+    }
+
+    /// the frequency doesn't slide below this
+    pub fn with_min_frequency(mut self, min_frequency: f32) -> Self {
+        self.min_frequency = min_frequency;
+        self
+    }
+
+    /// how many octaves per second the frequency goes up, negative values make it go down
+    pub fn with_frequency_slide(mut self, frequency_slide: f32) -> Self {
+        self.frequency_slide = frequency_slide;
+        self
+    }
+
+    /// how much the slide changes every second, in octaves per second
+    pub fn with_frequency_slide_change(mut self, frequency_slide_change: f32) -> Self {
+        self.frequency_slide_change = frequency_slide_change;
+        self
+    }
+
+    /// wobbles the frequency, the depth is a fraction of the frequency and the speed is in hertz
+    pub fn with_vibrato(mut self, depth: f32, speed: f32) -> Self {
+        self.vibrato_depth = depth;
+        self.vibrato_speed = speed;
+        self
+    }
+
+    /// multiplies the frequency once the time has passed, this is the jump in pitch of a coin sound
+    pub fn with_arpeggio(mut self, multiplier: f32, time: Duration) -> Self {
+        self.arpeggio = Some((multiplier, time));
+        self
+    }
+
+    /// how much of each period a square wave is high, 0.5 is a normal square wave
+    pub fn with_duty_cycle(mut self, duty_cycle: f32) -> Self {
+        self.duty_cycle = duty_cycle;
+        self
+    }
+
+    /// how much the duty cycle changes every second
+    pub fn with_duty_sweep(mut self, duty_sweep: f32) -> Self {
+        self.duty_sweep = duty_sweep;
+        self
+    }
+
+    /// the sound fades in over the attack, stays at full volume for the sustain and fades out over the decay
+    pub fn with_envelope(mut self, attack: Duration, sustain: Duration, decay: Duration) -> Self {
+        self.attack = attack;
+        self.sustain = sustain;
+        self.decay = decay;
+        self
+    }
+
+    /// how much louder the start of the sustain is, it fades back to full volume over the sustain
+    pub fn with_punch(mut self, punch: f32) -> Self {
+        self.punch = punch;
+        self
+    }
+
+    /// takes the frequencies above the cutoff in hertz out of the sound
+    pub fn with_low_pass(mut self, cutoff: f32) -> Self {
+        self.low_pass_cutoff = Some(cutoff);
+        self
+    }
+
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    /// the seed of the noise, the same seed always makes the same noise
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn get_waveform(&self) -> Waveform {
+        self.waveform
+    }
+
+    pub fn get_frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    pub fn get_volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// how long the sound plays for, the attack, sustain and decay together
+    pub fn get_duration(&self) -> Duration {
+        self.attack + self.sustain + self.decay
+    }
+
+    pub fn to_source(&self) -> SynthSource {
+        SynthSource::new(self.clone())
+    }
+
+    /// makes the sound once and keeps the samples, so playing it again doesn't make it again
+    pub fn to_clip(&self) -> AudioClip {
+        AudioClip::from_source(self.to_source())
+    }
+}
+
+impl Default for SynthSound {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Playable for SynthSound {
+    fn into_source(self) -> PlayableSource {
+        Box::new(SynthSource::new(self))
+    }
+}
+
+impl Playable for &SynthSound {
+    fn into_source(self) -> PlayableSource {
+        Box::new(self.to_source())
+    }
+}
+
+/// makes the samples of a SynthSound as it is played
+pub struct SynthSource {
+    sound: SynthSound,
+    rng: StdRng,
+    position: usize,
+    length: usize,
+    // how far through the current period of the wave, from 0 to 1
+    phase: f32,
+    noise: f32,
+    filtered: f32,
+}
+
+impl SynthSource {
+    fn new(sound: SynthSound) -> Self {
+        let length =
+            (sound.get_duration().as_secs_f64() * SYNTH_SAMPLE_RATE as f64).round() as usize;
+        let mut rng = StdRng::seed_from_u64(sound.seed);
+        Self {
+            noise: rng.gen_range(-1.0..1.0),
+            rng,
+            sound,
+            position: 0,
+            length,
+            phase: 0.0,
+            filtered: 0.0,
+        }
+    }
+
+    fn get_frequency(&self, time: f32) -> f32 {
+        let sound = &self.sound;
+        let octaves =
+            sound.frequency_slide * time + sound.frequency_slide_change * time * time / 2.0;
+        let mut frequency = (sound.frequency * 2f32.powf(octaves)).max(sound.min_frequency);
+
+        if let Some((multiplier, arpeggio_time)) = sound.arpeggio {
+            if time >= arpeggio_time.as_secs_f32() {
+                frequency *= multiplier;
+            }
+        }
+        frequency *= 1.0 + sound.vibrato_depth * (TAU * sound.vibrato_speed * time).sin();
+
+        frequency.clamp(1.0, SYNTH_SAMPLE_RATE as f32 / 2.0)
+    }
+
+    fn get_envelope(&self, time: f32) -> f32 {
+        let sound = &self.sound;
+        let attack = sound.attack.as_secs_f32();
+        let sustain = sound.sustain.as_secs_f32();
+        let decay = sound.decay.as_secs_f32();
+
+        if time < attack {
+            time / attack
+        } else if time < attack + sustain {
+            1.0 + sound.punch * (1.0 - (time - attack) / sustain)
+        } else if decay > 0.0 {
+            (1.0 - (time - attack - sustain) / decay).max(0.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Iterator for SynthSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.position >= self.length {
+            return None;
+        }
+
+        let time = self.position as f32 / SYNTH_SAMPLE_RATE as f32;
+        self.position += 1;
+
+        let sample = match self.sound.waveform {
+            Waveform::Square => {
+                let duty_cycle =
+                    (self.sound.duty_cycle + self.sound.duty_sweep * time).clamp(0.05, 0.95);
+                if self.phase < duty_cycle {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sawtooth => 1.0 - 2.0 * self.phase,
+            Waveform::Sine => (TAU * self.phase).sin(),
+            Waveform::Noise => self.noise,
+        };
+
+        self.phase += self.get_frequency(time) / SYNTH_SAMPLE_RATE as f32;
+        if self.phase >= 1.0 {
+            self.phase %= 1.0;
+            self.noise = self.rng.gen_range(-1.0..1.0);
+        }
+
+        let sample = match self.sound.low_pass_cutoff {
+            Some(cutoff) => {
+                let amount = 1.0 - (-TAU * cutoff / SYNTH_SAMPLE_RATE as f32).exp();
+                self.filtered += (sample - self.filtered) * amount;
+                self.filtered
+            }
+            None => sample,
+        };
+
+        Some((sample * self.get_envelope(time) * self.sound.volume).clamp(-1.0, 1.0))
+    }
+}
+
+impl Source for SynthSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.length - self.position)
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SYNTH_SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(self.sound.get_duration())
+    }
+}
+
+impl SoundSource for SynthSource {
+    fn seek(&mut self, position: Duration) -> bool {
+        // the wave depends on everything before it, so the sound is made again up to the position
+        let target = (position.as_secs_f64() * SYNTH_SAMPLE_RATE as f64) as usize;
+        if target < self.position {
+            *self = SynthSource::new(self.sound.clone());
+        }
+        while self.position < target.min(self.length) {
+            self.next();
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AudioHandle, OfflineAudioBackend};
+
+    #[test]
+    fn presets_make_the_same_sound_from_the_same_seed() {
+        for preset in [
+            SynthPreset::Coin,
+            SynthPreset::Jump,
+            SynthPreset::Explosion,
+            SynthPreset::Laser,
+            SynthPreset::Blip,
+        ] {
+            let clip = SynthSound::from_preset(preset, 7).to_clip();
+            let same = SynthSound::from_preset(preset, 7).to_clip();
+            let other = SynthSound::from_preset(preset, 8).to_clip();
+
+            assert_eq!(clip.get_samples(), same.get_samples());
+            assert_ne!(clip.get_samples(), other.get_samples());
+            assert!(clip.get_samples().iter().any(|sample| sample.abs() > 0.1));
+            assert!(clip.get_samples().iter().all(|sample| sample.abs() <= 1.0));
+        }
+    }
+
+    #[test]
+    fn synth_sounds_play_like_any_other_sound() {
+        let sound = SynthSound::new()
+            .with_waveform(Waveform::Sine)
+            .with_envelope(Duration::ZERO, Duration::from_millis(50), Duration::ZERO);
+        assert_eq!(sound.to_clip().get_samples().len(), 2205);
+
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        audio_handle.set_master_volume(10.0);
+        audio_handle.play_one_shot(&sound);
+        let samples = audio_handle
+            .get_backend_mut::<OfflineAudioBackend>()
+            .unwrap()
+            .render(Duration::from_millis(100));
+
+        assert!(samples.iter().any(|sample| sample.abs() > 0.1));
+        // the sound is over after 50 milliseconds
+        assert!(samples[samples.len() - 100..]
+            .iter()
+            .all(|sample| *sample == 0.0));
+    }
+}
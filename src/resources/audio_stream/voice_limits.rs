@@ -0,0 +1,339 @@
+use fxhash::FxHashMap;
+use std::time::Duration;
+
+use super::{AudioClip, SoundHandle};
+
+/// which sound is stopped to make room for a new one when there are too many playing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VoiceStealing {
+    /// stops the sound that started first
+    Oldest,
+    /// stops the sound with the lowest volume
+    Quietest,
+    /// stops the sound with the lowest priority, the new sound isn't played if every other sound has a higher priority
+    LowestPriority,
+}
+
+/// how many sounds can play at once, for a clip, a bus or everything
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VoiceLimit {
+    max_voices: usize,
+    stealing: VoiceStealing,
+}
+
+impl VoiceLimit {
+    /// by default the oldest sound is stopped to make room for a new one
+    pub fn new(max_voices: usize) -> Self {
+        Self {
+            max_voices,
+            stealing: VoiceStealing::Oldest,
+        }
+    }
+
+    pub fn with_stealing(mut self, stealing: VoiceStealing) -> Self {
+        self.stealing = stealing;
+        self
+    }
+
+    pub fn get_max_voices(&self) -> usize {
+        self.max_voices
+    }
+
+    pub fn get_stealing(&self) -> VoiceStealing {
+        self.stealing
+    }
+}
+
+/// what is set for a clip, the clip is kept so its id isn't reused by another clip
+struct ClipSettings {
+    _clip: AudioClip,
+    limit: Option<VoiceLimit>,
+    priority: i32,
+    cooldown: Duration,
+    last_played: Option<f64>,
+}
+
+impl ClipSettings {
+    fn new(clip: &AudioClip) -> Self {
+        Self {
+            _clip: clip.clone(),
+            limit: None,
+            priority: 0,
+            cooldown: Duration::ZERO,
+            last_played: None,
+        }
+    }
+}
+
+struct ActiveVoice {
+    sound: SoundHandle,
+    clip_id: Option<usize>,
+    bus: Option<String>,
+    priority: i32,
+}
+
+/// which of the playing sounds a limit counts
+#[derive(Clone, Copy)]
+enum VoiceGroup<'a> {
+    Clip(usize),
+    Bus(&'a str),
+    All,
+}
+
+impl VoiceGroup<'_> {
+    fn contains(&self, voice: &ActiveVoice) -> bool {
+        match self {
+            VoiceGroup::Clip(id) => voice.clip_id == Some(*id),
+            VoiceGroup::Bus(name) => voice.bus.as_deref() == Some(*name),
+            VoiceGroup::All => true,
+        }
+    }
+}
+
+/// Keeps track of the sounds played by the AudioHandle so they can be limited.
+/// the music of the MusicPlayer doesn't count towards any limit
+#[derive(Default)]
+pub(crate) struct VoiceLimiter {
+    limit: Option<VoiceLimit>,
+    clips: FxHashMap<usize, ClipSettings>,
+    // in the order they were played, so the first one is the oldest
+    voices: Vec<ActiveVoice>,
+    // real time in seconds, cooldowns keep counting while the game is paused
+    time: f64,
+}
+
+impl VoiceLimiter {
+    pub(crate) fn set_limit(&mut self, limit: Option<VoiceLimit>) {
+        self.limit = limit;
+    }
+
+    pub(crate) fn get_limit(&self) -> Option<VoiceLimit> {
+        self.limit
+    }
+
+    fn clip_settings_mut(&mut self, clip: &AudioClip) -> &mut ClipSettings {
+        self.clips
+            .entry(clip.get_id())
+            .or_insert_with(|| ClipSettings::new(clip))
+    }
+
+    pub(crate) fn set_clip_limit(&mut self, clip: &AudioClip, limit: Option<VoiceLimit>) {
+        self.clip_settings_mut(clip).limit = limit;
+    }
+
+    pub(crate) fn set_clip_priority(&mut self, clip: &AudioClip, priority: i32) {
+        self.clip_settings_mut(clip).priority = priority;
+    }
+
+    pub(crate) fn set_clip_cooldown(&mut self, clip: &AudioClip, cooldown: Duration) {
+        self.clip_settings_mut(clip).cooldown = cooldown;
+    }
+
+    pub(crate) fn get_voice_count(&self) -> usize {
+        self.voices
+            .iter()
+            .filter(|voice| voice.sound.is_playing())
+            .count()
+    }
+
+    pub(crate) fn update(&mut self, delta_time: f64) {
+        self.time += delta_time.max(0.0);
+        self.voices.retain(|voice| voice.sound.is_playing());
+    }
+
+    /// Stops sounds until the new sound fits in every limit it is under.
+    /// returns false without stopping anything if the new sound shouldn't be played
+    pub(crate) fn make_room(
+        &mut self,
+        clip: Option<&AudioClip>,
+        bus: Option<&str>,
+        bus_limit: Option<VoiceLimit>,
+    ) -> bool {
+        self.voices.retain(|voice| voice.sound.is_playing());
+
+        let clip_id = clip.map(AudioClip::get_id);
+        let settings = clip_id.and_then(|id| self.clips.get(&id));
+        let priority = settings.map_or(0, |settings| settings.priority);
+
+        if let Some(settings) = settings {
+            let cooling_down = settings.last_played.is_some_and(|last_played| {
+                self.time - last_played < settings.cooldown.as_secs_f64()
+            });
+            if cooling_down {
+                return false;
+            }
+        }
+
+        // the smallest group first, so a sound stopped for the clip also makes room on the bus and overall
+        let limits = [
+            (
+                settings.and_then(|settings| settings.limit),
+                clip_id.map(VoiceGroup::Clip),
+            ),
+            (bus_limit, bus.map(VoiceGroup::Bus)),
+            (self.limit, Some(VoiceGroup::All)),
+        ];
+
+        let mut stolen = vec![false; self.voices.len()];
+        for (limit, group) in limits {
+            let (Some(limit), Some(group)) = (limit, group) else {
+                continue;
+            };
+
+            let mut playing = self
+                .voices
+                .iter()
+                .enumerate()
+                .filter(|(index, voice)| !stolen[*index] && group.contains(voice))
+                .count();
+            while playing >= limit.max_voices {
+                match self.pick_voice_to_steal(limit.stealing, group, &stolen, priority) {
+                    Some(index) => stolen[index] = true,
+                    None => return false,
+                }
+                playing -= 1;
+            }
+        }
+
+        for (voice, stolen) in self.voices.iter().zip(&stolen) {
+            if *stolen {
+                voice.sound.stop();
+            }
+        }
+        let mut stolen = stolen.into_iter();
+        self.voices.retain(|_| !stolen.next().unwrap_or(false));
+        true
+    }
+
+    /// the index of the sound to stop, the oldest sound is picked when sounds are tied
+    fn pick_voice_to_steal(
+        &self,
+        stealing: VoiceStealing,
+        group: VoiceGroup,
+        stolen: &[bool],
+        priority: i32,
+    ) -> Option<usize> {
+        let mut candidates = self
+            .voices
+            .iter()
+            .enumerate()
+            .filter(|(index, voice)| !stolen[*index] && group.contains(voice));
+
+        match stealing {
+            VoiceStealing::Oldest => candidates.next().map(|(index, _)| index),
+            VoiceStealing::Quietest => candidates
+                .min_by(|(a_index, a), (b_index, b)| {
+                    a.sound
+                        .get_loudness()
+                        .total_cmp(&b.sound.get_loudness())
+                        .then(a_index.cmp(b_index))
+                })
+                .map(|(index, _)| index),
+            VoiceStealing::LowestPriority => candidates
+                .min_by_key(|(index, voice)| (voice.priority, *index))
+                .filter(|(_, voice)| voice.priority <= priority)
+                .map(|(index, _)| index),
+        }
+    }
+
+    /// keeps track of a sound that was just played
+    pub(crate) fn add(&mut self, sound: SoundHandle, clip: Option<&AudioClip>, bus: Option<&str>) {
+        let clip_id = clip.map(AudioClip::get_id);
+        let mut priority = 0;
+        if let Some(settings) = clip_id.and_then(|id| self.clips.get_mut(&id)) {
+            settings.last_played = Some(self.time);
+            priority = settings.priority;
+        }
+
+        self.voices.push(ActiveVoice {
+            sound,
+            clip_id,
+            bus: bus.map(str::to_string),
+            priority,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VoiceLimit, VoiceStealing};
+    use crate::{AudioBus, AudioClip, AudioHandle, OfflineAudioBackend};
+    use std::time::Duration;
+
+    fn clip() -> AudioClip {
+        AudioClip::from_samples(vec![0.5; 4410], 1, 44100)
+    }
+
+    #[test]
+    fn limits_steal_the_right_sound() {
+        let shot = clip();
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        audio_handle.set_clip_voice_limit(&shot, Some(VoiceLimit::new(2)));
+
+        let first = audio_handle.play_one_shot(&shot);
+        let second = audio_handle.play_one_shot(&shot);
+        let third = audio_handle.play_one_shot(&shot);
+        assert!(!first.is_playing());
+        assert!(second.is_playing() && third.is_playing());
+
+        // other clips don't count towards the limit of the clip
+        let other = audio_handle.play_one_shot(&clip());
+        assert!(other.is_playing());
+        assert_eq!(audio_handle.get_voice_count(), 3);
+
+        audio_handle.set_clip_voice_limit(
+            &shot,
+            Some(VoiceLimit::new(2).with_stealing(VoiceStealing::Quietest)),
+        );
+        second.set_volume(0.5);
+        let fourth = audio_handle.play_one_shot(&shot);
+        assert!(!second.is_playing());
+        assert!(third.is_playing() && fourth.is_playing());
+    }
+
+    #[test]
+    fn low_priority_sounds_make_room_for_high_priority_sounds() {
+        let footstep = clip();
+        let explosion = clip();
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        audio_handle.add_bus(
+            AudioBus::new("sfx")
+                .with_voice_limit(VoiceLimit::new(2).with_stealing(VoiceStealing::LowestPriority)),
+        );
+        audio_handle.set_clip_priority(&footstep, -1);
+        audio_handle.set_clip_priority(&explosion, 1);
+
+        let step = audio_handle.play_sound_on_bus(&footstep, "sfx");
+        let boom = audio_handle.play_sound_on_bus(&explosion, "sfx");
+        let second_boom = audio_handle.play_sound_on_bus(&explosion, "sfx");
+        assert!(!step.is_playing());
+        assert!(boom.is_playing() && second_boom.is_playing());
+
+        // a footstep isn't important enough to stop an explosion
+        let second_step = audio_handle.play_sound_on_bus(&footstep, "sfx");
+        assert!(!second_step.is_playing());
+        assert!(boom.is_playing() && second_boom.is_playing());
+
+        // sounds that aren't on the bus don't count towards its limit
+        assert!(audio_handle.play_one_shot(&footstep).is_playing());
+
+        audio_handle.set_voice_limit(Some(VoiceLimit::new(1)));
+        let last = audio_handle.play_one_shot(&clip());
+        assert!(last.is_playing());
+        assert_eq!(audio_handle.get_voice_count(), 1);
+    }
+
+    #[test]
+    fn cooldowns_throttle_repeats() {
+        let shot = clip();
+        let mut audio_handle = AudioHandle::new_with_backend(OfflineAudioBackend::new());
+        audio_handle.set_clip_cooldown(&shot, Duration::from_millis(100));
+
+        assert!(audio_handle.play_one_shot(&shot).is_playing());
+        assert!(!audio_handle.play_one_shot(&shot).is_playing());
+        assert!(audio_handle.play_one_shot(&clip()).is_playing());
+
+        audio_handle.update_voices(0.1);
+        assert!(audio_handle.play_one_shot(&shot).is_playing());
+    }
+}
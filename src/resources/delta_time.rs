@@ -40,6 +40,11 @@ impl DeltaTime {
         (self.delta_time * self.time_scale) + self.correctional_delta_time
     }
 
+    /// the time the last frame took, ignoring the time scale
+    pub fn get_unscaled_delta_time(&self) -> f64 {
+        self.delta_time
+    }
+
     pub fn get_total_time(&self) -> f64 {
         self.total_time
     }